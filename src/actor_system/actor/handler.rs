@@ -3,7 +3,8 @@
 use std::marker::PhantomData;
 
 use async_trait::async_trait;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, mpsc::error::TrySendError, oneshot};
+use tokio::time::Duration;
 
 use crate::actor_system::{
     actor::{ActorContext, Handler, Message},
@@ -15,6 +16,9 @@ use super::Actor;
 #[async_trait]
 pub trait MessageHandler<E: SystemEvent, A: Actor<E>>: Send + Sync {
     async fn handle(&mut self, actor: &mut A, ctx: &mut ActorContext<E>);
+
+    /// Type name of the wrapped message, for diagnostics.
+    fn name(&self) -> &'static str;
 }
 
 pub(crate) struct ActorMessage<M, E, A>
@@ -45,6 +49,10 @@ where
             })
         }
     }
+
+    fn name(&self) -> &'static str {
+        std::any::type_name::<M>()
+    }
 }
 
 impl<M, E, A> ActorMessage<M, E, A>
@@ -64,8 +72,109 @@ where
 }
 
 pub type BoxedMessageHandler<E, A> = Box<dyn MessageHandler<E, A>>;
-pub type MailboxReceiver<E, A> = mpsc::UnboundedReceiver<BoxedMessageHandler<E, A>>;
-pub type MailboxSender<E, A> = mpsc::UnboundedSender<BoxedMessageHandler<E, A>>;
+
+/// Sending half of an actor mailbox, either unbounded or with a fixed capacity.
+pub enum MailboxSender<E: SystemEvent, A: Actor<E>> {
+    Unbounded(mpsc::UnboundedSender<BoxedMessageHandler<E, A>>),
+    Bounded(mpsc::Sender<BoxedMessageHandler<E, A>>),
+}
+
+impl<E: SystemEvent, A: Actor<E>> Clone for MailboxSender<E, A> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Unbounded(sender) => Self::Unbounded(sender.clone()),
+            Self::Bounded(sender) => Self::Bounded(sender.clone()),
+        }
+    }
+}
+
+impl<E: SystemEvent, A: Actor<E>> MailboxSender<E, A> {
+    /// Enqueues without waiting, failing with `Full` if a bounded mailbox has no capacity.
+    pub fn try_send(
+        &self,
+        msg: BoxedMessageHandler<E, A>,
+    ) -> Result<(), TrySendError<BoxedMessageHandler<E, A>>> {
+        match self {
+            Self::Unbounded(sender) => sender
+                .send(msg)
+                .map_err(|error| TrySendError::Closed(error.0)),
+            Self::Bounded(sender) => sender.try_send(msg),
+        }
+    }
+
+    /// Enqueues, waiting up to `timeout` for a bounded mailbox to free up capacity.
+    pub async fn send(
+        &self,
+        msg: BoxedMessageHandler<E, A>,
+        timeout: Option<Duration>,
+    ) -> Result<(), TrySendError<BoxedMessageHandler<E, A>>> {
+        let sender = match self {
+            Self::Unbounded(_) => return self.try_send(msg),
+            Self::Bounded(sender) => sender,
+        };
+
+        let permit = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, sender.reserve()).await {
+                Ok(permit) => permit,
+                Err(_elapsed) => return Err(TrySendError::Full(msg)),
+            },
+            None => sender.reserve().await,
+        };
+
+        match permit {
+            Ok(permit) => {
+                permit.send(msg);
+                Ok(())
+            }
+            Err(_closed) => Err(TrySendError::Closed(msg)),
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        match self {
+            Self::Unbounded(sender) => sender.is_closed(),
+            Self::Bounded(sender) => sender.is_closed(),
+        }
+    }
+
+    /// Resolves once the actor's receiver is closed or dropped.
+    pub async fn closed(&self) {
+        match self {
+            Self::Unbounded(sender) => sender.closed().await,
+            Self::Bounded(sender) => sender.closed().await,
+        }
+    }
+
+    /// Checks if a bounded mailbox has no capacity left. Unbounded mailboxes are never full.
+    pub fn is_full(&self) -> bool {
+        match self {
+            Self::Unbounded(_) => false,
+            Self::Bounded(sender) => sender.capacity() == 0,
+        }
+    }
+}
+
+/// Receiving half of an actor mailbox.
+pub enum MailboxReceiver<E: SystemEvent, A: Actor<E>> {
+    Unbounded(mpsc::UnboundedReceiver<BoxedMessageHandler<E, A>>),
+    Bounded(mpsc::Receiver<BoxedMessageHandler<E, A>>),
+}
+
+impl<E: SystemEvent, A: Actor<E>> MailboxReceiver<E, A> {
+    pub async fn recv(&mut self) -> Option<BoxedMessageHandler<E, A>> {
+        match self {
+            Self::Unbounded(receiver) => receiver.recv().await,
+            Self::Bounded(receiver) => receiver.recv().await,
+        }
+    }
+
+    pub fn close(&mut self) {
+        match self {
+            Self::Unbounded(receiver) => receiver.close(),
+            Self::Bounded(receiver) => receiver.close(),
+        }
+    }
+}
 
 pub struct ActorMailbox<E: SystemEvent, A: Actor<E>> {
     _phantom_actor: PhantomData<A>,
@@ -73,7 +182,23 @@ pub struct ActorMailbox<E: SystemEvent, A: Actor<E>> {
 }
 
 impl<E: SystemEvent, A: Actor<E>> ActorMailbox<E, A> {
-    pub fn create() -> (MailboxSender<E, A>, MailboxReceiver<E, A>) {
-        mpsc::unbounded_channel()
+    /// Creates a bounded mailbox when a capacity is given, otherwise an unbounded one.
+    pub fn create(capacity: Option<usize>) -> (MailboxSender<E, A>, MailboxReceiver<E, A>) {
+        match capacity {
+            Some(capacity) => {
+                let (sender, receiver) = mpsc::channel(capacity.max(1));
+                (
+                    MailboxSender::Bounded(sender),
+                    MailboxReceiver::Bounded(receiver),
+                )
+            }
+            None => {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                (
+                    MailboxSender::Unbounded(sender),
+                    MailboxReceiver::Unbounded(receiver),
+                )
+            }
+        }
     }
 }
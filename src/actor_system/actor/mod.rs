@@ -6,10 +6,13 @@ pub(crate) mod handler;
 pub(crate) mod runner;
 pub(crate) mod supervision;
 
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use async_trait::async_trait;
 use thiserror::Error;
 
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc::error::TrySendError, oneshot};
 use tokio::time::Duration;
 
 mod path;
@@ -17,16 +20,24 @@ pub use path::ActorPath;
 
 use supervision::SupervisionStrategy;
 
-use crate::actor_system::system::{ActorSystem, SystemEvent};
+use crate::actor_system::bus::EventBus;
+use crate::actor_system::system::{ActorSystem, DeadLetter, SystemEvent};
 
 /// The actor context gives a running actor access to its path and the system.
 #[derive(Debug)]
 pub struct ActorContext<E: SystemEvent> {
     pub path: ActorPath,
     pub system: ActorSystem<E>,
+    stopping: bool,
 }
 
 impl<E: SystemEvent> ActorContext<E> {
+    /// Stops this actor once the current message has been handled. `post_stop` still runs;
+    /// anything left in the mailbox is dropped.
+    pub fn stop_self(&mut self) {
+        self.stopping = true;
+    }
+
     /// Create a child actor under this actor.
     pub async fn create_child<A: Actor<E>>(
         &self,
@@ -84,11 +95,31 @@ pub trait Message: Clone + Send + Sync + 'static {
 /// Basic trait for actors.
 #[async_trait]
 pub trait Actor<E: SystemEvent>: Send + Sync + 'static {
-    /// Defines the timeout to set for this actor.
+    /// Defines the timeout to set for this actor, shared by every instance of the type.
+    ///
+    /// `ActorRef` has no actor instance to ask, so this alone bounds how long a send waits
+    /// for room in a bounded mailbox. It is also the default for `idle_timeout`.
     fn timeout() -> Option<Duration> {
         None
     }
 
+    /// How long the actor may go without a message before it is stopped. Defaults to
+    /// `timeout()`.
+    ///
+    /// The runner only consults this one for idle expiry, so an override wins over
+    /// `timeout()` there. Override it when the limit comes from the actor's own state,
+    /// such as its config, which the associated `timeout()` cannot read.
+    fn idle_timeout(&self) -> Option<Duration> {
+        Self::timeout()
+    }
+
+    /// Defines the mailbox capacity for this actor. `None` means unbounded.
+    ///
+    /// Read once when the actor is created, so it can come from the actor's own config.
+    fn mailbox_capacity(&self) -> Option<usize> {
+        None
+    }
+
     /// Defines the supervision strategy to use for this actor.
     fn supervision_strategy() -> SupervisionStrategy {
         SupervisionStrategy::Stop
@@ -108,6 +139,10 @@ pub trait Actor<E: SystemEvent>: Send + Sync + 'static {
         self.pre_start(ctx).await
     }
 
+    /// Override this function to perform work when the actor is stopped for inactivity.
+    /// Runs before `post_stop`.
+    async fn on_timeout(&mut self, _ctx: &mut ActorContext<E>) {}
+
     /// Override this function to perform work when the actor is stopped.
     async fn post_stop(&mut self, _ctx: &mut ActorContext<E>) {}
 }
@@ -121,7 +156,9 @@ pub trait Handler<E: SystemEvent, M: Message>: Actor<E> {
 /// A clonable actor reference.
 pub struct ActorRef<E: SystemEvent, A: Actor<E>> {
     path: ActorPath,
-    sender: mpsc::UnboundedSender<handler::BoxedMessageHandler<E, A>>,
+    sender: handler::MailboxSender<E, A>,
+    mailbox_len: Arc<AtomicUsize>,
+    dead_letters: EventBus<DeadLetter>,
 }
 
 impl<E: SystemEvent, A: Actor<E>> Clone for ActorRef<E, A> {
@@ -129,6 +166,8 @@ impl<E: SystemEvent, A: Actor<E>> Clone for ActorRef<E, A> {
         Self {
             path: self.path.clone(),
             sender: self.sender.clone(),
+            mailbox_len: self.mailbox_len.clone(),
+            dead_letters: self.dead_letters.clone(),
         }
     }
 }
@@ -140,35 +179,115 @@ impl<E: SystemEvent, A: Actor<E>> ActorRef<E, A> {
     }
 
     /// Fire and forget sending of messages to this actor.
+    ///
+    /// Fails with `ActorError::MailboxFull` if the actor has a bounded mailbox with no capacity left.
     pub fn tell<M>(&self, msg: M) -> Result<(), ActorError>
     where
         M: Message,
         A: Handler<E, M>,
     {
         let message = handler::ActorMessage::<M, E, A>::new(msg, None);
-        if let Err(error) = self.sender.send(Box::new(message)) {
-            log::error!("Failed to tell message! {}", error.to_string());
-            Err(ActorError::SendError(error.to_string()))
+        self.mailbox_len.fetch_add(1, Ordering::SeqCst);
+        if let Err(error) = self.sender.try_send(Box::new(message)) {
+            self.mailbox_len.fetch_sub(1, Ordering::SeqCst);
+            log::error!("Failed to tell message! {}", error);
+            self.dead_letter::<M>();
+            Err(self.send_error(error))
         } else {
             Ok(())
         }
     }
 
     /// Send a message to an actor, expecting a response.
+    ///
+    /// With a bounded mailbox this waits for capacity up to the actor's `timeout()`.
     pub async fn ask<M>(&self, msg: M) -> Result<M::Response, ActorError>
+    where
+        M: Message,
+        A: Handler<E, M>,
+    {
+        self.enqueue_ask(msg)
+            .await?
+            .await
+            .map_err(|error| ActorError::SendError(error.to_string()))
+    }
+
+    /// Like `ask`, but gives up with `ActorError::Timeout` if no response arrives within
+    /// `timeout` of the message being queued.
+    ///
+    /// The actor still handles the message; only its response is discarded.
+    pub async fn ask_timeout<M>(&self, msg: M, timeout: Duration) -> Result<M::Response, ActorError>
+    where
+        M: Message,
+        A: Handler<E, M>,
+    {
+        let response_receiver = self.enqueue_ask(msg).await?;
+        match tokio::time::timeout(timeout, response_receiver).await {
+            Ok(response) => response.map_err(|error| ActorError::SendError(error.to_string())),
+            Err(_) => Err(ActorError::Timeout(self.path.clone())),
+        }
+    }
+
+    /// Like `ask`, but fails fast once the actor has stopped.
+    ///
+    /// Returns `ActorError::SendError` without queueing anything if the mailbox is already
+    /// closed, and gives up on the response as soon as the mailbox closes while waiting, so a
+    /// message dropped by a stopping actor can't leave the caller hanging.
+    pub async fn try_ask<M>(&self, msg: M) -> Result<M::Response, ActorError>
+    where
+        M: Message,
+        A: Handler<E, M>,
+    {
+        if self.is_closed() {
+            self.dead_letter::<M>();
+            return Err(ActorError::SendError("actor mailbox is closed".to_string()));
+        }
+
+        let response_receiver = self.enqueue_ask(msg).await?;
+        tokio::select! {
+            // A response sent just before the actor stopped still wins.
+            biased;
+            response = response_receiver => {
+                response.map_err(|error| ActorError::SendError(error.to_string()))
+            }
+            _ = self.sender.closed() => Err(ActorError::SendError(
+                "actor stopped before responding".to_string(),
+            )),
+        }
+    }
+
+    /// Queues `msg` with a response slot and returns the receiver its answer arrives on.
+    async fn enqueue_ask<M>(&self, msg: M) -> Result<oneshot::Receiver<M::Response>, ActorError>
     where
         M: Message,
         A: Handler<E, M>,
     {
         let (response_sender, response_receiver) = oneshot::channel();
         let message = handler::ActorMessage::<M, E, A>::new(msg, Some(response_sender));
-        if let Err(error) = self.sender.send(Box::new(message)) {
-            log::error!("Failed to ask message! {}", error.to_string());
-            Err(ActorError::SendError(error.to_string()))
+        self.mailbox_len.fetch_add(1, Ordering::SeqCst);
+        if let Err(error) = self.sender.send(Box::new(message), A::timeout()).await {
+            self.mailbox_len.fetch_sub(1, Ordering::SeqCst);
+            log::error!("Failed to ask message! {}", error);
+            self.dead_letter::<M>();
+            Err(self.send_error(error))
         } else {
-            response_receiver
-                .await
-                .map_err(|error| ActorError::SendError(error.to_string()))
+            Ok(response_receiver)
+        }
+    }
+
+    /// Publishes an undelivered message on the system's dead letter bus.
+    fn dead_letter<M: Message>(&self) {
+        // No subscribers is the normal case outside of debugging.
+        let _ = self.dead_letters.send(DeadLetter {
+            path: self.path.clone(),
+            message_type: std::any::type_name::<M>(),
+        });
+    }
+
+    fn send_error<T>(&self, error: TrySendError<T>) -> ActorError {
+        match error {
+            TrySendError::Full(_) => ActorError::MailboxFull(self.path.clone()),
+            TrySendError::Closed(_) => ActorError::SendError(error.to_string()),
         }
     }
 
@@ -177,8 +296,37 @@ impl<E: SystemEvent, A: Actor<E>> ActorRef<E, A> {
         self.sender.is_closed()
     }
 
-    pub(crate) fn new(path: ActorPath, sender: handler::MailboxSender<E, A>) -> Self {
-        ActorRef { path, sender }
+    /// Number of messages queued in the mailbox that have not been handled yet.
+    pub fn len(&self) -> usize {
+        self.mailbox_len.load(Ordering::SeqCst)
+    }
+
+    /// Checks if a bounded mailbox has no capacity left. Always false for unbounded mailboxes.
+    pub fn is_full(&self) -> bool {
+        self.sender.is_full()
+    }
+
+    /// Checks if the actor has no pending messages.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn new(
+        path: ActorPath,
+        sender: handler::MailboxSender<E, A>,
+        mailbox_len: Arc<AtomicUsize>,
+        dead_letters: EventBus<DeadLetter>,
+    ) -> Self {
+        ActorRef {
+            path,
+            sender,
+            mailbox_len,
+            dead_letters,
+        }
+    }
+
+    pub(crate) fn mailbox_len(&self) -> Arc<AtomicUsize> {
+        self.mailbox_len.clone()
     }
 }
 
@@ -199,8 +347,17 @@ pub enum ActorError {
     #[error("Sending message failed")]
     SendError(String),
 
+    #[error("Actor mailbox is full")]
+    MailboxFull(ActorPath),
+
     #[error("Actor runtime error")]
     RuntimeError(anyhow::Error),
+
+    #[error("Actor handler panicked: {0}")]
+    Panicked(String),
+
+    #[error("Actor did not respond in time")]
+    Timeout(ActorPath),
 }
 
 impl ActorError {
@@ -211,3 +368,258 @@ impl ActorError {
         Self::RuntimeError(anyhow::Error::new(error))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor_system::EventBus;
+
+    #[derive(Clone, Debug)]
+    struct TestEvent;
+
+    impl SystemEvent for TestEvent {}
+
+    struct CounterActor {
+        count: usize,
+    }
+
+    impl Actor<TestEvent> for CounterActor {}
+
+    #[derive(Clone, Debug)]
+    struct Increment;
+
+    impl Message for Increment {
+        type Response = usize;
+    }
+
+    #[async_trait]
+    impl Handler<TestEvent, Increment> for CounterActor {
+        async fn handle(&mut self, _msg: Increment, _ctx: &mut ActorContext<TestEvent>) -> usize {
+            self.count += 1;
+            self.count
+        }
+    }
+
+    struct BoundedActor;
+
+    impl Actor<TestEvent> for BoundedActor {
+        fn mailbox_capacity(&self) -> Option<usize> {
+            Some(2)
+        }
+    }
+
+    #[async_trait]
+    impl Handler<TestEvent, Increment> for BoundedActor {
+        async fn handle(&mut self, _msg: Increment, _ctx: &mut ActorContext<TestEvent>) -> usize {
+            0
+        }
+    }
+
+    struct SlowActor;
+
+    impl Actor<TestEvent> for SlowActor {}
+
+    #[async_trait]
+    impl Handler<TestEvent, Increment> for SlowActor {
+        async fn handle(&mut self, _msg: Increment, _ctx: &mut ActorContext<TestEvent>) -> usize {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            1
+        }
+    }
+
+    struct LifecycleActor;
+
+    #[async_trait]
+    impl Actor<TestEvent> for LifecycleActor {
+        async fn post_stop(&mut self, ctx: &mut ActorContext<TestEvent>) {
+            ctx.system.publish(TestEvent);
+        }
+    }
+
+    async fn counter() -> (ActorSystem<TestEvent>, ActorRef<TestEvent, CounterActor>) {
+        let system = ActorSystem::new("test", EventBus::new(16));
+        let actor_ref = system
+            .create_actor("counter", CounterActor { count: 0 })
+            .await
+            .unwrap();
+        (system, actor_ref)
+    }
+
+    #[tokio::test]
+    async fn test_mailbox_len_tracks_pending_messages() {
+        let (system, actor_ref) = counter().await;
+
+        for _ in 0..3 {
+            actor_ref.tell(Increment).unwrap();
+        }
+        assert_eq!(actor_ref.len(), 3);
+        assert_eq!(
+            system.mailbox_depths().await,
+            vec![(ActorPath::from("/user/counter"), 3)]
+        );
+
+        assert_eq!(actor_ref.ask(Increment).await.unwrap(), 4);
+        assert!(actor_ref.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mailbox_len_with_dropped_responder() {
+        let (_system, actor_ref) = counter().await;
+
+        actor_ref.tell(Increment).unwrap();
+        let mut abandoned = Box::pin(actor_ref.ask(Increment));
+        assert!(futures::poll!(&mut abandoned).is_pending());
+        drop(abandoned);
+        assert_eq!(actor_ref.len(), 2);
+
+        assert_eq!(actor_ref.ask(Increment).await.unwrap(), 3);
+        assert!(actor_ref.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bounded_mailbox_rejects_when_full() {
+        let system = ActorSystem::new("test", EventBus::new(16));
+        let actor_ref = system.create_actor("bounded", BoundedActor).await.unwrap();
+
+        actor_ref.tell(Increment).unwrap();
+        actor_ref.tell(Increment).unwrap();
+        assert!(actor_ref.is_full());
+
+        let error = actor_ref.tell(Increment).unwrap_err();
+        assert!(matches!(error, ActorError::MailboxFull(_)));
+        assert_eq!(actor_ref.len(), 2);
+
+        // Once the runner drains the mailbox there is room again.
+        assert_eq!(actor_ref.ask(Increment).await.unwrap(), 0);
+        assert!(!actor_ref.is_full());
+    }
+
+    #[tokio::test]
+    async fn test_unbounded_mailbox_is_never_full() {
+        let (_system, actor_ref) = counter().await;
+        for _ in 0..100 {
+            actor_ref.tell(Increment).unwrap();
+        }
+        assert!(!actor_ref.is_full());
+    }
+
+    #[tokio::test]
+    async fn test_stop_all_runs_post_stop_with_live_refs() {
+        let system = ActorSystem::new("test", EventBus::new(16));
+        let mut events = system.events();
+        let first = system.create_actor("first", LifecycleActor).await.unwrap();
+        let second = system.create_actor("second", LifecycleActor).await.unwrap();
+
+        system.stop_all().await;
+
+        assert!(events.try_recv().is_ok());
+        assert!(events.try_recv().is_ok());
+        assert!(first.is_closed());
+        assert!(second.is_closed());
+        assert!(system.mailbox_depths().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_actors_reflects_survivor() {
+        let system = ActorSystem::new("test", EventBus::new(16));
+        let first = system.create_actor("first", LifecycleActor).await.unwrap();
+        system.create_actor("second", LifecycleActor).await.unwrap();
+        assert_eq!(system.list_actors().await.len(), 2);
+
+        system.stop_actor(first.path()).await;
+
+        assert_eq!(
+            system.list_actors().await,
+            vec![ActorPath::from("/user/second")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_actor_count_and_contains_follow_create_and_stop() {
+        let system = ActorSystem::new("test", EventBus::new(16));
+        let path = ActorPath::from("/user/first");
+        assert_eq!(system.actor_count().await, 0);
+        assert!(!system.contains(&path).await);
+
+        system.create_actor("first", LifecycleActor).await.unwrap();
+        system.create_actor("second", LifecycleActor).await.unwrap();
+        assert_eq!(system.actor_count().await, 2);
+        assert!(system.contains(&path).await);
+
+        system.stop_actor(&path).await;
+        assert_eq!(system.actor_count().await, 1);
+        assert!(!system.contains(&path).await);
+
+        // Stopping twice must not disturb the survivor.
+        system.stop_actor(&path).await;
+        assert_eq!(system.actor_count().await, 1);
+        assert!(system.contains(&ActorPath::from("/user/second")).await);
+    }
+
+    #[tokio::test]
+    async fn test_tell_to_stopped_actor_is_dead_letter() {
+        let (system, actor_ref) = counter().await;
+        let mut dead_letters = system.dead_letters();
+
+        system.stop_all().await;
+        assert!(actor_ref.tell(Increment).is_err());
+
+        let dead_letter = dead_letters.try_recv().unwrap();
+        assert_eq!(dead_letter.path, ActorPath::from("/user/counter"));
+        assert_eq!(dead_letter.message_type, std::any::type_name::<Increment>());
+        assert!(dead_letters.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stale_runner_does_not_deregister_replacement() {
+        let system = ActorSystem::new("test", EventBus::new(16));
+        let old = system.create_actor("same", LifecycleActor).await.unwrap();
+
+        system.stop_actor(old.path()).await;
+        let new = system.create_actor("same", LifecycleActor).await.unwrap();
+
+        // Let the old runner finish and release its path.
+        while !old.is_closed() {
+            tokio::task::yield_now().await;
+        }
+        assert!(!new.is_closed());
+        assert!(
+            system
+                .get_actor::<LifecycleActor>(new.path())
+                .await
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_try_ask_fails_fast_on_stopped_actor() {
+        let (system, actor_ref) = counter().await;
+        assert_eq!(actor_ref.try_ask(Increment).await.unwrap(), 1);
+
+        system.stop_actor(actor_ref.path()).await;
+        let result = tokio::time::timeout(Duration::from_secs(1), actor_ref.try_ask(Increment))
+            .await
+            .expect("try_ask hung on a stopped actor");
+        assert!(matches!(result, Err(ActorError::SendError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_ask_timeout_expires_on_slow_handler() {
+        let system = ActorSystem::new("test", EventBus::<TestEvent>::new(16));
+        let actor_ref = system.create_actor("slow", SlowActor).await.unwrap();
+
+        let result = actor_ref
+            .ask_timeout(Increment, Duration::from_millis(20))
+            .await;
+        assert!(matches!(result, Err(ActorError::Timeout(path)) if path == *actor_ref.path()));
+
+        let (_system, fast_ref) = counter().await;
+        assert_eq!(
+            fast_ref
+                .ask_timeout(Increment, Duration::from_secs(1))
+                .await
+                .unwrap(),
+            1
+        );
+    }
+}
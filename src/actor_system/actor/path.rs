@@ -10,6 +10,12 @@ use std::fmt::{Error, Formatter};
 pub struct ActorPath(Vec<String>);
 
 impl ActorPath {
+    /// The path of a top-level actor, `/user/<segment>`, where `ActorSystem::create_actor`
+    /// places actors.
+    pub fn user(segment: &str) -> Self {
+        ActorPath(vec!["user".to_string(), segment.to_string()])
+    }
+
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
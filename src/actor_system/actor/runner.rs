@@ -1,93 +1,540 @@
 //! Actor runner - manages actor lifecycle.
 
-use crate::actor_system::system::{ActorSystem, SystemEvent};
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Instant;
+
+use futures::FutureExt;
+use tokio::sync::{Notify, watch};
+use tokio::time::Duration;
+
+use crate::actor_system::bus::EventBus;
+use crate::actor_system::system::{ActorSystem, DeadLetter, SystemEvent, SystemLifecycleEvent};
 
 use super::{
-    Actor, ActorContext, ActorPath, ActorRef, SupervisionStrategy,
-    handler::{ActorMailbox, MailboxReceiver},
+    Actor, ActorContext, ActorError, ActorPath, ActorRef, SupervisionStrategy,
+    handler::{ActorMailbox, BoxedMessageHandler, MailboxReceiver},
 };
 
+/// Lets the system ask a running actor to stop and wait until it has.
+#[derive(Clone)]
+pub(crate) struct StopHandle {
+    signal: Arc<Notify>,
+    stopped: watch::Receiver<bool>,
+}
+
+impl StopHandle {
+    /// Signals the runner to stop after the message it is currently handling.
+    pub fn stop(&self) {
+        self.signal.notify_one();
+    }
+
+    /// Resolves once the runner has finished, including `post_stop`.
+    pub async fn stopped(&self) {
+        let mut stopped = self.stopped.clone();
+        let _ = stopped.wait_for(|stopped| *stopped).await;
+    }
+
+    pub fn is_same(&self, other: &StopHandle) -> bool {
+        Arc::ptr_eq(&self.signal, &other.signal)
+    }
+}
+
+/// Why the runner stopped pulling messages.
+enum StopReason {
+    Signal,
+    Timeout,
+    MailboxClosed,
+    Requested,
+    Panicked,
+}
+
+/// Best-effort text of a panic payload.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
 pub(crate) struct ActorRunner<E: SystemEvent, A: Actor<E>> {
     path: ActorPath,
     actor: A,
     receiver: MailboxReceiver<E, A>,
+    mailbox_len: Arc<AtomicUsize>,
+    processing_nanos: Arc<AtomicU64>,
+    stop_handle: StopHandle,
+    stopped: watch::Sender<bool>,
 }
 
 impl<E: SystemEvent, A: Actor<E>> ActorRunner<E, A> {
-    pub fn create(path: ActorPath, actor: A) -> (Self, ActorRef<E, A>) {
-        let (sender, receiver) = ActorMailbox::create();
-        let actor_ref = ActorRef::new(path.clone(), sender);
+    pub fn create(
+        path: ActorPath,
+        actor: A,
+        dead_letters: EventBus<DeadLetter>,
+    ) -> (Self, ActorRef<E, A>) {
+        let (sender, receiver) = ActorMailbox::create(actor.mailbox_capacity());
+        let mailbox_len = Arc::new(AtomicUsize::new(0));
+        let (stopped, stopped_rx) = watch::channel(false);
+        let actor_ref = ActorRef::new(path.clone(), sender, mailbox_len.clone(), dead_letters);
         let runner = ActorRunner {
             path,
             actor,
             receiver,
+            mailbox_len,
+            processing_nanos: Arc::new(AtomicU64::new(0)),
+            stop_handle: StopHandle {
+                signal: Arc::new(Notify::new()),
+                stopped: stopped_rx,
+            },
+            stopped,
         };
         (runner, actor_ref)
     }
 
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop_handle.clone()
+    }
+
+    /// Total time spent in message handlers, in nanoseconds.
+    pub fn processing_nanos(&self) -> Arc<AtomicU64> {
+        self.processing_nanos.clone()
+    }
+
+    /// Waits for the next message, or the reason there won't be one.
+    async fn next_message(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<BoxedMessageHandler<E, A>, StopReason> {
+        let signal = self.stop_handle.signal.clone();
+        let receiver = &mut self.receiver;
+        let recv = async move {
+            match timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, receiver.recv()).await {
+                    Ok(msg) => msg.ok_or(StopReason::MailboxClosed),
+                    Err(_) => {
+                        log::debug!("Actor timed out after {:?} of inactivity.", timeout);
+                        Err(StopReason::Timeout)
+                    }
+                },
+                None => receiver.recv().await.ok_or(StopReason::MailboxClosed),
+            }
+        };
+
+        tokio::select! {
+            biased;
+            _ = signal.notified() => {
+                log::debug!("Actor '{}' received stop signal.", &self.path);
+                Err(StopReason::Signal)
+            }
+            msg = recv => msg,
+        }
+    }
+
+    /// Applies `strategy` after `error`, restarting the actor while retries remain.
+    ///
+    /// `retries` counts restarts over the actor's whole life. Returns the error the actor
+    /// finally gave up on, or `None` once a restart succeeded.
+    async fn supervise(
+        &mut self,
+        ctx: &mut ActorContext<E>,
+        strategy: &mut SupervisionStrategy,
+        retries: &mut usize,
+        mut error: ActorError,
+    ) -> Option<ActorError> {
+        let SupervisionStrategy::Retry(retry_strategy) = strategy else {
+            return Some(error);
+        };
+        log::debug!("Restarting actor with retry strategy: {:?}", retry_strategy);
+        while *retries < retry_strategy.max_retries() {
+            log::debug!("retries: {}", retries);
+            if let Some(duration) = retry_strategy.next_backoff() {
+                log::debug!("Backoff for {:?}", &duration);
+                tokio::time::sleep(duration).await;
+            }
+            *retries += 1;
+            ctx.system.record_restart(&self.path);
+            match ctx.restart(&mut self.actor, Some(&error)).await {
+                Ok(()) => {
+                    ctx.system
+                        .publish_lifecycle(SystemLifecycleEvent::Restarted {
+                            path: self.path.clone(),
+                            retries: *retries,
+                        });
+                    return None;
+                }
+                Err(restart_error) => error = restart_error,
+            }
+        }
+        Some(error)
+    }
+
     pub async fn start(&mut self, system: ActorSystem<E>) {
         log::debug!("Starting actor '{}'...", &self.path);
 
         let mut ctx = ActorContext {
             path: self.path.clone(),
             system: system.clone(),
+            stopping: false,
         };
 
-        // Start the actor
-        let mut start_error = self.actor.pre_start(&mut ctx).await.err();
-
-        // Handle supervision strategy if startup failed
-        if start_error.is_some() {
-            let mut retries = 0;
-            match A::supervision_strategy() {
-                SupervisionStrategy::Stop => {
-                    log::error!("Actor '{}' failed to start!", &self.path);
-                }
-                SupervisionStrategy::Retry(mut retry_strategy) => {
-                    log::debug!(
-                        "Restarting actor with retry strategy: {:?}",
-                        &retry_strategy
-                    );
-                    while retries < retry_strategy.max_retries() && start_error.is_some() {
-                        log::debug!("retries: {}", &retries);
-                        if let Some(duration) = retry_strategy.next_backoff() {
-                            log::debug!("Backoff for {:?}", &duration);
-                            tokio::time::sleep(duration).await;
-                        }
-                        retries += 1;
-                        start_error = ctx
-                            .restart(&mut self.actor, start_error.as_ref())
-                            .await
-                            .err();
-                    }
-                }
+        // Start the actor, applying the supervision strategy if that fails
+        let mut strategy = A::supervision_strategy();
+        let mut retries = 0;
+        let start_error = match self.actor.pre_start(&mut ctx).await {
+            Ok(()) => None,
+            Err(error) => {
+                self.supervise(&mut ctx, &mut strategy, &mut retries, error)
+                    .await
             }
+        };
+        if start_error.is_some() {
+            log::error!("Actor '{}' failed to start!", &self.path);
         }
 
         // Run the actor if startup succeeded
         if start_error.is_none() {
             log::debug!("Actor '{}' has started successfully.", &self.path);
+            system.publish_lifecycle(SystemLifecycleEvent::Started {
+                path: self.path.clone(),
+            });
 
-            if let Some(timeout) = A::timeout() {
+            let timeout = self.actor.idle_timeout();
+            if let Some(timeout) = timeout {
                 log::debug!("Timeout of {:?} set for actor {}", timeout, &self.path);
-                while let Ok(Some(mut msg)) =
-                    tokio::time::timeout(timeout, self.receiver.recv()).await
+            }
+
+            let slow_threshold = system.slow_handler_threshold();
+            let reason = loop {
+                let mut msg = match self.next_message(timeout).await {
+                    Ok(msg) => msg,
+                    Err(reason) => break reason,
+                };
+                let started = Instant::now();
+                let outcome = AssertUnwindSafe(msg.handle(&mut self.actor, &mut ctx))
+                    .catch_unwind()
+                    .await;
+                let elapsed = started.elapsed();
+
+                self.processing_nanos
+                    .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+                if let Some(threshold) = slow_threshold
+                    && elapsed > threshold
                 {
-                    msg.handle(&mut self.actor, &mut ctx).await;
+                    log::warn!(
+                        "Actor '{}' took {:?} to handle {}",
+                        &self.path,
+                        elapsed,
+                        msg.name()
+                    );
                 }
-                log::debug!("Actor timed out after {:?} of inactivity.", timeout);
-            } else {
-                while let Some(mut msg) = self.receiver.recv().await {
-                    msg.handle(&mut self.actor, &mut ctx).await;
+                self.mailbox_len.fetch_sub(1, Ordering::SeqCst);
+
+                if let Err(payload) = outcome {
+                    let error = ActorError::Panicked(panic_message(payload.as_ref()));
+                    log::error!(
+                        "Actor '{}' panicked handling {}: {}",
+                        &self.path,
+                        msg.name(),
+                        error
+                    );
+                    if self
+                        .supervise(&mut ctx, &mut strategy, &mut retries, error)
+                        .await
+                        .is_some()
+                    {
+                        log::error!("Actor '{}' stopped after a handler panic.", &self.path);
+                        break StopReason::Panicked;
+                    }
                 }
+
+                if ctx.stopping {
+                    log::debug!("Actor '{}' stopped itself.", &self.path);
+                    break StopReason::Requested;
+                }
+            };
+
+            if matches!(reason, StopReason::Timeout) {
+                self.actor.on_timeout(&mut ctx).await;
             }
 
             self.actor.post_stop(&mut ctx).await;
-            system.stop_actor(&self.path).await;
+            system.release_actor(&self.path, &self.stop_handle).await;
+            system.publish_lifecycle(SystemLifecycleEvent::Stopped {
+                path: self.path.clone(),
+            });
 
             log::debug!("Actor '{}' stopped.", &self.path);
         }
 
         self.receiver.close();
+        // Anything still queued is dropped together with the receiver.
+        self.mailbox_len.store(0, Ordering::SeqCst);
+        let _ = self.stopped.send(true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor_system::actor::supervision::NoIntervalStrategy;
+    use crate::actor_system::{EventBus, Handler, Message, async_trait};
+    use std::sync::{Mutex, Once};
+
+    static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct CaptureLogger;
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::Level::Warn
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                WARNINGS.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn capture_warnings() {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            log::set_logger(&CaptureLogger).unwrap();
+            log::set_max_level(log::LevelFilter::Warn);
+        });
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestEvent;
+
+    impl SystemEvent for TestEvent {}
+
+    struct SleepyActor;
+
+    impl Actor<TestEvent> for SleepyActor {}
+
+    #[derive(Clone, Debug)]
+    struct SlowNap;
+
+    impl Message for SlowNap {
+        type Response = ();
+    }
+
+    #[async_trait]
+    impl Handler<TestEvent, SlowNap> for SleepyActor {
+        async fn handle(&mut self, _msg: SlowNap, _ctx: &mut ActorContext<TestEvent>) {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_handler_logs_warning() {
+        capture_warnings();
+        let system = ActorSystem::new("test", EventBus::new(16))
+            .with_slow_handler_threshold(Some(Duration::from_millis(10)));
+        let actor_ref = system.create_actor("sleepy", SleepyActor).await.unwrap();
+
+        actor_ref.ask(SlowNap).await.unwrap();
+
+        let warned =
+            WARNINGS.lock().unwrap().iter().any(|w| {
+                w.contains("/user/sleepy") && w.contains(std::any::type_name::<SlowNap>())
+            });
+        assert!(warned);
+
+        let (_, processing) = system.processing_times().await.remove(0);
+        assert!(processing >= Duration::from_millis(30));
+    }
+
+    /// Panics on `Explode`. Retries up to `RETRIES` restarts, or stops when it is 0.
+    struct FragileActor<const RETRIES: usize> {
+        starts: usize,
+    }
+
+    #[async_trait]
+    impl<const RETRIES: usize> Actor<TestEvent> for FragileActor<RETRIES> {
+        fn supervision_strategy() -> SupervisionStrategy {
+            match RETRIES {
+                0 => SupervisionStrategy::Stop,
+                retries => SupervisionStrategy::Retry(Box::new(NoIntervalStrategy::new(retries))),
+            }
+        }
+
+        async fn pre_start(
+            &mut self,
+            _ctx: &mut ActorContext<TestEvent>,
+        ) -> Result<(), ActorError> {
+            self.starts += 1;
+            Ok(())
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct Explode;
+
+    impl Message for Explode {
+        type Response = ();
+    }
+
+    #[derive(Clone, Debug)]
+    struct Starts;
+
+    impl Message for Starts {
+        type Response = usize;
+    }
+
+    #[async_trait]
+    impl<const RETRIES: usize> Handler<TestEvent, Explode> for FragileActor<RETRIES> {
+        async fn handle(&mut self, _msg: Explode, _ctx: &mut ActorContext<TestEvent>) {
+            panic!("boom");
+        }
+    }
+
+    #[async_trait]
+    impl<const RETRIES: usize> Handler<TestEvent, Starts> for FragileActor<RETRIES> {
+        async fn handle(&mut self, _msg: Starts, _ctx: &mut ActorContext<TestEvent>) -> usize {
+            self.starts
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handler_panic_restarts_actor() {
+        let system = ActorSystem::new("test", EventBus::new(16));
+        let actor_ref = system
+            .create_actor("fragile", FragileActor::<1> { starts: 0 })
+            .await
+            .unwrap();
+
+        assert!(actor_ref.ask(Explode).await.is_err());
+        assert_eq!(actor_ref.ask(Starts).await.unwrap(), 2);
+
+        // The only retry is spent, so the next panic stops it.
+        assert!(actor_ref.ask(Explode).await.is_err());
+        assert!(actor_ref.ask(Starts).await.is_err());
+        assert!(
+            system
+                .get_actor::<FragileActor<1>>(actor_ref.path())
+                .await
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handler_panic_stops_actor() {
+        let system = ActorSystem::new("test", EventBus::new(16));
+        let actor_ref = system
+            .create_actor("fragile", FragileActor::<0> { starts: 0 })
+            .await
+            .unwrap();
+
+        assert!(actor_ref.ask(Explode).await.is_err());
+        assert!(actor_ref.ask(Starts).await.is_err());
+        assert!(
+            system
+                .get_actor::<FragileActor<0>>(actor_ref.path())
+                .await
+                .is_none()
+        );
+
+        // The system carries on.
+        let replacement = system
+            .create_actor("fragile", FragileActor::<0> { starts: 0 })
+            .await
+            .unwrap();
+        assert_eq!(replacement.ask(Starts).await.unwrap(), 1);
+    }
+
+    /// Fails `pre_start` until it has failed `FAILURES` times.
+    struct FlakyStarter<const FAILURES: usize> {
+        failures: usize,
+    }
+
+    #[async_trait]
+    impl<const FAILURES: usize> Actor<TestEvent> for FlakyStarter<FAILURES> {
+        fn supervision_strategy() -> SupervisionStrategy {
+            SupervisionStrategy::Retry(Box::new(NoIntervalStrategy::new(5)))
+        }
+
+        async fn pre_start(
+            &mut self,
+            _ctx: &mut ActorContext<TestEvent>,
+        ) -> Result<(), ActorError> {
+            if self.failures < FAILURES {
+                self.failures += 1;
+                return Err(ActorError::CreateError("not yet".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_starts_count_restarts() {
+        let system = ActorSystem::new("test", EventBus::new(16));
+        let mut events = system.lifecycle_events();
+        system
+            .create_actor("flaky", FlakyStarter::<2> { failures: 0 })
+            .await
+            .unwrap();
+
+        loop {
+            if let SystemLifecycleEvent::Started { .. } = next_lifecycle(&mut events).await {
+                break;
+            }
+        }
+        assert_eq!(system.restart_counts(), vec![("flaky".to_string(), 2)]);
+    }
+
+    async fn next_lifecycle(
+        events: &mut crate::actor_system::bus::EventReceiver<SystemLifecycleEvent>,
+    ) -> SystemLifecycleEvent {
+        tokio::time::timeout(Duration::from_secs(1), events.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_events_started_then_stopped() {
+        let system = ActorSystem::new("test", EventBus::new(16));
+        let mut events = system.lifecycle_events();
+        let actor_ref = system.create_actor("sleepy", SleepyActor).await.unwrap();
+        let path = actor_ref.path().clone();
+
+        assert_eq!(
+            next_lifecycle(&mut events).await,
+            SystemLifecycleEvent::Started { path: path.clone() }
+        );
+
+        system.stop_actor(&path).await;
+        assert_eq!(
+            next_lifecycle(&mut events).await,
+            SystemLifecycleEvent::Stopped { path }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_restart_carries_retry_count() {
+        let system = ActorSystem::new("test", EventBus::new(16));
+        let mut events = system.lifecycle_events();
+        let actor_ref = system
+            .create_actor("fragile", FragileActor::<1> { starts: 0 })
+            .await
+            .unwrap();
+        let path = actor_ref.path().clone();
+        next_lifecycle(&mut events).await;
+
+        assert!(actor_ref.ask(Explode).await.is_err());
+        assert_eq!(
+            next_lifecycle(&mut events).await,
+            SystemLifecycleEvent::Restarted { path, retries: 1 }
+        );
     }
 }
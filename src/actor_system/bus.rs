@@ -1,18 +1,67 @@
 //! Event bus for actor system pub/sub.
 
-use tokio::sync::broadcast;
+use serde::Deserialize;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 #[allow(unused_imports)]
 pub use tokio::sync::broadcast::error::RecvError as EventRecvError;
 pub use tokio::sync::broadcast::error::SendError;
 use tokio::sync::broadcast::{Receiver as BroadcastReceiver, Sender as BroadcastSender};
+use tokio::sync::{broadcast, mpsc};
 
 pub type EventReceiver<T> = BroadcastReceiver<T>;
+pub type FilteredEventReceiver<T> = mpsc::Receiver<T>;
 pub(crate) type EventSender<T> = BroadcastSender<T>;
 
+/// What the bus does with a new event once its slowest subscriber has `capacity` unread.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Overwrite the oldest event. Subscribers that hadn't read it get `Lagged` and skip ahead.
+    #[default]
+    DropOldest,
+    /// Refuse the new event instead. Subscribers never skip over an event they were sent, but
+    /// what is published while the bus is full is lost, and `send` reports it as failed.
+    DropNewest,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "drop_oldest" => Ok(OverflowPolicy::DropOldest),
+            "drop_newest" => Ok(OverflowPolicy::DropNewest),
+            other => Err(format!("unknown overflow policy: {}", other)),
+        }
+    }
+}
+
+/// How well the subscribers of an `EventBus` are keeping up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventBusStats {
+    pub subscribers: usize,
+    /// Events the slowest subscriber has yet to read.
+    pub queued: usize,
+    /// Unread events the bus holds before its overflow policy kicks in.
+    pub capacity: usize,
+    /// Events overwritten before every subscriber had read them.
+    pub dropped: u64,
+    /// Events refused because the bus was full.
+    pub rejected: u64,
+}
+
 /// Broadcast event bus for pub/sub messaging.
 #[derive(Clone)]
 pub struct EventBus<T: Clone> {
     tx: EventSender<T>,
+    capacity: usize,
+    overflow: OverflowPolicy,
+    /// Serializes the fullness check with the send under `OverflowPolicy::DropNewest`.
+    send_lock: Arc<Mutex<()>>,
+    dropped: Arc<AtomicU64>,
+    rejected: Arc<AtomicU64>,
 }
 
 impl<T: Clone> EventBus<T> {
@@ -20,12 +69,160 @@ impl<T: Clone> EventBus<T> {
         self.tx.subscribe()
     }
 
+    /// Subscribes to only the events matching `filter`.
+    ///
+    /// A forwarding task applies the predicate on the subscriber's behalf, so other
+    /// subscribers are unaffected. Must be called from within a Tokio runtime; the task
+    /// ends when the returned receiver is dropped or the bus is closed.
+    pub fn subscribe_filtered<F>(&self, filter: F) -> FilteredEventReceiver<T>
+    where
+        T: Send + 'static,
+        F: Fn(&T) -> bool + Send + 'static,
+    {
+        let mut events = self.tx.subscribe();
+        let (tx, rx) = mpsc::channel(self.capacity);
+        tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    _ = tx.closed() => break,
+                    event = events.recv() => event,
+                };
+                match event {
+                    Ok(event) if filter(&event) => {
+                        if tx.send(event).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(EventRecvError::Lagged(skipped)) => {
+                        log::warn!("Filtered subscriber lagged, skipped {} events", skipped);
+                    }
+                    Err(EventRecvError::Closed) => break,
+                }
+            }
+        });
+        rx
+    }
+
+    /// Publishes `event` to every subscriber, returning how many there are.
+    ///
+    /// Fails when there are no subscribers, or when the bus is full under
+    /// `OverflowPolicy::DropNewest`.
     pub fn send(&self, event: T) -> Result<usize, SendError<T>> {
-        self.tx.send(event)
+        match self.overflow {
+            OverflowPolicy::DropOldest => {
+                if self.is_full() {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                self.tx.send(event)
+            }
+            OverflowPolicy::DropNewest => {
+                let _guard = self.send_lock.lock().unwrap_or_else(|e| e.into_inner());
+                if self.is_full() {
+                    self.rejected.fetch_add(1, Ordering::Relaxed);
+                    return Err(SendError(event));
+                }
+                self.tx.send(event)
+            }
+        }
+    }
+
+    /// Number of live subscribers, each filtered subscription counting as one.
+    pub fn subscriber_count(&self) -> usize {
+        self.tx.receiver_count()
+    }
+
+    pub fn stats(&self) -> EventBusStats {
+        EventBusStats {
+            subscribers: self.subscriber_count(),
+            queued: self.tx.len(),
+            capacity: self.slots(),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Whether the next event would overflow the slowest subscriber.
+    fn is_full(&self) -> bool {
+        self.tx.receiver_count() > 0 && self.tx.len() >= self.slots()
     }
 
+    /// The channel rounds its capacity up to a power of two.
+    fn slots(&self) -> usize {
+        self.capacity.next_power_of_two()
+    }
+
+    /// A bus holding up to `capacity` unread events, dropping the oldest beyond that.
     pub fn new(capacity: usize) -> Self {
         let (tx, _) = broadcast::channel(capacity);
-        EventBus { tx }
+        EventBus {
+            tx,
+            capacity,
+            overflow: OverflowPolicy::DropOldest,
+            send_lock: Arc::new(Mutex::new(())),
+            dropped: Arc::new(AtomicU64::new(0)),
+            rejected: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn with_overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum TestEvent {
+        PlayerJoined(u32),
+        PlayerMoved(u32),
+    }
+
+    #[tokio::test]
+    async fn test_filtered_subscriber_only_receives_matching_events() {
+        let bus = EventBus::new(16);
+        let mut joined = bus.subscribe_filtered(|e| matches!(e, TestEvent::PlayerJoined(_)));
+        let mut all = bus.subscribe();
+
+        bus.send(TestEvent::PlayerMoved(1)).unwrap();
+        bus.send(TestEvent::PlayerJoined(2)).unwrap();
+        bus.send(TestEvent::PlayerMoved(3)).unwrap();
+        bus.send(TestEvent::PlayerJoined(4)).unwrap();
+
+        assert_eq!(joined.recv().await, Some(TestEvent::PlayerJoined(2)));
+        assert_eq!(joined.recv().await, Some(TestEvent::PlayerJoined(4)));
+        assert_eq!(all.recv().await.unwrap(), TestEvent::PlayerMoved(1));
+
+        drop(bus);
+        assert_eq!(joined.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_tiny_bus_surfaces_lag_to_slow_subscriber() {
+        let bus = EventBus::new(4);
+        let mut slow = bus.subscribe();
+        for id in 0..10 {
+            bus.send(TestEvent::PlayerMoved(id)).unwrap();
+        }
+
+        let stats = bus.stats();
+        assert_eq!(stats.subscribers, 1);
+        assert_eq!(stats.queued, 4);
+        assert_eq!(stats.dropped, 6);
+        assert!(matches!(slow.recv().await, Err(EventRecvError::Lagged(6))));
+        assert_eq!(slow.recv().await.unwrap(), TestEvent::PlayerMoved(6));
+
+        // Dropping the newest keeps the subscriber's backlog intact and refuses the overflow.
+        let bus = EventBus::new(4).with_overflow(OverflowPolicy::DropNewest);
+        let mut slow = bus.subscribe();
+        let sent = (0..10)
+            .filter(|id| bus.send(TestEvent::PlayerMoved(*id)).is_ok())
+            .count();
+        assert_eq!(sent, 4);
+        assert_eq!(bus.stats().rejected, 6);
+        assert_eq!(slow.recv().await.unwrap(), TestEvent::PlayerMoved(0));
     }
 }
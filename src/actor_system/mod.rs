@@ -5,11 +5,12 @@
 
 mod actor;
 mod bus;
+mod scheduler;
 mod system;
 
 pub use actor::{Actor, ActorContext, ActorError, ActorPath, ActorRef, Handler, Message};
 
-pub use bus::EventBus;
-pub use system::{ActorSystem, SystemEvent};
+pub use bus::{EventBus, EventBusStats, OverflowPolicy};
+pub use system::{ActorSystem, SystemEvent, SystemLifecycleEvent};
 
 pub use async_trait::async_trait;
@@ -0,0 +1,215 @@
+//! Periodic tasks driven by a single timer.
+
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+
+use tokio::sync::Notify;
+use tokio::time::{Duration, Instant};
+
+type Task = Arc<dyn Fn() + Send + Sync + 'static>;
+
+struct ScheduledTask {
+    interval: Duration,
+    next_due: Instant,
+    task: Task,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    tasks: Vec<ScheduledTask>,
+    /// Set once the timer task has been spawned.
+    running: bool,
+    cancelled: bool,
+}
+
+struct SchedulerInner {
+    state: Mutex<SchedulerState>,
+    /// Wakes the timer when a task is added, the scheduler is cancelled or it is dropped.
+    wake: Arc<Notify>,
+}
+
+impl SchedulerInner {
+    fn lock(&self) -> MutexGuard<'_, SchedulerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for SchedulerInner {
+    fn drop(&mut self) {
+        self.wake.notify_one();
+    }
+}
+
+/// Runs registered tasks every `interval` off one timer task, instead of a loop per task.
+///
+/// Tasks run on the timer task itself, so they should be quick and spawn anything that waits
+/// or blocks. A task that panics is logged and runs again at its next interval. Every task
+/// stops when the scheduler is cancelled or its last clone is dropped.
+#[derive(Clone)]
+pub struct Scheduler {
+    inner: Arc<SchedulerInner>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(SchedulerInner {
+                state: Mutex::new(SchedulerState::default()),
+                wake: Arc::new(Notify::new()),
+            }),
+        }
+    }
+
+    /// Runs `task` every `interval`, first after one `interval` has passed. Must be called
+    /// from within a tokio runtime.
+    pub fn schedule(&self, interval: Duration, task: impl Fn() + Send + Sync + 'static) {
+        if interval.is_zero() {
+            log::warn!("Ignoring task scheduled with a zero interval");
+            return;
+        }
+
+        let mut state = self.inner.lock();
+        if state.cancelled {
+            log::warn!("Ignoring task scheduled after the scheduler was cancelled");
+            return;
+        }
+        state.tasks.push(ScheduledTask {
+            interval,
+            next_due: Instant::now() + interval,
+            task: Arc::new(task),
+        });
+        if !state.running {
+            state.running = true;
+            tokio::spawn(run(Arc::downgrade(&self.inner), self.inner.wake.clone()));
+        }
+        drop(state);
+        self.inner.wake.notify_one();
+    }
+
+    /// Stops every task. Tasks scheduled afterwards are ignored.
+    pub fn cancel(&self) {
+        let mut state = self.inner.lock();
+        state.cancelled = true;
+        state.tasks.clear();
+        drop(state);
+        self.inner.wake.notify_one();
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The timer loop. Holds the scheduler only weakly, so dropping it ends the loop.
+async fn run(inner: Weak<SchedulerInner>, wake: Arc<Notify>) {
+    loop {
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        let Some((due, next_due)) = take_due(&inner) else {
+            return;
+        };
+        // Run without the lock held, so a task may schedule further tasks.
+        for task in due {
+            if catch_unwind(AssertUnwindSafe(|| task())).is_err() {
+                log::error!("Scheduled task panicked");
+            }
+        }
+        drop(inner);
+
+        match next_due {
+            Some(next_due) => {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(next_due) => {}
+                    _ = wake.notified() => {}
+                }
+            }
+            None => wake.notified().await,
+        }
+    }
+}
+
+/// Takes the tasks due now and moves each on by its interval. Returns them along with when
+/// the next task is due, or `None` once the scheduler is cancelled.
+fn take_due(inner: &SchedulerInner) -> Option<(Vec<Task>, Option<Instant>)> {
+    let now = Instant::now();
+    let mut state = inner.lock();
+    if state.cancelled {
+        return None;
+    }
+
+    let mut due = Vec::new();
+    for scheduled in state.tasks.iter_mut() {
+        if scheduled.next_due <= now {
+            due.push(scheduled.task.clone());
+            scheduled.next_due += scheduled.interval;
+            // A stalled timer skips the runs it missed rather than firing them in a burst.
+            if scheduled.next_due <= now {
+                scheduled.next_due = now + scheduled.interval;
+            }
+        }
+    }
+    let next_due = state.tasks.iter().map(|scheduled| scheduled.next_due).min();
+    Some((due, next_due))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_task_fires_each_interval_until_dropped() {
+        let scheduler = Scheduler::new();
+        let fired = Arc::new(AtomicUsize::new(0));
+        let counter = fired.clone();
+        scheduler.schedule(Duration::from_millis(20), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        tokio::time::sleep(Duration::from_millis(110)).await;
+        let count = fired.load(Ordering::SeqCst);
+        assert!((4..=6).contains(&count), "fired {} times", count);
+
+        drop(scheduler);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let after_drop = fired.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(fired.load(Ordering::SeqCst), after_drop);
+    }
+
+    #[tokio::test]
+    async fn test_panicking_task_leaves_others_running() {
+        let scheduler = Scheduler::new();
+        let fired = Arc::new(AtomicUsize::new(0));
+        scheduler.schedule(Duration::from_millis(10), || panic!("task failed"));
+        let counter = fired.clone();
+        scheduler.schedule(Duration::from_millis(10), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        tokio::time::sleep(Duration::from_millis(55)).await;
+        assert!(fired.load(Ordering::SeqCst) >= 3);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_every_task() {
+        let scheduler = Scheduler::new();
+        let fired = Arc::new(AtomicUsize::new(0));
+        for _ in 0..2 {
+            let counter = fired.clone();
+            scheduler.schedule(Duration::from_millis(10), move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        scheduler.cancel();
+        scheduler.schedule(Duration::from_millis(10), || {
+            panic!("scheduled after cancel")
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(fired.load(Ordering::SeqCst), 0);
+    }
+}
@@ -2,25 +2,81 @@
 
 #![allow(dead_code)]
 
-use std::{any::Any, collections::HashMap, fmt, sync::Arc};
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
 
 use tokio::sync::RwLock;
+use tokio::time::Duration;
 
 use crate::actor_system::{
     ActorError, ActorPath,
-    actor::{Actor, ActorRef, runner::ActorRunner},
-    bus::{EventBus, EventReceiver},
+    actor::{
+        Actor, ActorRef, Handler, Message,
+        runner::{ActorRunner, StopHandle},
+    },
+    bus::{EventBus, EventBusStats, EventReceiver, FilteredEventReceiver},
+    scheduler::Scheduler,
 };
 
 /// Events that this actor system will send.
 pub trait SystemEvent: Clone + Send + Sync + 'static {}
 
+/// Number of undelivered messages buffered for each dead letter subscriber.
+const DEAD_LETTER_CAPACITY: usize = 256;
+
+/// Number of lifecycle transitions buffered for each lifecycle subscriber.
+const LIFECYCLE_CAPACITY: usize = 256;
+
+/// A transition in an actor's lifecycle, published on the system's lifecycle channel.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SystemLifecycleEvent {
+    Started {
+        path: ActorPath,
+    },
+    Stopped {
+        path: ActorPath,
+    },
+    /// `retries` counts the actor's restarts over its whole life, including this one.
+    Restarted {
+        path: ActorPath,
+        retries: usize,
+    },
+}
+
+/// A message that could not be delivered to an actor's mailbox.
+#[derive(Clone, Debug)]
+pub struct DeadLetter {
+    pub path: ActorPath,
+    pub message_type: &'static str,
+}
+
+/// A type-erased actor reference held in the system registry.
+struct ActorEntry {
+    actor_ref: Box<dyn Any + Send + Sync + 'static>,
+    mailbox_len: Arc<AtomicUsize>,
+    processing_nanos: Arc<AtomicU64>,
+    stop: StopHandle,
+}
+
 /// An actor system that manages actors and provides event bus functionality.
 #[derive(Clone)]
 pub struct ActorSystem<E: SystemEvent> {
     name: String,
-    actors: Arc<RwLock<HashMap<ActorPath, Box<dyn Any + Send + Sync + 'static>>>>,
+    actors: Arc<RwLock<HashMap<ActorPath, ActorEntry>>>,
     bus: EventBus<E>,
+    dead_letters: EventBus<DeadLetter>,
+    lifecycle: EventBus<SystemLifecycleEvent>,
+    slow_handler_threshold: Option<Duration>,
+    scheduler: Scheduler,
+    /// Supervised restarts attempted so far, by actor path key. Kept after the actor stops.
+    restarts: Arc<Mutex<HashMap<String, u64>>>,
 }
 
 impl<E: SystemEvent> ActorSystem<E> {
@@ -32,10 +88,17 @@ impl<E: SystemEvent> ActorSystem<E> {
     /// Publish an event on the actor system's event bus.
     pub fn publish(&self, event: E) {
         self.bus.send(event).unwrap_or_else(|error| {
-            log::warn!(
-                "No listeners active on event bus. Dropping event: {:?}",
-                &error.to_string(),
-            );
+            if self.bus.subscriber_count() == 0 {
+                log::warn!(
+                    "No listeners active on event bus. Dropping event: {:?}",
+                    &error.to_string(),
+                );
+            } else {
+                log::warn!(
+                    "Event bus is full. Dropping event: {:?}",
+                    &error.to_string()
+                );
+            }
             0
         });
     }
@@ -45,12 +108,141 @@ impl<E: SystemEvent> ActorSystem<E> {
         self.bus.subscribe()
     }
 
+    /// Subscribe to only the events of this actor system matching `filter`.
+    pub fn events_filtered<F>(&self, filter: F) -> FilteredEventReceiver<E>
+    where
+        F: Fn(&E) -> bool + Send + 'static,
+    {
+        self.bus.subscribe_filtered(filter)
+    }
+
+    /// Subscribe to messages that could not be delivered to their actor.
+    pub fn dead_letters(&self) -> EventReceiver<DeadLetter> {
+        self.dead_letters.subscribe()
+    }
+
+    /// Subscriber count and overflow counters of the event bus.
+    pub fn event_bus_stats(&self) -> EventBusStats {
+        self.bus.stats()
+    }
+
+    /// Subscribe to actor lifecycle transitions. Separate from the event bus given to `new`.
+    pub fn lifecycle_events(&self) -> EventReceiver<SystemLifecycleEvent> {
+        self.lifecycle.subscribe()
+    }
+
+    /// Publishes a lifecycle transition. Dropped silently when nobody is subscribed.
+    pub(crate) fn publish_lifecycle(&self, event: SystemLifecycleEvent) {
+        let _ = self.lifecycle.send(event);
+    }
+
     /// Retrieves an actor running in this actor system.
     pub async fn get_actor<A: Actor<E>>(&self, path: &ActorPath) -> Option<ActorRef<E, A>> {
         let actors = self.actors.read().await;
         actors
             .get(path)
-            .and_then(|any| any.downcast_ref::<ActorRef<E, A>>().cloned())
+            .and_then(|entry| entry.actor_ref.downcast_ref::<ActorRef<E, A>>().cloned())
+    }
+
+    /// Tells a clone of `msg` to every `A` actor at or below `prefix`.
+    ///
+    /// Actors of other types under the prefix are skipped. Returns how many accepted the message.
+    pub async fn broadcast_to_actors<A, M>(&self, prefix: &ActorPath, msg: M) -> usize
+    where
+        A: Handler<E, M>,
+        M: Message + Clone,
+    {
+        let targets: Vec<ActorRef<E, A>> = {
+            let actors = self.actors.read().await;
+            actors
+                .iter()
+                .filter(|(path, _)| *path == prefix || path.is_descendant_of(prefix))
+                .filter_map(|(_, entry)| entry.actor_ref.downcast_ref::<ActorRef<E, A>>().cloned())
+                .collect()
+        };
+        targets
+            .iter()
+            .filter(|actor_ref| actor_ref.tell(msg.clone()).is_ok())
+            .count()
+    }
+
+    /// Lists the paths of every actor currently registered, sorted.
+    pub async fn list_actors(&self) -> Vec<ActorPath> {
+        let actors = self.actors.read().await;
+        let mut paths: Vec<ActorPath> = actors.keys().cloned().collect();
+        paths.sort_unstable();
+        paths
+    }
+
+    /// Number of actors currently registered, of any type.
+    pub async fn actor_count(&self) -> usize {
+        self.actors.read().await.len()
+    }
+
+    /// Whether an actor of any type is registered at `path`.
+    pub async fn contains(&self, path: &ActorPath) -> bool {
+        self.actors.read().await.contains_key(path)
+    }
+
+    /// Lists every registered actor together with its pending mailbox depth.
+    pub async fn mailbox_depths(&self) -> Vec<(ActorPath, usize)> {
+        let actors = self.actors.read().await;
+        let mut depths: Vec<(ActorPath, usize)> = actors
+            .iter()
+            .map(|(path, entry)| (path.clone(), entry.mailbox_len.load(Ordering::SeqCst)))
+            .collect();
+        depths.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        depths
+    }
+
+    /// Lists every registered actor together with the total time spent in its handlers.
+    pub async fn processing_times(&self) -> Vec<(ActorPath, Duration)> {
+        let actors = self.actors.read().await;
+        let mut times: Vec<(ActorPath, Duration)> = actors
+            .iter()
+            .map(|(path, entry)| {
+                let nanos = entry.processing_nanos.load(Ordering::Relaxed);
+                (path.clone(), Duration::from_nanos(nanos))
+            })
+            .collect();
+        times.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        times
+    }
+
+    /// Counts a supervised restart attempt of the actor at `path`.
+    pub(crate) fn record_restart(&self, path: &ActorPath) {
+        let mut restarts = self.restarts.lock().unwrap_or_else(|e| e.into_inner());
+        *restarts.entry(path.key()).or_insert(0) += 1;
+    }
+
+    /// Supervised restarts attempted by every actor that has needed one, keyed by the last
+    /// segment of its path and sorted.
+    pub fn restart_counts(&self) -> Vec<(String, u64)> {
+        let restarts = self.restarts.lock().unwrap_or_else(|e| e.into_inner());
+        let mut counts: Vec<(String, u64)> = restarts
+            .iter()
+            .map(|(key, count)| (key.clone(), *count))
+            .collect();
+        counts.sort_unstable();
+        counts
+    }
+
+    /// Runs `task` every `interval` until `stop_all`, on a timer shared by every scheduled task.
+    ///
+    /// Tasks should return quickly; anything that waits belongs in a spawned task.
+    pub fn schedule(&self, interval: Duration, task: impl Fn() + Send + Sync + 'static) {
+        self.scheduler.schedule(interval, task);
+    }
+
+    /// Handlers taking longer than this are logged as warnings. `None` disables the check.
+    pub fn slow_handler_threshold(&self) -> Option<Duration> {
+        self.slow_handler_threshold
+    }
+
+    /// Sets the threshold above which a single message handler is logged as slow.
+    pub fn with_slow_handler_threshold(mut self, threshold: Option<Duration>) -> Self {
+        self.slow_handler_threshold = threshold;
+        self
     }
 
     pub(crate) async fn create_actor_path<A: Actor<E>>(
@@ -66,15 +258,22 @@ impl<E: SystemEvent> ActorSystem<E> {
         }
 
         let system = self.clone();
-        let (mut runner, actor_ref) = ActorRunner::create(path, actor);
+        let (mut runner, actor_ref) = ActorRunner::create(path, actor, self.dead_letters.clone());
+        let stop = runner.stop_handle();
+        let processing_nanos = runner.processing_nanos();
         tokio::spawn(async move {
             runner.start(system).await;
         });
 
         let path = actor_ref.path().clone();
-        let any = Box::new(actor_ref.clone());
+        let entry = ActorEntry {
+            actor_ref: Box::new(actor_ref.clone()),
+            mailbox_len: actor_ref.mailbox_len(),
+            processing_nanos,
+            stop,
+        };
 
-        actors.insert(path, any);
+        actors.insert(path, entry);
 
         Ok(actor_ref)
     }
@@ -85,7 +284,7 @@ impl<E: SystemEvent> ActorSystem<E> {
         name: &str,
         actor: A,
     ) -> Result<ActorRef<E, A>, ActorError> {
-        let path = ActorPath::from("/user") / name;
+        let path = ActorPath::user(name);
         self.create_actor_path(path, actor).await
     }
 
@@ -99,7 +298,7 @@ impl<E: SystemEvent> ActorSystem<E> {
         A: Actor<E>,
         F: FnOnce() -> A,
     {
-        let path = ActorPath::from("/user") / name;
+        let path = ActorPath::user(name);
         self.get_or_create_actor_path(&path, actor_fn).await
     }
 
@@ -125,28 +324,96 @@ impl<E: SystemEvent> ActorSystem<E> {
     /// Stops the actor on this actor system. All its children will also be stopped.
     pub async fn stop_actor(&self, path: &ActorPath) {
         log::debug!("Stopping actor '{}' on system '{}'...", &path, &self.name);
-        let mut paths: Vec<ActorPath> = vec![path.clone()];
+        let mut actors = self.actors.write().await;
+        Self::remove_tree(&mut actors, path);
+    }
+
+    /// Like `stop_actor`, but returns only once the actor and its children have finished their
+    /// `post_stop`, so a replacement created at the same path starts after they are gone.
+    pub async fn stop_actor_and_wait(&self, path: &ActorPath) {
+        log::debug!("Stopping actor '{}' on system '{}'...", &path, &self.name);
+        let stopping = {
+            let mut actors = self.actors.write().await;
+            Self::remove_tree(&mut actors, path)
+        };
+        for stop in stopping {
+            stop.stopped().await;
+        }
+    }
+
+    /// Cancels every scheduled task, then stops every actor on this actor system and waits for
+    /// each to finish its `post_stop`.
+    pub async fn stop_all(&self) {
+        log::debug!("Stopping all actors on system '{}'...", &self.name);
+        self.scheduler.cancel();
+        let stopping: Vec<StopHandle> = {
+            let mut actors = self.actors.write().await;
+            actors
+                .drain()
+                .map(|(_, entry)| {
+                    entry.stop.stop();
+                    entry.stop
+                })
+                .collect()
+        };
+
+        for stop in stopping {
+            stop.stopped().await;
+        }
+    }
+
+    /// Called by a runner once it has stopped. Only deregisters the path if it still
+    /// belongs to that runner, so a freshly created actor at the same path is left alone.
+    pub(crate) async fn release_actor(&self, path: &ActorPath, stop: &StopHandle) {
+        let mut actors = self.actors.write().await;
+        if actors
+            .get(path)
+            .is_some_and(|entry| entry.stop.is_same(stop))
         {
-            let running_actors = self.actors.read().await;
-            for running in running_actors.keys() {
-                if running.is_descendant_of(path) {
-                    paths.push(running.clone());
-                }
+            Self::remove_tree(&mut actors, path);
+        }
+    }
+
+    /// Deregisters `path` and everything below it, signalling each to stop. Returns their
+    /// stop handles.
+    fn remove_tree(
+        actors: &mut HashMap<ActorPath, ActorEntry>,
+        path: &ActorPath,
+    ) -> Vec<StopHandle> {
+        let mut paths: Vec<ActorPath> = vec![path.clone()];
+        for running in actors.keys() {
+            if running.is_descendant_of(path) {
+                paths.push(running.clone());
             }
         }
         paths.sort_unstable();
         paths.reverse();
-        let mut actors = self.actors.write().await;
+        let mut stopping = Vec::new();
         for path in &paths {
-            actors.remove(path);
+            if let Some(entry) = actors.remove(path) {
+                entry.stop.stop();
+                stopping.push(entry.stop);
+            }
         }
+        stopping
     }
 
     /// Creates a new actor system on which you can create actors.
     pub fn new(name: &str, bus: EventBus<E>) -> Self {
         let name = name.to_string();
         let actors = Arc::new(RwLock::new(HashMap::new()));
-        ActorSystem { name, actors, bus }
+        let dead_letters = EventBus::new(DEAD_LETTER_CAPACITY);
+        let lifecycle = EventBus::new(LIFECYCLE_CAPACITY);
+        ActorSystem {
+            name,
+            actors,
+            bus,
+            dead_letters,
+            lifecycle,
+            slow_handler_threshold: None,
+            scheduler: Scheduler::new(),
+            restarts: Arc::new(Mutex::new(HashMap::new())),
+        }
     }
 }
 
@@ -1,3 +1,9 @@
+mod pipeline;
+mod thresholds;
 mod validation;
 
-pub use validation::{ValidationResult, validate_movement};
+pub use pipeline::{MoveContext, ValidatorChain};
+pub use thresholds::{AntiCheatConfig, SharedAntiCheatConfig};
+pub use validation::{
+    MovementLimits, ValidationResult, clamp_to_bounds, is_in_bounds, is_teleport,
+};
@@ -0,0 +1,134 @@
+use crate::anticheat::validation::{MAX_DELTA_TIME, ValidationResult};
+use crate::types::Position;
+
+/// A single move as seen by the validators.
+#[derive(Debug, Clone)]
+pub struct MoveContext<'a> {
+    pub old_pos: &'a Position,
+    pub new_pos: &'a Position,
+    pub velocity: &'a Position,
+    /// Velocity accepted with the player's previous move.
+    pub previous_velocity: &'a Position,
+    /// Client frame time, clamped to `[0, MAX_DELTA_TIME]`. Non-finite values become 0.
+    pub delta_time: f64,
+}
+
+impl<'a> MoveContext<'a> {
+    pub fn new(
+        old_pos: &'a Position,
+        new_pos: &'a Position,
+        velocity: &'a Position,
+        previous_velocity: &'a Position,
+        delta_time: f32,
+    ) -> Self {
+        let delta_time = if delta_time.is_finite() {
+            f64::from(delta_time.clamp(0.0, MAX_DELTA_TIME))
+        } else {
+            0.0
+        };
+        Self {
+            old_pos,
+            new_pos,
+            velocity,
+            previous_velocity,
+            delta_time,
+        }
+    }
+
+    /// Lowers the `delta_time` ceiling below `MAX_DELTA_TIME`.
+    pub fn with_max_delta_time(mut self, max_delta_time: f32) -> Self {
+        self.delta_time = self.delta_time.min(f64::from(max_delta_time));
+        self
+    }
+
+    pub fn distance(&self) -> f64 {
+        self.old_pos.distance_to(self.new_pos)
+    }
+}
+
+/// One anti-cheat check. Returns the failure it detected, or `None` if the move passes.
+pub trait MovementValidator: Send + Sync {
+    fn check(&self, ctx: &MoveContext) -> Option<ValidationResult>;
+}
+
+/// Runs validators in order and reports the first failure.
+#[derive(Default)]
+pub struct ValidatorChain {
+    validators: Vec<Box<dyn MovementValidator>>,
+}
+
+impl ValidatorChain {
+    pub fn new(validators: Vec<Box<dyn MovementValidator>>) -> Self {
+        Self { validators }
+    }
+
+    pub fn check(&self, ctx: &MoveContext) -> ValidationResult {
+        self.validators
+            .iter()
+            .find_map(|validator| validator.check(ctx))
+            .unwrap_or(ValidationResult::Valid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anticheat::validation::{BoundsValidator, SpeedValidator};
+    use crate::types::{MAX_SPEED, WorldBounds};
+
+    fn bounds() -> Box<dyn MovementValidator> {
+        Box::new(BoundsValidator {
+            bounds: WorldBounds::cube(100.0),
+        })
+    }
+
+    fn speed() -> Box<dyn MovementValidator> {
+        Box::new(SpeedValidator {
+            max_speed: MAX_SPEED,
+            tolerance_factor: 1.0,
+        })
+    }
+
+    #[test]
+    fn test_pass_is_order_independent() {
+        let (old_pos, new_pos) = (Position::new(0.0, 0.0, 0.0), Position::new(5.0, 0.0, 0.0));
+        let velocity = Position::new(50.0, 0.0, 0.0);
+        let ctx = MoveContext::new(&old_pos, &new_pos, &velocity, &velocity, 0.1);
+
+        for chain in [
+            ValidatorChain::new(vec![bounds(), speed()]),
+            ValidatorChain::new(vec![speed(), bounds()]),
+        ] {
+            assert_eq!(chain.check(&ctx), ValidationResult::Valid);
+        }
+    }
+
+    #[test]
+    fn test_first_failure_wins() {
+        // Both out of bounds and too fast for the frame.
+        let (old_pos, new_pos) = (
+            Position::new(90.0, 0.0, 0.0),
+            Position::new(150.0, 0.0, 0.0),
+        );
+        let velocity = Position::new(50.0, 0.0, 0.0);
+        let ctx = MoveContext::new(&old_pos, &new_pos, &velocity, &velocity, 0.1);
+
+        let bounds_first = ValidatorChain::new(vec![bounds(), speed()]);
+        assert_eq!(bounds_first.check(&ctx), ValidationResult::OutOfBounds);
+
+        let speed_first = ValidatorChain::new(vec![speed(), bounds()]);
+        assert_eq!(speed_first.check(&ctx), ValidationResult::SpeedHack);
+    }
+
+    #[test]
+    fn test_empty_chain_accepts_everything() {
+        let (old_pos, new_pos) = (Position::default(), Position::new(1e6, 0.0, 0.0));
+        let velocity = Position::default();
+        let ctx = MoveContext::new(&old_pos, &new_pos, &velocity, &velocity, 0.1);
+
+        assert_eq!(
+            ValidatorChain::default().check(&ctx),
+            ValidationResult::Valid
+        );
+    }
+}
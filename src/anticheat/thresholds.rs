@@ -0,0 +1,55 @@
+use crate::config::ServerConfig;
+use crate::types::{AntiCheatRequest, MAX_SPEED, TELEPORT_THRESHOLD};
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
+
+/// Live anti-cheat thresholds, shared by every player actor and updated through
+/// `/admin/anticheat`.
+pub type SharedAntiCheatConfig = Arc<RwLock<AntiCheatConfig>>;
+
+/// The anti-cheat thresholds admins can tune without a restart.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AntiCheatConfig {
+    pub max_speed: f64,
+    /// Longest single move, in world units, before it counts as a teleport.
+    pub teleport_threshold: f64,
+    /// Multiplier on `max_speed * delta_time` allowed for the distance covered in one move.
+    pub speed_tolerance_factor: f64,
+}
+
+impl AntiCheatConfig {
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self {
+            max_speed: MAX_SPEED,
+            teleport_threshold: TELEPORT_THRESHOLD,
+            speed_tolerance_factor: f64::from(config.speed_tolerance_factor),
+        }
+    }
+
+    /// A shared handle on the thresholds `config` starts with.
+    pub fn shared(config: &ServerConfig) -> SharedAntiCheatConfig {
+        Arc::new(RwLock::new(Self::from_config(config)))
+    }
+
+    /// These thresholds with `update` applied. Every value must be positive and finite.
+    pub fn updated(&self, update: &AntiCheatRequest) -> Result<Self, String> {
+        let updated = Self {
+            max_speed: update.max_speed.unwrap_or(self.max_speed),
+            teleport_threshold: update.teleport_threshold.unwrap_or(self.teleport_threshold),
+            speed_tolerance_factor: update
+                .speed_tolerance_factor
+                .unwrap_or(self.speed_tolerance_factor),
+        };
+
+        for (field, value) in [
+            ("max_speed", updated.max_speed),
+            ("teleport_threshold", updated.teleport_threshold),
+            ("speed_tolerance_factor", updated.speed_tolerance_factor),
+        ] {
+            if !value.is_finite() || value <= 0.0 {
+                return Err(format!("{} must be a positive number", field));
+            }
+        }
+        Ok(updated)
+    }
+}
@@ -1,57 +1,305 @@
-use crate::types::{Position, TELEPORT_THRESHOLD, WORLD_BOUNDS};
+use crate::anticheat::pipeline::{MoveContext, MovementValidator, ValidatorChain};
+use crate::anticheat::thresholds::AntiCheatConfig;
+use crate::config::ServerConfig;
+use crate::types::{MAX_SPEED, Position, TELEPORT_THRESHOLD, WorldBounds};
+use serde::Serialize;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum ValidationResult {
     Valid,
     SpeedHack,
     Teleport,
     OutOfBounds,
+    VelocityMismatch,
+    ImpossibleAcceleration,
+    /// A coordinate was NaN or infinite.
+    Invalid,
 }
 
-pub fn validate_movement(
-    old_pos: &Position,
-    new_pos: &Position,
-    _velocity: &Position,
-    delta_time: f32,
-    max_speed: f32,
-) -> ValidationResult {
-    if !is_in_bounds(new_pos, WORLD_BOUNDS) {
-        return ValidationResult::OutOfBounds;
+/// Headroom allowed on the reported velocity magnitude over `max_speed`.
+const VELOCITY_TOLERANCE: f64 = 1.1;
+
+/// Absolute slack (in world units) when comparing integrated velocity to displacement.
+const VELOCITY_SLACK: f64 = 1.0;
+
+/// Longest frame a client may claim, so a huge `delta_time` can't legitimize a teleport.
+/// `max_delta_time` in the config can only lower it.
+pub const MAX_DELTA_TIME: f32 = 1.0;
+
+/// Tunables for the standard validator chain.
+#[derive(Debug, Clone)]
+pub struct MovementLimits {
+    pub max_speed: f64,
+    /// Multiplier on `max_speed * delta_time` allowed for the distance covered in one move.
+    pub speed_tolerance_factor: f64,
+    pub mismatch_factor: f64,
+    /// Fraction of the larger speed the reported velocity may differ from the derived one by.
+    /// 0 leaves the derived velocity check out of the standard chain.
+    pub derivation_tolerance: f64,
+    /// 0 leaves the acceleration check out of the standard chain.
+    pub max_acceleration: f64,
+    pub teleport_threshold: f64,
+    pub bounds: WorldBounds,
+}
+
+impl MovementLimits {
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self {
+            max_speed: MAX_SPEED,
+            speed_tolerance_factor: f64::from(config.speed_tolerance_factor),
+            mismatch_factor: f64::from(config.velocity_mismatch_factor),
+            derivation_tolerance: f64::from(config.velocity_derivation_tolerance_pct) / 100.0,
+            max_acceleration: f64::from(config.max_acceleration),
+            teleport_threshold: TELEPORT_THRESHOLD,
+            bounds: config.world_bounds.clone(),
+        }
+    }
+
+    /// Takes the tunable limits from `thresholds` instead.
+    pub fn with_thresholds(mut self, thresholds: &AntiCheatConfig) -> Self {
+        self.max_speed = thresholds.max_speed;
+        self.teleport_threshold = thresholds.teleport_threshold;
+        self.speed_tolerance_factor = thresholds.speed_tolerance_factor;
+        self
+    }
+}
+
+impl ValidatorChain {
+    /// Bounds, teleport, speed, velocity mismatch, derived velocity and acceleration checks, in
+    /// that order.
+    pub fn standard(limits: &MovementLimits) -> Self {
+        let mut validators: Vec<Box<dyn MovementValidator>> = vec![
+            Box::new(BoundsValidator {
+                bounds: limits.bounds.clone(),
+            }),
+            Box::new(TeleportValidator {
+                max_distance: limits.teleport_threshold,
+            }),
+            Box::new(SpeedValidator {
+                max_speed: limits.max_speed,
+                tolerance_factor: limits.speed_tolerance_factor,
+            }),
+            Box::new(VelocityMismatchValidator {
+                factor: limits.mismatch_factor,
+            }),
+        ];
+        if limits.derivation_tolerance > 0.0 {
+            validators.push(Box::new(DerivedVelocityValidator {
+                tolerance: limits.derivation_tolerance,
+            }));
+        }
+        if limits.max_acceleration > 0.0 {
+            validators.push(Box::new(AccelerationValidator {
+                max_acceleration: limits.max_acceleration,
+            }));
+        }
+        Self::new(validators)
+    }
+}
+
+/// Rejects positions outside the world.
+pub struct BoundsValidator {
+    pub bounds: WorldBounds,
+}
+
+impl MovementValidator for BoundsValidator {
+    fn check(&self, ctx: &MoveContext) -> Option<ValidationResult> {
+        (!is_in_bounds(ctx.new_pos, &self.bounds)).then_some(ValidationResult::OutOfBounds)
     }
+}
 
-    let distance = old_pos.distance_to(new_pos);
+/// Rejects single moves longer than `max_distance`, whatever the frame time.
+pub struct TeleportValidator {
+    pub max_distance: f64,
+}
 
-    if is_teleport(old_pos, new_pos, TELEPORT_THRESHOLD) {
-        return ValidationResult::Teleport;
+impl MovementValidator for TeleportValidator {
+    fn check(&self, ctx: &MoveContext) -> Option<ValidationResult> {
+        is_teleport(ctx.old_pos, ctx.new_pos, self.max_distance)
+            .then_some(ValidationResult::Teleport)
     }
+}
+
+/// Rejects moves covering more ground than `max_speed` allows, or reporting a velocity above it.
+pub struct SpeedValidator {
+    pub max_speed: f64,
+    /// Multiplier on `max_speed * delta_time` allowed for the distance covered in one move.
+    pub tolerance_factor: f64,
+}
+
+impl MovementValidator for SpeedValidator {
+    fn check(&self, ctx: &MoveContext) -> Option<ValidationResult> {
+        let distance = ctx.distance();
+        let max_allowed = self.max_speed * ctx.delta_time * self.tolerance_factor;
 
-    let max_allowed = max_speed * delta_time * 3.0;
+        if distance > max_allowed {
+            log::debug!(
+                "Speed check: distance={:.2}, max={:.2}, dt={:.4}",
+                distance,
+                max_allowed,
+                ctx.delta_time
+            );
+            return Some(ValidationResult::SpeedHack);
+        }
 
-    if distance > max_allowed {
+        if ctx.velocity.magnitude() > self.max_speed * VELOCITY_TOLERANCE {
+            log::debug!(
+                "Velocity check: speed={:.2}, max={:.2}",
+                ctx.velocity.magnitude(),
+                self.max_speed
+            );
+            return Some(ValidationResult::SpeedHack);
+        }
+
+        None
+    }
+}
+
+/// Rejects moves whose reported velocity doesn't account for the distance covered.
+pub struct VelocityMismatchValidator {
+    pub factor: f64,
+}
+
+impl MovementValidator for VelocityMismatchValidator {
+    fn check(&self, ctx: &MoveContext) -> Option<ValidationResult> {
+        is_velocity_mismatch(ctx.distance(), ctx.velocity, ctx.delta_time, self.factor)
+            .then_some(ValidationResult::VelocityMismatch)
+    }
+}
+
+/// Rejects moves whose reported velocity points somewhere the player didn't go.
+///
+/// Derives the velocity from the displacement over `delta_time` and compares the two as
+/// vectors, which catches a fabricated velocity with a plausible magnitude that
+/// [`VelocityMismatchValidator`] lets through.
+pub struct DerivedVelocityValidator {
+    /// Fraction of the larger of the two speeds the vectors may differ by.
+    pub tolerance: f64,
+}
+
+impl MovementValidator for DerivedVelocityValidator {
+    fn check(&self, ctx: &MoveContext) -> Option<ValidationResult> {
+        is_derived_velocity_mismatch(
+            ctx.old_pos,
+            ctx.new_pos,
+            ctx.velocity,
+            ctx.delta_time,
+            self.tolerance,
+        )
+        .then_some(ValidationResult::VelocityMismatch)
+    }
+}
+
+/// Rejects velocity changes sharper than `max_acceleration` allows over the frame,
+/// such as reversing direction at full speed from one move to the next.
+pub struct AccelerationValidator {
+    /// Units per second squared.
+    pub max_acceleration: f64,
+}
+
+impl MovementValidator for AccelerationValidator {
+    fn check(&self, ctx: &MoveContext) -> Option<ValidationResult> {
+        let change = ctx.velocity.distance_to(ctx.previous_velocity);
+        let max_change = self.max_acceleration * ctx.delta_time;
+        if change > max_change {
+            log::debug!(
+                "Acceleration check: dv={:.2}, max={:.2}, dt={:.4}",
+                change,
+                max_change,
+                ctx.delta_time
+            );
+            return Some(ValidationResult::ImpossibleAcceleration);
+        }
+        None
+    }
+}
+
+/// Checks whether the distance covered by `velocity * delta_time` and the actual
+/// displacement differ by more than `factor` in either direction.
+pub fn is_velocity_mismatch(
+    distance: f64,
+    velocity: &Position,
+    delta_time: f64,
+    factor: f64,
+) -> bool {
+    let integrated = velocity.magnitude() * delta_time;
+    distance > integrated * factor + VELOCITY_SLACK
+        || integrated > distance * factor + VELOCITY_SLACK
+}
+
+/// Checks whether `velocity` differs from `(new_pos - old_pos) / delta_time` by more than
+/// `tolerance` of the larger speed. Compared over the frame's displacement, with the same
+/// absolute slack as [`is_velocity_mismatch`], so tiny moves don't trip it.
+pub fn is_derived_velocity_mismatch(
+    old_pos: &Position,
+    new_pos: &Position,
+    velocity: &Position,
+    delta_time: f64,
+    tolerance: f64,
+) -> bool {
+    let moved = Position::new(
+        new_pos.x - old_pos.x,
+        new_pos.y - old_pos.y,
+        new_pos.z - old_pos.z,
+    );
+    let integrated = Position::new(
+        velocity.x * delta_time,
+        velocity.y * delta_time,
+        velocity.z * delta_time,
+    );
+    let difference = moved.distance_to(&integrated);
+    let allowed = moved.magnitude().max(integrated.magnitude()) * tolerance + VELOCITY_SLACK;
+    if difference > allowed {
         log::debug!(
-            "Speed check: distance={:.2}, max={:.2}, dt={:.4}",
-            distance,
-            max_allowed,
+            "Derived velocity check: difference={:.2}, allowed={:.2}, dt={:.4}",
+            difference,
+            allowed,
             delta_time
         );
-        return ValidationResult::SpeedHack;
+        return true;
     }
-
-    ValidationResult::Valid
+    false
 }
 
-pub fn is_teleport(old_pos: &Position, new_pos: &Position, max_distance: f32) -> bool {
+pub fn is_teleport(old_pos: &Position, new_pos: &Position, max_distance: f64) -> bool {
     old_pos.distance_to(new_pos) > max_distance
 }
 
-pub fn is_in_bounds(pos: &Position, bounds: f32) -> bool {
-    pos.x.abs() <= bounds && pos.y.abs() <= bounds && pos.z.abs() <= bounds
+pub fn is_in_bounds(pos: &Position, bounds: &WorldBounds) -> bool {
+    (bounds.min.x..=bounds.max.x).contains(&pos.x)
+        && (bounds.min.y..=bounds.max.y).contains(&pos.y)
+        && (bounds.min.z..=bounds.max.z).contains(&pos.z)
+}
+
+/// The nearest point to `pos` inside `bounds`.
+pub fn clamp_to_bounds(pos: &Position, bounds: &WorldBounds) -> Position {
+    Position::new(
+        pos.x.clamp(bounds.min.x, bounds.max.x),
+        pos.y.clamp(bounds.min.y, bounds.max.y),
+        pos.z.clamp(bounds.min.z, bounds.max.z),
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::MAX_SPEED;
+    use crate::types::WORLD_BOUNDS;
+
+    fn validate_movement(
+        old_pos: &Position,
+        new_pos: &Position,
+        velocity: &Position,
+        delta_time: f32,
+        limits: &MovementLimits,
+    ) -> ValidationResult {
+        ValidatorChain::standard(limits).check(&MoveContext::new(
+            old_pos, new_pos, velocity, velocity, delta_time,
+        ))
+    }
+
+    fn limits() -> MovementLimits {
+        MovementLimits::from_config(&ServerConfig::default())
+    }
 
     #[test]
     fn test_valid_movement() {
@@ -59,24 +307,24 @@ mod tests {
         let new_pos = Position::new(1.0, 0.0, 0.0);
         let velocity = Position::new(10.0, 0.0, 0.0);
 
-        let result = validate_movement(&old_pos, &new_pos, &velocity, 0.1, MAX_SPEED);
+        let result = validate_movement(&old_pos, &new_pos, &velocity, 0.1, &limits());
         assert_eq!(result, ValidationResult::Valid);
     }
 
     #[test]
     fn test_speed_hack() {
         let old_pos = Position::new(0.0, 0.0, 0.0);
-        let new_pos = Position::new(15.0, 0.0, 0.0);
+        let new_pos = Position::new(50.0, 0.0, 0.0);
         let velocity = Position::new(10.0, 0.0, 0.0);
 
-        let result = validate_movement(&old_pos, &new_pos, &velocity, 0.1, MAX_SPEED);
+        let result = validate_movement(&old_pos, &new_pos, &velocity, 0.1, &limits());
         assert_eq!(result, ValidationResult::SpeedHack);
     }
 
     #[test]
     fn test_teleport() {
         let old_pos = Position::new(0.0, 0.0, 0.0);
-        let new_pos = Position::new(25.0, 0.0, 0.0);
+        let new_pos = Position::new(350.0, 0.0, 0.0);
 
         assert!(is_teleport(&old_pos, &new_pos, TELEPORT_THRESHOLD));
     }
@@ -84,6 +332,181 @@ mod tests {
     #[test]
     fn test_out_of_bounds() {
         let pos = Position::new(1001.0, 0.0, 0.0);
-        assert!(!is_in_bounds(&pos, WORLD_BOUNDS));
+        assert!(!is_in_bounds(&pos, &WorldBounds::cube(WORLD_BOUNDS)));
+    }
+
+    fn flat_map() -> WorldBounds {
+        WorldBounds {
+            min: Position::new(-1000.0, 0.0, -1000.0),
+            max: Position::new(1000.0, 50.0, 1000.0),
+        }
+    }
+
+    #[test]
+    fn test_clamp_to_bounds() {
+        let bounds = flat_map();
+
+        let clamped = clamp_to_bounds(&Position::new(1000.5, 51.0, -3.0), &bounds);
+        assert_eq!(clamped, Position::new(1000.0, 50.0, -3.0));
+        assert!(is_in_bounds(&clamped, &bounds));
+
+        let inside = Position::new(1.0, 2.0, 3.0);
+        assert_eq!(clamp_to_bounds(&inside, &bounds), inside);
+    }
+
+    #[test]
+    fn test_asymmetric_bounds() {
+        let bounds = flat_map();
+
+        assert!(is_in_bounds(&Position::new(900.0, 50.0, -900.0), &bounds));
+        assert!(!is_in_bounds(&Position::new(0.0, 51.0, 0.0), &bounds));
+        assert!(!is_in_bounds(&Position::new(0.0, -1.0, 0.0), &bounds));
+    }
+
+    #[test]
+    fn test_flying_above_flat_map_out_of_bounds() {
+        let old_pos = Position::new(0.0, 49.0, 0.0);
+        let new_pos = Position::new(0.0, 52.0, 0.0);
+        let velocity = Position::new(0.0, 30.0, 0.0);
+
+        let result = validate_movement(
+            &old_pos,
+            &new_pos,
+            &velocity,
+            0.1,
+            &MovementLimits {
+                bounds: flat_map(),
+                ..limits()
+            },
+        );
+        assert_eq!(result, ValidationResult::OutOfBounds);
+    }
+
+    #[test]
+    fn test_velocity_over_cap() {
+        let old_pos = Position::new(0.0, 0.0, 0.0);
+        let new_pos = Position::new(1.0, 0.0, 0.0);
+        let velocity = Position::new(MAX_SPEED * 10.0, 0.0, 0.0);
+
+        let result = validate_movement(&old_pos, &new_pos, &velocity, 0.1, &limits());
+        assert_eq!(result, ValidationResult::SpeedHack);
+    }
+
+    #[test]
+    fn test_velocity_mismatch() {
+        let old_pos = Position::new(0.0, 0.0, 0.0);
+        let new_pos = Position::new(9.0, 0.0, 0.0);
+        let velocity = Position::new(0.0, 0.0, 0.0);
+
+        let result = validate_movement(&old_pos, &new_pos, &velocity, 0.1, &limits());
+        assert_eq!(result, ValidationResult::VelocityMismatch);
+    }
+
+    #[test]
+    fn test_derived_velocity_matches_reported() {
+        let old_pos = Position::new(0.0, 0.0, 0.0);
+        let new_pos = Position::new(6.0, 0.0, 8.0);
+        let velocity = Position::new(60.0, 0.0, 80.0);
+
+        assert!(!is_derived_velocity_mismatch(
+            &old_pos, &new_pos, &velocity, 0.1, 0.1
+        ));
+        let result = validate_movement(&old_pos, &new_pos, &velocity, 0.1, &limits());
+        assert_eq!(result, ValidationResult::Valid);
+    }
+
+    #[test]
+    fn test_fabricated_velocity_flagged() {
+        let old_pos = Position::new(0.0, 0.0, 0.0);
+        let new_pos = Position::new(10.0, 0.0, 0.0);
+        // The right speed, pointing the other way.
+        let velocity = Position::new(-100.0, 0.0, 0.0);
+
+        let magnitude_only = VelocityMismatchValidator {
+            factor: limits().mismatch_factor,
+        };
+        let ctx = MoveContext::new(&old_pos, &new_pos, &velocity, &velocity, 0.1);
+        assert_eq!(magnitude_only.check(&ctx), None);
+
+        let result = validate_movement(&old_pos, &new_pos, &velocity, 0.1, &limits());
+        assert_eq!(result, ValidationResult::VelocityMismatch);
+    }
+
+    #[test]
+    fn test_tighter_speed_tolerance_flags_move() {
+        let old_pos = Position::new(0.0, 0.0, 0.0);
+        let new_pos = Position::new(20.0, 0.0, 0.0);
+        let velocity = Position::new(MAX_SPEED, 0.0, 0.0);
+
+        let lenient = validate_movement(&old_pos, &new_pos, &velocity, 0.1, &limits());
+        assert_eq!(lenient, ValidationResult::Valid);
+
+        let strict = MovementLimits {
+            speed_tolerance_factor: 1.5,
+            ..limits()
+        };
+        let result = validate_movement(&old_pos, &new_pos, &velocity, 0.1, &strict);
+        assert_eq!(result, ValidationResult::SpeedHack);
+    }
+
+    #[test]
+    fn test_huge_delta_time_clamped() {
+        let old_pos = Position::new(0.0, 0.0, 0.0);
+        let new_pos = Position::new(200.0, 0.0, 0.0);
+        let velocity = Position::new(MAX_SPEED, 0.0, 0.0);
+        let strict = MovementLimits {
+            speed_tolerance_factor: 1.5,
+            ..limits()
+        };
+
+        for delta_time in [10.0, f32::INFINITY, f32::NAN] {
+            let result = validate_movement(&old_pos, &new_pos, &velocity, delta_time, &strict);
+            assert_eq!(result, ValidationResult::SpeedHack);
+        }
+    }
+
+    #[test]
+    fn test_full_reversal_flagged() {
+        let old_pos = Position::new(0.0, 0.0, 0.0);
+        let new_pos = Position::new(-1.6, 0.0, 0.0);
+        let previous = Position::new(MAX_SPEED, 0.0, 0.0);
+        let reversed = Position::new(-MAX_SPEED, 0.0, 0.0);
+
+        let ctx = MoveContext::new(&old_pos, &new_pos, &reversed, &previous, 0.016);
+        assert_eq!(
+            ValidatorChain::standard(&limits()).check(&ctx),
+            ValidationResult::ImpossibleAcceleration
+        );
+    }
+
+    #[test]
+    fn test_gradual_turn_passes() {
+        let limits = limits();
+        let mut position = Position::new(0.0, 0.0, 0.0);
+        let mut velocity = Position::new(MAX_SPEED * 0.5, 0.0, 0.0);
+
+        // A quarter turn spread over 30 frames.
+        for frame in 1..=30 {
+            let angle = std::f64::consts::FRAC_PI_2 * frame as f64 / 30.0;
+            let next_velocity = Position::new(
+                MAX_SPEED * 0.5 * angle.cos(),
+                0.0,
+                MAX_SPEED * 0.5 * angle.sin(),
+            );
+            let next_position = Position::new(
+                position.x + next_velocity.x * 0.016,
+                0.0,
+                position.z + next_velocity.z * 0.016,
+            );
+
+            let ctx = MoveContext::new(&position, &next_position, &next_velocity, &velocity, 0.016);
+            assert_eq!(
+                ValidatorChain::standard(&limits).check(&ctx),
+                ValidationResult::Valid
+            );
+
+            position = next_position;
+            velocity = next_velocity;
+        }
     }
 }
@@ -1,60 +1,1098 @@
+use crate::actor_system::OverflowPolicy;
+use crate::types::{Position, WorldBounds};
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
 use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use thiserror::Error;
+use warp::http::Uri;
 
-#[derive(Debug, Clone)]
+/// Config file read by `ServerConfig::load` when `CONFIG_PATH` isn't set.
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Placeholder JWT secret, only accepted in debug mode.
+const INSECURE_JWT_SECRET: &str = "your-secret-key-change-in-production";
+
+/// Shortest JWT secret accepted outside debug mode, matching HS256's key size.
+const MIN_JWT_SECRET_BYTES: usize = 32;
+
+/// Most moves a player's audit log may keep, so the buffer can't grow without bound.
+const MAX_AUDIT_LOG_SIZE: usize = 1024;
+
+/// Reasons a configuration can't be loaded.
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("failed to read {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("invalid config file: {0}")]
+    Parse(#[from] toml::de::Error),
+
+    #[error("invalid {field}: {reason}")]
+    Invalid { field: &'static str, reason: String },
+
+    #[error("invalid {var} environment variable: {value:?}")]
+    Env { var: &'static str, value: String },
+}
+
+/// Encoding used for websocket frames sent to clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    Json,
+    Bincode,
+}
+
+impl FromStr for WireFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(WireFormat::Json),
+            "bincode" => Ok(WireFormat::Bincode),
+            other => Err(format!("unknown wire format: {}", other)),
+        }
+    }
+}
+
+/// What anti-cheat does about a move that fails validation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AntiCheatMode {
+    /// Reject the move, tell the client and kick repeat offenders.
+    Enforce,
+    /// Count, log and publish violations but accept the move anyway, so new checks can be
+    /// observed before they affect players.
+    LogOnly,
+    /// Skip validation entirely.
+    Disabled,
+}
+
+impl FromStr for AntiCheatMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "enforce" => Ok(AntiCheatMode::Enforce),
+            "log_only" => Ok(AntiCheatMode::LogOnly),
+            "disabled" => Ok(AntiCheatMode::Disabled),
+            other => Err(format!("unknown anti-cheat mode: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ServerConfig {
     pub port: u16,
+    pub max_players: usize,
+    /// Spectator sockets allowed at once, on top of `max_players`. 0 turns spectators away.
+    pub max_spectators: usize,
+    /// Above this many connections `/auth` answers 503 until the count falls below
+    /// `overload_low_water`. 0 disables the breaker.
+    pub overload_high_water: usize,
+    /// 0 uses 80% of `overload_high_water`.
+    pub overload_low_water: usize,
     pub debug_mode: bool,
+    /// Seeds debug session ids and spawn jitter in debug mode, for reproducible test runs.
+    pub debug_seed: Option<u64>,
     pub rpc_url: String,
+    /// One mint or a comma separated list; holding any of them grants entry.
     pub token_mint: String,
+    /// Base units of an accepted mint a wallet must hold to authenticate.
+    pub min_token_balance: u64,
     pub jwt_secret: String,
     pub tickrate_ms: u64,
+    /// `(max_players, tickrate_ms)` buckets sorted by `max_players`. Empty keeps `tickrate_ms` fixed.
+    pub tickrate_ramp: Vec<(usize, u64)>,
+    /// Fixed steps per second of the world simulation, independent of the broadcast tickrate.
+    pub sim_hz: u32,
+    pub interest_radius: f32,
+    pub speed_tolerance_factor: f32,
+    pub velocity_mismatch_factor: f32,
+    /// How far, as a percentage of the larger speed, the reported velocity may stray from the
+    /// one derived from the displacement. 0 disables the check.
+    pub velocity_derivation_tolerance_pct: f32,
+    pub anticheat_mode: AntiCheatMode,
+    /// Longest `delta_time` in seconds a move is credited with, however much time it claims.
+    pub max_delta_time: f32,
+    /// Largest velocity change per second a move may report. 0 disables the check.
+    pub max_acceleration: f32,
+    pub attack_range: f32,
+    pub attack_damage: f32,
+    pub reconnect_grace_ms: u64,
+    pub refresh_grace_hours: u64,
+    /// Failed signature checks allowed per wallet, and per client IP, within
+    /// `auth_rate_window_secs` before further auth requests from either are refused.
+    pub auth_rate_limit: u32,
+    pub auth_rate_window_secs: u64,
+    /// Logins a wallet may make per `reauth_window_secs`, across all of its characters,
+    /// before it has to cool down. 0 disables the limit.
+    pub reauth_limit: u32,
+    pub reauth_window_secs: u64,
+    /// Characters of one wallet that may hold a session at once. 0 disables the cap.
+    pub max_characters_per_wallet: usize,
+    /// Per-connection messages allowed per second, by kind; a `MoveBatch` spends one per sample.
+    /// 0 disables the limit for that kind.
+    pub move_rate_limit: u32,
+    pub chat_rate_limit: u32,
+    pub attack_rate_limit: u32,
+    pub get_state_rate_limit: u32,
+    pub time_sync_rate_limit: u32,
+    pub challenge_ttl_secs: u64,
+    /// How often live sessions have their token ownership re-checked. 0 disables.
+    pub reverify_interval_secs: u64,
+    pub use_delta_updates: bool,
+    /// Send `InterpUpdate` with each player's last two positions instead of full or delta updates.
+    pub send_interpolation: bool,
+    pub wire_format: WireFormat,
+    /// Reject client JSON carrying fields the protocol doesn't define instead of ignoring them.
+    /// Off by default so clients newer than the server keep working.
+    pub strict_protocol: bool,
+    /// Gzip periodic state broadcasts into binary frames that clients inflate.
+    pub compress_broadcasts: bool,
+    pub admin_token: String,
+    /// Ban length used by `/admin/ban` when the request doesn't give one.
+    pub ban_duration_secs: u64,
+    /// How long a player kicked by anti-cheat has their address banned. 0 disables.
+    pub auto_ban_secs: u64,
+    pub world_bounds: WorldBounds,
+    pub spawn_point: Position,
+    /// Radius on the x/z plane around `spawn_point` new players are scattered within.
+    pub spawn_jitter: f32,
+    /// A player's first move only has to land inside the world and within this distance of
+    /// `spawn_point`; later moves get the full checks. 0 validates the first move like any other.
+    pub spawn_grace_radius: f32,
+    pub heartbeat_interval_ms: u64,
+    pub heartbeat_timeout_ms: u64,
+    pub violation_decay_secs: u64,
+    /// How close to a server-allowed warp target the next move must land to skip the teleport check.
+    pub warp_tolerance: f32,
+    /// How long a server-allowed warp stays usable.
+    pub warp_timeout_ms: u64,
+    /// Authoritative positions each player keeps for validating late moves. 0 disables.
+    pub reconcile_history: usize,
+    /// How far in the past a timestamped move may be to be validated against history.
+    pub reconcile_window_ms: u64,
+    /// Recent moves each player keeps for `/admin/audit`. 0 disables.
+    pub audit_log_size: usize,
+    /// Stops player actors that receive no messages for this long. 0 disables.
+    pub afk_timeout_ms: u64,
+    /// Consecutive undecodable frames after which a connection is closed. 0 disables.
+    pub max_invalid_messages: u32,
+    /// Frames a connection may have queued before broadcasts skip it. 0 disables.
+    pub max_send_queue: usize,
+    /// How long a connection may stay over `max_send_queue` before it is dropped.
+    pub slow_consumer_timeout_ms: u64,
+    /// Frames a connection's outbound channel holds. A send that finds it full drops the
+    /// connection as too slow, so it must leave room above `max_send_queue`.
+    pub send_buffer: usize,
+    /// Messages a player actor's mailbox holds before further ones are refused. 0 leaves it
+    /// unbounded.
+    pub player_mailbox_capacity: usize,
+    pub player_store_path: Option<String>,
+    /// Word list, one word per line, that nicknames are checked against at auth. Filtering is
+    /// disabled when unset or the file is missing.
+    pub nickname_blocklist_path: Option<String>,
+    /// Also mask blocked words in nicknames the server broadcasts, which catches tokens
+    /// issued before the list changed.
+    pub censor_nicknames: bool,
+    /// How often the player store is written to disk, besides once at shutdown. 0 writes it
+    /// only at shutdown.
+    pub store_flush_interval_secs: u64,
+    pub slow_handler_threshold_ms: u64,
+    /// Game events the bus holds for its slowest subscriber, rounded up to a power of two.
+    /// About a second's worth of moves from every player is a reasonable start.
+    pub event_bus_capacity: usize,
+    /// What happens to new events once the slowest subscriber has `event_bus_capacity` unread.
+    pub event_bus_overflow: OverflowPolicy,
+    /// Forget a player's leaderboard score when they leave instead of keeping it until restart.
+    pub leaderboard_reset_on_leave: bool,
+    /// Newline-delimited JSON file every game event is recorded to, with its time since
+    /// startup. Recording is disabled when unset.
+    pub record_events_path: Option<PathBuf>,
+    /// Recording to replay into the world simulation at startup, rebuilding the world it
+    /// captured without any real connections. Meant for reproducing desyncs, not live servers.
+    pub replay_path: Option<PathBuf>,
 }
 
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
             port: 9000,
+            max_players: 1000,
+            max_spectators: 100,
+            overload_high_water: 0,
+            overload_low_water: 0,
             debug_mode: false,
+            debug_seed: None,
             rpc_url: "https://api.devnet.solana.com".to_string(),
             token_mint: "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string(),
-            jwt_secret: "your-secret-key-change-in-production".to_string(),
+            min_token_balance: 1,
+            jwt_secret: INSECURE_JWT_SECRET.to_string(),
             tickrate_ms: 4,
+            tickrate_ramp: Vec::new(),
+            sim_hz: 60,
+            interest_radius: 500.0,
+            speed_tolerance_factor: 3.0,
+            velocity_mismatch_factor: 2.0,
+            velocity_derivation_tolerance_pct: 100.0,
+            anticheat_mode: AntiCheatMode::Enforce,
+            max_delta_time: 0.25,
+            max_acceleration: 8000.0,
+            attack_range: 5.0,
+            attack_damage: 10.0,
+            reconnect_grace_ms: 5000,
+            refresh_grace_hours: 1,
+            auth_rate_limit: 5,
+            auth_rate_window_secs: 60,
+            reauth_limit: 10,
+            reauth_window_secs: 600,
+            max_characters_per_wallet: 5,
+            move_rate_limit: 60,
+            chat_rate_limit: 3,
+            attack_rate_limit: 10,
+            get_state_rate_limit: 5,
+            time_sync_rate_limit: 5,
+            challenge_ttl_secs: 60,
+            reverify_interval_secs: 600,
+            use_delta_updates: false,
+            send_interpolation: false,
+            wire_format: WireFormat::Json,
+            strict_protocol: false,
+            compress_broadcasts: false,
+            admin_token: String::new(),
+            ban_duration_secs: 3600,
+            auto_ban_secs: 0,
+            world_bounds: WorldBounds::default(),
+            spawn_point: Position::default(),
+            spawn_jitter: 0.0,
+            spawn_grace_radius: 0.0,
+            heartbeat_interval_ms: 15000,
+            heartbeat_timeout_ms: 45000,
+            violation_decay_secs: 10,
+            warp_tolerance: 5.0,
+            warp_timeout_ms: 5000,
+            reconcile_history: 16,
+            reconcile_window_ms: 250,
+            audit_log_size: 64,
+            afk_timeout_ms: 300_000,
+            max_invalid_messages: 10,
+            max_send_queue: 256,
+            slow_consumer_timeout_ms: 5000,
+            send_buffer: 1024,
+            player_mailbox_capacity: 0,
+            player_store_path: None,
+            nickname_blocklist_path: None,
+            censor_nicknames: false,
+            store_flush_interval_secs: 30,
+            slow_handler_threshold_ms: 50,
+            event_bus_capacity: 1000,
+            event_bus_overflow: OverflowPolicy::DropOldest,
+            leaderboard_reset_on_leave: false,
+            record_events_path: None,
+            replay_path: None,
         }
     }
 }
 
 impl ServerConfig {
-    pub fn from_env() -> Self {
-        let mut config = Self::default();
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::default().apply_env(|key| env::var(key).ok())
+    }
 
-        if let Ok(url) = env::var("SOLANA_RPC_URL") {
+    /// Reads a TOML config file. Settings it leaves out keep their defaults.
+    ///
+    /// Not validated on its own, since the environment may still override it; `load` checks
+    /// the merged result.
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    /// Loads `CONFIG_PATH` (default `config.toml`, skipped if absent) and applies
+    /// environment overrides on top, so the environment always wins.
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = env::var("CONFIG_PATH").ok().or_else(|| {
+            Path::new(DEFAULT_CONFIG_PATH)
+                .exists()
+                .then(|| DEFAULT_CONFIG_PATH.to_string())
+        });
+        let config = match path {
+            Some(path) => Self::from_file(Path::new(&path))?.apply_env(|key| env::var(key).ok())?,
+            None => Self::from_env()?,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Overrides settings with any of the environment variables `var` resolves. Fails on the
+    /// first variable that doesn't parse, rather than quietly keeping the previous value.
+    fn apply_env(self, var: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let mut config = self;
+
+        if let Some(url) = var("SOLANA_RPC_URL") {
             config.rpc_url = url;
         }
 
-        if let Ok(mint) = env::var("TOKEN_MINT_ADDRESS") {
+        if let Some(mint) = var("TOKEN_MINT_ADDRESS") {
             config.token_mint = mint;
         }
 
-        if let Ok(secret) = env::var("JWT_SECRET") {
+        if let Some(b) = parsed::<u64>(&var, "MIN_TOKEN_BALANCE")? {
+            config.min_token_balance = b;
+        }
+
+        if let Some(secret) = var("JWT_SECRET") {
             config.jwt_secret = secret;
         }
 
-        if let Ok(port) = env::var("PORT") {
-            if let Ok(p) = port.parse::<u16>() {
-                config.port = p;
-            }
+        if let Some(p) = parsed::<u16>(&var, "PORT")? {
+            config.port = p;
+        }
+
+        if let Some(m) = parsed::<usize>(&var, "MAX_PLAYERS")? {
+            config.max_players = m;
+        }
+
+        if let Some(m) = parsed::<usize>(&var, "MAX_SPECTATORS")? {
+            config.max_spectators = m;
+        }
+
+        if let Some(h) = parsed::<usize>(&var, "OVERLOAD_HIGH_WATER")? {
+            config.overload_high_water = h;
+        }
+
+        if let Some(l) = parsed::<usize>(&var, "OVERLOAD_LOW_WATER")? {
+            config.overload_low_water = l;
+        }
+
+        if let Some(debug) = parsed::<bool>(&var, "DEBUG_MODE")? {
+            config.debug_mode = debug;
+        }
+
+        if let Some(s) = parsed::<u64>(&var, "DEBUG_SEED")? {
+            config.debug_seed = Some(s);
+        }
+
+        if let Some(t) = parsed::<u64>(&var, "TICKRATE_MS")? {
+            config.tickrate_ms = t;
+        }
+
+        if let Some(ramp) = var("TICKRATE_RAMP") {
+            let r =
+                parse_tickrate_ramp(&ramp).ok_or_else(|| invalid_env("TICKRATE_RAMP", &ramp))?;
+            config.tickrate_ramp = r;
+        }
+
+        if let Some(h) = parsed::<u32>(&var, "SIM_HZ")? {
+            config.sim_hz = h;
+        }
+
+        if let Some(r) = parsed::<f32>(&var, "INTEREST_RADIUS")? {
+            config.interest_radius = r;
+        }
+
+        if let Some(f) = parsed::<f32>(&var, "SPEED_TOLERANCE_FACTOR")? {
+            config.speed_tolerance_factor = f;
+        }
+
+        if let Some(f) = parsed::<f32>(&var, "VELOCITY_MISMATCH_FACTOR")? {
+            config.velocity_mismatch_factor = f;
+        }
+
+        if let Some(m) = parsed::<AntiCheatMode>(&var, "ANTICHEAT_MODE")? {
+            config.anticheat_mode = m;
+        }
+
+        if let Some(m) = parsed::<f32>(&var, "MAX_DELTA_TIME")? {
+            config.max_delta_time = m;
+        }
+
+        if let Some(t) = parsed::<f32>(&var, "VELOCITY_DERIVATION_TOLERANCE_PCT")? {
+            config.velocity_derivation_tolerance_pct = t;
+        }
+
+        if let Some(a) = parsed::<f32>(&var, "MAX_ACCELERATION")? {
+            config.max_acceleration = a;
+        }
+
+        if let Some(r) = parsed::<f32>(&var, "ATTACK_RANGE")? {
+            config.attack_range = r;
+        }
+
+        if let Some(d) = parsed::<f32>(&var, "ATTACK_DAMAGE")? {
+            config.attack_damage = d;
+        }
+
+        if let Some(g) = parsed::<u64>(&var, "RECONNECT_GRACE_MS")? {
+            config.reconnect_grace_ms = g;
+        }
+
+        if let Some(g) = parsed::<u64>(&var, "REFRESH_GRACE_HOURS")? {
+            config.refresh_grace_hours = g;
+        }
+
+        if let Some(l) = parsed::<u32>(&var, "AUTH_RATE_LIMIT")? {
+            config.auth_rate_limit = l;
+        }
+
+        if let Some(w) = parsed::<u64>(&var, "AUTH_RATE_WINDOW_SECS")? {
+            config.auth_rate_window_secs = w;
+        }
+
+        if let Some(l) = parsed::<u32>(&var, "REAUTH_LIMIT")? {
+            config.reauth_limit = l;
+        }
+
+        if let Some(w) = parsed::<u64>(&var, "REAUTH_WINDOW_SECS")? {
+            config.reauth_window_secs = w;
+        }
+
+        if let Some(m) = parsed::<usize>(&var, "MAX_CHARACTERS_PER_WALLET")? {
+            config.max_characters_per_wallet = m;
+        }
+
+        if let Some(l) = parsed::<u32>(&var, "MOVE_RATE_LIMIT")? {
+            config.move_rate_limit = l;
+        }
+
+        if let Some(l) = parsed::<u32>(&var, "CHAT_RATE_LIMIT")? {
+            config.chat_rate_limit = l;
+        }
+
+        if let Some(l) = parsed::<u32>(&var, "ATTACK_RATE_LIMIT")? {
+            config.attack_rate_limit = l;
+        }
+
+        if let Some(l) = parsed::<u32>(&var, "GET_STATE_RATE_LIMIT")? {
+            config.get_state_rate_limit = l;
+        }
+
+        if let Some(l) = parsed::<u32>(&var, "TIME_SYNC_RATE_LIMIT")? {
+            config.time_sync_rate_limit = l;
+        }
+
+        if let Some(t) = parsed::<u64>(&var, "CHALLENGE_TTL_SECS")? {
+            config.challenge_ttl_secs = t;
+        }
+
+        if let Some(i) = parsed::<u64>(&var, "REVERIFY_INTERVAL_SECS")? {
+            config.reverify_interval_secs = i;
+        }
+
+        if let Some(d) = parsed::<bool>(&var, "USE_DELTA_UPDATES")? {
+            config.use_delta_updates = d;
+        }
+
+        if let Some(i) = parsed::<bool>(&var, "SEND_INTERPOLATION")? {
+            config.send_interpolation = i;
+        }
+
+        if let Some(token) = var("ADMIN_TOKEN") {
+            config.admin_token = token;
+        }
+
+        if let Some(s) = parsed::<u64>(&var, "BAN_DURATION_SECS")? {
+            config.ban_duration_secs = s;
+        }
+
+        if let Some(s) = parsed::<u64>(&var, "AUTO_BAN_SECS")? {
+            config.auto_ban_secs = s;
+        }
+
+        if let Some(min) = var("WORLD_BOUNDS_MIN") {
+            let m = parse_position(&min).ok_or_else(|| invalid_env("WORLD_BOUNDS_MIN", &min))?;
+            config.world_bounds.min = m;
+        }
+
+        if let Some(max) = var("WORLD_BOUNDS_MAX") {
+            let m = parse_position(&max).ok_or_else(|| invalid_env("WORLD_BOUNDS_MAX", &max))?;
+            config.world_bounds.max = m;
+        }
+
+        if let Some(spawn) = var("SPAWN_POINT") {
+            let s = parse_position(&spawn).ok_or_else(|| invalid_env("SPAWN_POINT", &spawn))?;
+            config.spawn_point = s;
+        }
+
+        if let Some(j) = parsed::<f32>(&var, "SPAWN_JITTER")? {
+            config.spawn_jitter = j.max(0.0);
+        }
+
+        if let Some(r) = parsed::<f32>(&var, "SPAWN_GRACE_RADIUS")? {
+            config.spawn_grace_radius = r;
+        }
+
+        if let Some(i) = parsed::<u64>(&var, "HEARTBEAT_INTERVAL_MS")? {
+            config.heartbeat_interval_ms = i;
+        }
+
+        if let Some(t) = parsed::<u64>(&var, "HEARTBEAT_TIMEOUT_MS")? {
+            config.heartbeat_timeout_ms = t;
+        }
+
+        if let Some(d) = parsed::<u64>(&var, "VIOLATION_DECAY_SECS")? {
+            config.violation_decay_secs = d;
+        }
+
+        if let Some(t) = parsed::<f32>(&var, "WARP_TOLERANCE")? {
+            config.warp_tolerance = t;
         }
 
-        if let Ok(debug) = env::var("DEBUG_MODE") {
-            config.debug_mode = debug.parse::<bool>().unwrap_or(false);
+        if let Some(t) = parsed::<u64>(&var, "WARP_TIMEOUT_MS")? {
+            config.warp_timeout_ms = t;
         }
 
-        if let Ok(tickrate) = env::var("TICKRATE_MS") {
-            if let Ok(t) = tickrate.parse::<u64>() {
-                config.tickrate_ms = t;
+        if let Some(h) = parsed::<usize>(&var, "RECONCILE_HISTORY")? {
+            config.reconcile_history = h;
+        }
+
+        if let Some(w) = parsed::<u64>(&var, "RECONCILE_WINDOW_MS")? {
+            config.reconcile_window_ms = w;
+        }
+
+        if let Some(s) = parsed::<usize>(&var, "AUDIT_LOG_SIZE")? {
+            config.audit_log_size = s;
+        }
+
+        if let Some(t) = parsed::<u64>(&var, "AFK_TIMEOUT_MS")? {
+            config.afk_timeout_ms = t;
+        }
+
+        if let Some(m) = parsed::<u32>(&var, "MAX_INVALID_MESSAGES")? {
+            config.max_invalid_messages = m;
+        }
+
+        if let Some(m) = parsed::<usize>(&var, "MAX_SEND_QUEUE")? {
+            config.max_send_queue = m;
+        }
+
+        if let Some(t) = parsed::<u64>(&var, "SLOW_CONSUMER_TIMEOUT_MS")? {
+            config.slow_consumer_timeout_ms = t;
+        }
+
+        if let Some(b) = parsed::<usize>(&var, "SEND_BUFFER")? {
+            config.send_buffer = b;
+        }
+
+        if let Some(c) = parsed::<usize>(&var, "PLAYER_MAILBOX_CAPACITY")? {
+            config.player_mailbox_capacity = c;
+        }
+
+        if let Some(path) = var("PLAYER_STORE_PATH") {
+            config.player_store_path = Some(path);
+        }
+
+        if let Some(path) = var("NICKNAME_BLOCKLIST_PATH") {
+            config.nickname_blocklist_path = Some(path);
+        }
+
+        if let Some(c) = parsed::<bool>(&var, "CENSOR_NICKNAMES")? {
+            config.censor_nicknames = c;
+        }
+
+        if let Some(path) = var("RECORD_EVENTS_PATH") {
+            config.record_events_path = Some(PathBuf::from(path));
+        }
+
+        if let Some(path) = var("REPLAY_PATH") {
+            config.replay_path = Some(PathBuf::from(path));
+        }
+
+        if let Some(i) = parsed::<u64>(&var, "STORE_FLUSH_INTERVAL_SECS")? {
+            config.store_flush_interval_secs = i;
+        }
+
+        if let Some(t) = parsed::<u64>(&var, "SLOW_HANDLER_THRESHOLD_MS")? {
+            config.slow_handler_threshold_ms = t;
+        }
+
+        if let Some(c) = parsed::<usize>(&var, "EVENT_BUS_CAPACITY")? {
+            config.event_bus_capacity = c;
+        }
+
+        if let Some(o) = parsed::<OverflowPolicy>(&var, "EVENT_BUS_OVERFLOW")? {
+            config.event_bus_overflow = o;
+        }
+
+        if let Some(s) = parsed::<bool>(&var, "STRICT_PROTOCOL")? {
+            config.strict_protocol = s;
+        }
+
+        if let Some(c) = parsed::<bool>(&var, "COMPRESS_BROADCASTS")? {
+            config.compress_broadcasts = c;
+        }
+
+        if let Some(r) = parsed::<bool>(&var, "LEADERBOARD_RESET_ON_LEAVE")? {
+            config.leaderboard_reset_on_leave = r;
+        }
+
+        if let Some(f) = parsed::<WireFormat>(&var, "WIRE_FORMAT")? {
+            config.wire_format = f;
+        }
+
+        Ok(config)
+    }
+
+    /// Whether `jwt_secret` is the placeholder default or too short to resist brute force.
+    pub fn has_weak_jwt_secret(&self) -> bool {
+        self.jwt_secret == INSECURE_JWT_SECRET || self.jwt_secret.len() < MIN_JWT_SECRET_BYTES
+    }
+
+    /// Checks that every setting is in range, reporting the first one that isn't.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |field: &'static str, reason: &str| {
+            Err(ConfigError::Invalid {
+                field,
+                reason: reason.to_string(),
+            })
+        };
+
+        if self.tickrate_ms == 0 {
+            return invalid("tickrate_ms", "must be greater than 0");
+        }
+        if self.sim_hz == 0 {
+            return invalid("sim_hz", "must be greater than 0");
+        }
+        if self.event_bus_capacity == 0 {
+            return invalid("event_bus_capacity", "must be greater than 0");
+        }
+        if self.jwt_secret.is_empty() {
+            return invalid("jwt_secret", "must not be empty");
+        }
+        if !self.debug_mode && self.has_weak_jwt_secret() {
+            return invalid(
+                "jwt_secret",
+                "must differ from the default and be at least 32 bytes outside debug mode",
+            );
+        }
+        let mints: Vec<&str> = self
+            .token_mint
+            .split(',')
+            .map(str::trim)
+            .filter(|mint| !mint.is_empty())
+            .collect();
+        if mints.is_empty() || mints.iter().any(|mint| Pubkey::from_str(mint).is_err()) {
+            return invalid(
+                "token_mint",
+                "must be a comma separated list of base58 public keys",
+            );
+        }
+        let rpc_uri = self.rpc_url.parse::<Uri>().ok();
+        if !rpc_uri.as_ref().is_some_and(|uri| {
+            matches!(uri.scheme_str(), Some("http" | "https"))
+                && uri.host().is_some_and(|host| !host.is_empty())
+        }) {
+            return invalid("rpc_url", "must be an http(s) URL with a host");
+        }
+        if self.max_players == 0 {
+            return invalid("max_players", "must be greater than 0");
+        }
+        if self.reauth_limit > 0 && self.reauth_window_secs == 0 {
+            return invalid("reauth_window_secs", "must be greater than 0");
+        }
+        if self.overload_high_water > 0 && self.overload_low_water >= self.overload_high_water {
+            return invalid("overload_low_water", "must be below overload_high_water");
+        }
+        if self.interest_radius <= 0.0 {
+            return invalid("interest_radius", "must be greater than 0");
+        }
+        if self.speed_tolerance_factor <= 0.0 {
+            return invalid("speed_tolerance_factor", "must be greater than 0");
+        }
+        if self.velocity_mismatch_factor <= 0.0 {
+            return invalid("velocity_mismatch_factor", "must be greater than 0");
+        }
+        if self.velocity_derivation_tolerance_pct < 0.0 {
+            return invalid("velocity_derivation_tolerance_pct", "must not be negative");
+        }
+        if self.max_delta_time <= 0.0 {
+            return invalid("max_delta_time", "must be greater than 0");
+        }
+        if self.max_acceleration < 0.0 {
+            return invalid("max_acceleration", "must not be negative");
+        }
+        if self.attack_range < 0.0 {
+            return invalid("attack_range", "must not be negative");
+        }
+        if self.attack_damage < 0.0 || self.attack_damage.is_nan() {
+            return invalid("attack_damage", "must not be negative");
+        }
+        if self.audit_log_size > MAX_AUDIT_LOG_SIZE {
+            return invalid(
+                "audit_log_size",
+                &format!("must be at most {}", MAX_AUDIT_LOG_SIZE),
+            );
+        }
+        if self.spawn_jitter < 0.0 {
+            return invalid("spawn_jitter", "must not be negative");
+        }
+        if self.spawn_grace_radius < 0.0 {
+            return invalid("spawn_grace_radius", "must not be negative");
+        }
+        if self.send_buffer == 0 {
+            return invalid("send_buffer", "must be greater than 0");
+        }
+        if self.send_buffer < self.max_send_queue {
+            return invalid("send_buffer", "must be at least max_send_queue");
+        }
+        let WorldBounds { min, max } = &self.world_bounds;
+        if min.x > max.x || min.y > max.y || min.z > max.z {
+            return invalid("world_bounds", "min must not exceed max on any axis");
+        }
+        if self.heartbeat_interval_ms > 0 && self.heartbeat_timeout_ms < self.heartbeat_interval_ms
+        {
+            return invalid(
+                "heartbeat_timeout_ms",
+                "must be at least heartbeat_interval_ms",
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Reads `key` through `var` and parses it, if it is set.
+fn parsed<T: FromStr>(
+    var: &impl Fn(&str) -> Option<String>,
+    key: &'static str,
+) -> Result<Option<T>, ConfigError> {
+    var(key)
+        .map(|value| value.parse::<T>().map_err(|_| invalid_env(key, &value)))
+        .transpose()
+}
+
+fn invalid_env(var: &'static str, value: &str) -> ConfigError {
+    ConfigError::Env {
+        var,
+        value: value.to_string(),
+    }
+}
+
+/// Parses a comma separated `x,y,z` triple.
+fn parse_position(value: &str) -> Option<Position> {
+    let mut parts = value.split(',').map(|part| part.trim().parse::<f64>());
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(x)), Some(Ok(y)), Some(Ok(z)), None) => Some(Position::new(x, y, z)),
+        _ => None,
+    }
+}
+
+/// Parses a comma separated list of `max_players:tickrate_ms` pairs, e.g. `16:4,64:8,256:16`.
+fn parse_tickrate_ramp(value: &str) -> Option<Vec<(usize, u64)>> {
+    let mut ramp = value
+        .split(',')
+        .map(|pair| {
+            let (players, tickrate) = pair.split_once(':')?;
+            let players = players.trim().parse::<usize>().ok()?;
+            let tickrate = tickrate.trim().parse::<u64>().ok().filter(|t| *t > 0)?;
+            Some((players, tickrate))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    ramp.sort_unstable_by_key(|(players, _)| *players);
+    Some(ramp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    /// Writes `contents` after a strong `jwt_secret`, which validation requires.
+    fn write_config(contents: &str) -> (tempfile::TempDir, std::path::PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, format!("jwt_secret = \"{SECRET}\"\n{contents}")).unwrap();
+        (dir, path)
+    }
+
+    fn valid() -> ServerConfig {
+        ServerConfig {
+            jwt_secret: SECRET.to_string(),
+            ..ServerConfig::default()
+        }
+    }
+
+    fn invalid_field(config: ServerConfig) -> &'static str {
+        match config.validate() {
+            Err(ConfigError::Invalid { field, .. }) => field,
+            other => panic!("expected an invalid field, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_valid_config_accepted() {
+        assert!(valid().validate().is_ok());
+        assert!(
+            ServerConfig {
+                debug_mode: true,
+                ..ServerConfig::default()
             }
+            .validate()
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_weak_jwt_secret_predicate() {
+        let with_secret = |secret: &str| ServerConfig {
+            jwt_secret: secret.to_string(),
+            ..ServerConfig::default()
+        };
+
+        assert!(with_secret(INSECURE_JWT_SECRET).has_weak_jwt_secret());
+        assert!(with_secret(&"x".repeat(MIN_JWT_SECRET_BYTES - 1)).has_weak_jwt_secret());
+        assert!(!with_secret(&"x".repeat(MIN_JWT_SECRET_BYTES)).has_weak_jwt_secret());
+    }
+
+    #[test]
+    fn test_startup_checks_reject_each_field() {
+        let cases = [
+            (
+                ServerConfig {
+                    tickrate_ms: 0,
+                    ..valid()
+                },
+                "tickrate_ms",
+            ),
+            (
+                ServerConfig {
+                    sim_hz: 0,
+                    ..valid()
+                },
+                "sim_hz",
+            ),
+            (
+                ServerConfig {
+                    event_bus_capacity: 0,
+                    ..valid()
+                },
+                "event_bus_capacity",
+            ),
+            (
+                ServerConfig {
+                    audit_log_size: MAX_AUDIT_LOG_SIZE + 1,
+                    ..valid()
+                },
+                "audit_log_size",
+            ),
+            (
+                ServerConfig {
+                    velocity_derivation_tolerance_pct: -1.0,
+                    ..valid()
+                },
+                "velocity_derivation_tolerance_pct",
+            ),
+            (
+                ServerConfig {
+                    send_buffer: 0,
+                    ..valid()
+                },
+                "send_buffer",
+            ),
+            (
+                ServerConfig {
+                    send_buffer: 100,
+                    max_send_queue: 256,
+                    ..valid()
+                },
+                "send_buffer",
+            ),
+            (
+                ServerConfig {
+                    spawn_grace_radius: -1.0,
+                    ..valid()
+                },
+                "spawn_grace_radius",
+            ),
+            (
+                ServerConfig {
+                    reauth_window_secs: 0,
+                    ..valid()
+                },
+                "reauth_window_secs",
+            ),
+            (
+                ServerConfig {
+                    overload_high_water: 100,
+                    overload_low_water: 100,
+                    ..valid()
+                },
+                "overload_low_water",
+            ),
+            (
+                ServerConfig {
+                    jwt_secret: String::new(),
+                    ..valid()
+                },
+                "jwt_secret",
+            ),
+            (ServerConfig::default(), "jwt_secret"),
+            (
+                ServerConfig {
+                    jwt_secret: "short-secret".to_string(),
+                    ..valid()
+                },
+                "jwt_secret",
+            ),
+            (
+                ServerConfig {
+                    token_mint: "not-a-mint".to_string(),
+                    ..valid()
+                },
+                "token_mint",
+            ),
+            (
+                ServerConfig {
+                    token_mint: " , ".to_string(),
+                    ..valid()
+                },
+                "token_mint",
+            ),
+            (
+                ServerConfig {
+                    rpc_url: "api.devnet.solana.com".to_string(),
+                    ..valid()
+                },
+                "rpc_url",
+            ),
+            (
+                ServerConfig {
+                    rpc_url: "ftp://api.devnet.solana.com".to_string(),
+                    ..valid()
+                },
+                "rpc_url",
+            ),
+        ];
+
+        for (config, field) in cases {
+            assert_eq!(invalid_field(config), field);
+        }
+    }
+
+    #[test]
+    fn test_from_file_keeps_defaults_for_missing_fields() {
+        let (_dir, path) = write_config(
+            r#"
+            port = 7000
+            tickrate_ms = 16
+            wire_format = "bincode"
+            tickrate_ramp = [[16, 4], [64, 8]]
+
+            [world_bounds]
+            min = { x = -10, y = 0, z = -10 }
+            max = { x = 10, y = 5, z = 10 }
+            "#,
+        );
+
+        let config = ServerConfig::from_file(&path).unwrap();
+
+        assert_eq!(config.port, 7000);
+        assert_eq!(config.tickrate_ms, 16);
+        assert_eq!(config.wire_format, WireFormat::Bincode);
+        assert_eq!(config.anticheat_mode, AntiCheatMode::Enforce);
+        assert_eq!(config.tickrate_ramp, vec![(16, 4), (64, 8)]);
+        assert_eq!(config.world_bounds.max, Position::new(10.0, 5.0, 10.0));
+        assert_eq!(config.max_players, ServerConfig::default().max_players);
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let (_dir, path) = write_config("port = 7000\nmax_players = 50\n");
+        let env: HashMap<&str, &str> = [
+            ("PORT", "7100"),
+            ("WIRE_FORMAT", "bincode"),
+            ("ANTICHEAT_MODE", "log_only"),
+        ]
+        .into();
+
+        let config = ServerConfig::from_file(&path)
+            .unwrap()
+            .apply_env(|key| env.get(key).map(|value| value.to_string()))
+            .unwrap();
+
+        assert_eq!(config.port, 7100);
+        assert_eq!(config.max_players, 50);
+        assert_eq!(config.wire_format, WireFormat::Bincode);
+        assert_eq!(config.anticheat_mode, AntiCheatMode::LogOnly);
+    }
+
+    #[test]
+    fn test_env_can_fix_invalid_file_value() {
+        let (_dir, path) = write_config("tickrate_ms = 0\n");
+
+        let config = ServerConfig::from_file(&path)
+            .unwrap()
+            .apply_env(|key| (key == "TICKRATE_MS").then(|| "50".to_string()))
+            .unwrap();
+
+        assert_eq!(config.tickrate_ms, 50);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_unparsable_env_value_names_the_variable() {
+        for (var, value) in [
+            ("MAX_SPECTATORS", "lots"),
+            ("DEBUG_MODE", "yes"),
+            ("WORLD_BOUNDS_MIN", "1,2"),
+        ] {
+            let error = ServerConfig::default()
+                .apply_env(|key| (key == var).then(|| value.to_string()))
+                .unwrap_err();
+            assert!(matches!(error, ConfigError::Env { var: v, .. } if v == var));
+            assert!(error.to_string().contains(var));
         }
+    }
+
+    #[test]
+    fn test_invalid_values_rejected() {
+        let (_dir, path) = write_config("tickrate_ms = 0\n");
+        assert!(matches!(
+            ServerConfig::from_file(&path).unwrap().validate(),
+            Err(ConfigError::Invalid {
+                field: "tickrate_ms",
+                ..
+            })
+        ));
+
+        let (_dir, path) = write_config("tickrate_msec = 4\n");
+        assert!(matches!(
+            ServerConfig::from_file(&path),
+            Err(ConfigError::Parse(_))
+        ));
+
+        let config = ServerConfig {
+            heartbeat_interval_ms: 1000,
+            heartbeat_timeout_ms: 500,
+            ..valid()
+        };
+        let error = config.validate().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid heartbeat_timeout_ms: must be at least heartbeat_interval_ms"
+        );
 
-        config
+        let config = ServerConfig {
+            attack_damage: -10.0,
+            ..valid()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid {
+                field: "attack_damage",
+                ..
+            })
+        ));
     }
 }
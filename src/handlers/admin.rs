@@ -0,0 +1,796 @@
+use crate::actor_system::{ActorError, ActorPath, ActorSystem};
+use crate::anticheat::SharedAntiCheatConfig;
+use crate::config::ServerConfig;
+use crate::network::{BanList, ConnectionManager};
+use crate::player::{
+    AllowWarp, Announce, Kick, PlayerActor, PlayerCommand, QUERY_TIMEOUT, player_path,
+};
+use crate::types::{
+    AnnounceRequest, AntiCheatRequest, BanRequest, GameEvent, KickRequest, PlayerState,
+    UnbanRequest, WarpRequest,
+};
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use warp::http::StatusCode;
+use warp::ws::Message as WsMessage;
+use warp::{Rejection, Reply, reject, reply};
+
+/// Reasons an admin request is rejected.
+#[derive(Error, Debug)]
+pub enum AdminError {
+    #[error("invalid admin token")]
+    Unauthorized,
+
+    #[error("player not connected")]
+    PlayerNotFound,
+
+    #[error("player did not answer in time")]
+    PlayerUnresponsive,
+
+    #[error("announcement text is empty")]
+    EmptyAnnouncement,
+
+    #[error("address not banned")]
+    NotBanned,
+
+    #[error("{0}")]
+    InvalidThresholds(String),
+}
+
+impl reject::Reject for AdminError {}
+
+impl AdminError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AdminError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AdminError::PlayerNotFound => StatusCode::NOT_FOUND,
+            AdminError::PlayerUnresponsive => StatusCode::GATEWAY_TIMEOUT,
+            AdminError::EmptyAnnouncement => StatusCode::BAD_REQUEST,
+            AdminError::NotBanned => StatusCode::NOT_FOUND,
+            AdminError::InvalidThresholds(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+fn authorize(admin_token: Option<&str>, config: &ServerConfig) -> Result<(), Rejection> {
+    // An empty configured token disables the admin routes entirely.
+    if config.admin_token.is_empty() || admin_token != Some(config.admin_token.as_str()) {
+        return Err(reject::custom(AdminError::Unauthorized));
+    }
+    Ok(())
+}
+
+/// Kicks a player: delivers `Kick` to its actor, stops the actor and closes the socket.
+pub async fn handle_admin_kick(
+    admin_token: Option<String>,
+    kick_req: KickRequest,
+    system: ActorSystem<GameEvent>,
+    connection_manager: ConnectionManager,
+    config: Arc<ServerConfig>,
+) -> Result<impl Reply, Rejection> {
+    authorize(admin_token.as_deref(), &config)?;
+
+    if !kick_player(
+        &system,
+        &connection_manager,
+        &kick_req.player_id,
+        kick_req.reason.clone(),
+    )
+    .await
+    {
+        return Err(reject::custom(AdminError::PlayerNotFound));
+    }
+
+    log::warn!(
+        "Admin kick for player {}: {}",
+        kick_req.player_id,
+        kick_req.reason
+    );
+
+    Ok(reply::json(&serde_json::json!({
+        "kicked": kick_req.player_id
+    })))
+}
+
+/// Moves a player to `target`, exempting the jump from the teleport check.
+pub async fn handle_admin_warp(
+    admin_token: Option<String>,
+    warp_req: WarpRequest,
+    system: ActorSystem<GameEvent>,
+    config: Arc<ServerConfig>,
+) -> Result<impl Reply, Rejection> {
+    authorize(admin_token.as_deref(), &config)?;
+
+    let Some(actor_ref) = system
+        .get_actor::<PlayerActor>(&player_path(&warp_req.player_id))
+        .await
+    else {
+        return Err(reject::custom(AdminError::PlayerNotFound));
+    };
+
+    let target = warp_req.target;
+    if actor_ref
+        .try_ask(AllowWarp {
+            target: target.clone(),
+        })
+        .await
+        .is_err()
+    {
+        return Err(reject::custom(AdminError::PlayerNotFound));
+    }
+
+    log::warn!(
+        "Admin warp for player {} to ({}, {}, {})",
+        warp_req.player_id,
+        target.x,
+        target.y,
+        target.z
+    );
+
+    Ok(reply::json(&serde_json::json!({
+        "warped": warp_req.player_id
+    })))
+}
+
+/// Delivers `Kick` to the player's actor, stops it and closes the socket.
+///
+/// Returns `false` without doing anything if the player has no actor.
+pub(crate) async fn kick_player(
+    system: &ActorSystem<GameEvent>,
+    connection_manager: &ConnectionManager,
+    player_id: &str,
+    reason: String,
+) -> bool {
+    let path = player_path(player_id);
+    let Some(actor_ref) = system.get_actor::<PlayerActor>(&path).await else {
+        return false;
+    };
+
+    if let Err(e) = actor_ref
+        .try_ask(PlayerCommand::Kick(Kick { reason }))
+        .await
+    {
+        log::warn!("Kick not delivered to player {}: {:?}", player_id, e);
+    }
+
+    // The actor stops itself after a delivered `Kick`; this covers the undelivered case
+    // and makes sure it is deregistered before we return.
+    system.stop_actor(&path).await;
+    connection_manager.send_to(player_id, WsMessage::close());
+    true
+}
+
+/// The player's recent moves with the anti-cheat verdict on each, oldest first.
+pub async fn handle_admin_audit(
+    player_id: String,
+    admin_token: Option<String>,
+    system: ActorSystem<GameEvent>,
+    config: Arc<ServerConfig>,
+) -> Result<impl Reply, Rejection> {
+    authorize(admin_token.as_deref(), &config)?;
+
+    let Some(actor_ref) = system
+        .get_actor::<PlayerActor>(&player_path(&player_id))
+        .await
+    else {
+        return Err(reject::custom(AdminError::PlayerNotFound));
+    };
+
+    // An actor that stops before answering drops the reply, which reads as gone.
+    let (get_audit, audit) = PlayerCommand::get_audit();
+    match actor_ref.ask_timeout(get_audit, QUERY_TIMEOUT).await {
+        Ok(()) => {}
+        Err(ActorError::Timeout(_)) => {
+            return Err(reject::custom(AdminError::PlayerUnresponsive));
+        }
+        Err(_) => return Err(reject::custom(AdminError::PlayerNotFound)),
+    }
+    let moves = audit
+        .await
+        .map_err(|_| reject::custom(AdminError::PlayerNotFound))?;
+
+    Ok(reply::json(&serde_json::json!({
+        "player_id": player_id,
+        "moves": moves
+    })))
+}
+
+/// The world as the broadcast loop last saw it, for services that don't hold a socket.
+pub async fn handle_api_state(
+    admin_token: Option<String>,
+    player_states: Arc<DashMap<String, PlayerState>>,
+    config: Arc<ServerConfig>,
+) -> Result<impl Reply, Rejection> {
+    authorize(admin_token.as_deref(), &config)?;
+
+    let mut players: Vec<PlayerState> = player_states
+        .iter()
+        .map(|entry| entry.value().clone())
+        .collect();
+    players.sort_by(|a, b| a.player_id.cmp(&b.player_id));
+
+    Ok(reply::json(&serde_json::json!({
+        "players": players
+    })))
+}
+
+/// Sends an announcement to every player actor, connected or within its reconnect grace period.
+pub async fn handle_admin_announce(
+    admin_token: Option<String>,
+    announce_req: AnnounceRequest,
+    system: ActorSystem<GameEvent>,
+    config: Arc<ServerConfig>,
+) -> Result<impl Reply, Rejection> {
+    authorize(admin_token.as_deref(), &config)?;
+
+    let text = announce_req.text.trim();
+    if text.is_empty() {
+        return Err(reject::custom(AdminError::EmptyAnnouncement));
+    }
+
+    log::info!("Admin announcement: {}", text);
+
+    let delivered = system
+        .broadcast_to_actors::<PlayerActor, _>(
+            &ActorPath::from("/user"),
+            Announce {
+                text: text.to_string(),
+            },
+        )
+        .await;
+
+    Ok(reply::json(&serde_json::json!({
+        "delivered": delivered
+    })))
+}
+
+/// Refuses new connections from an address. Players already connected from it stay online.
+pub async fn handle_admin_ban(
+    admin_token: Option<String>,
+    ban_req: BanRequest,
+    bans: BanList,
+    config: Arc<ServerConfig>,
+) -> Result<impl Reply, Rejection> {
+    authorize(admin_token.as_deref(), &config)?;
+
+    let duration_secs = ban_req.duration_secs.unwrap_or(config.ban_duration_secs);
+    log::warn!("Admin ban for {} ({}s)", ban_req.ip, duration_secs);
+    bans.ban(ban_req.ip, Duration::from_secs(duration_secs));
+
+    Ok(reply::json(&serde_json::json!({
+        "banned": ban_req.ip,
+        "expires_in": duration_secs
+    })))
+}
+
+pub async fn handle_admin_unban(
+    admin_token: Option<String>,
+    unban_req: UnbanRequest,
+    bans: BanList,
+    config: Arc<ServerConfig>,
+) -> Result<impl Reply, Rejection> {
+    authorize(admin_token.as_deref(), &config)?;
+
+    if !bans.unban(&unban_req.ip) {
+        return Err(reject::custom(AdminError::NotBanned));
+    }
+    log::info!("Admin unban for {}", unban_req.ip);
+
+    Ok(reply::json(&serde_json::json!({
+        "unbanned": unban_req.ip
+    })))
+}
+
+/// Updates the live anti-cheat thresholds. Players are checked against them from their next
+/// move on.
+pub async fn handle_admin_anticheat(
+    admin_token: Option<String>,
+    anticheat_req: AntiCheatRequest,
+    anticheat: SharedAntiCheatConfig,
+    config: Arc<ServerConfig>,
+) -> Result<impl Reply, Rejection> {
+    authorize(admin_token.as_deref(), &config)?;
+
+    let mut thresholds = anticheat.write().unwrap_or_else(|e| e.into_inner());
+    let updated = thresholds
+        .updated(&anticheat_req)
+        .map_err(|e| reject::custom(AdminError::InvalidThresholds(e)))?;
+    log::warn!("Admin changed anti-cheat thresholds to {:?}", updated);
+    *thresholds = updated.clone();
+
+    Ok(reply::json(&updated))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor_system::EventBus;
+    use crate::anticheat::AntiCheatConfig;
+    use crate::metrics::Metrics;
+    use crate::network::outbound_channel;
+    use crate::player::MovePlayer;
+    use crate::rng::SharedRng;
+    use crate::types::Position;
+
+    const ADMIN_TOKEN: &str = "admin-secret";
+
+    fn config() -> Arc<ServerConfig> {
+        Arc::new(ServerConfig {
+            admin_token: ADMIN_TOKEN.to_string(),
+            ..ServerConfig::default()
+        })
+    }
+
+    fn kick_request(player_id: &str) -> KickRequest {
+        KickRequest {
+            player_id: player_id.to_string(),
+            reason: "griefing".to_string(),
+        }
+    }
+
+    fn rejection_of(result: Result<impl Reply, Rejection>) -> Rejection {
+        match result {
+            Ok(_) => panic!("expected a rejection"),
+            Err(rejection) => rejection,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bad_token_rejected() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+
+        for token in [None, Some("wrong".to_string())] {
+            let rejection = rejection_of(
+                handle_admin_kick(
+                    token,
+                    kick_request("p1"),
+                    system.clone(),
+                    ConnectionManager::new(),
+                    config(),
+                )
+                .await,
+            );
+            assert!(matches!(
+                rejection.find::<AdminError>(),
+                Some(AdminError::Unauthorized)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_empty_admin_token_disables_route() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+
+        let rejection = rejection_of(
+            handle_admin_kick(
+                Some(String::new()),
+                kick_request("p1"),
+                system,
+                ConnectionManager::new(),
+                Arc::new(ServerConfig::default()),
+            )
+            .await,
+        );
+        assert!(matches!(
+            rejection.find::<AdminError>(),
+            Some(AdminError::Unauthorized)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_player_not_found() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+
+        let rejection = rejection_of(
+            handle_admin_kick(
+                Some(ADMIN_TOKEN.to_string()),
+                kick_request("missing"),
+                system,
+                ConnectionManager::new(),
+                config(),
+            )
+            .await,
+        );
+        assert!(matches!(
+            rejection.find::<AdminError>(),
+            Some(AdminError::PlayerNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_kick_notifies_and_stops_player() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let manager = ConnectionManager::new();
+        let (tx, mut rx) = outbound_channel(1024);
+        manager.add("p1".to_string(), tx.clone());
+        let actor = PlayerActor::new(
+            "p1".to_string(),
+            "wallet".to_string(),
+            "Nick".to_string(),
+            tx,
+            config(),
+            Arc::new(Metrics::new()),
+            &SharedRng::default(),
+        );
+        system.create_actor("player-p1", actor).await.unwrap();
+
+        let result = handle_admin_kick(
+            Some(ADMIN_TOKEN.to_string()),
+            kick_request("p1"),
+            system.clone(),
+            manager,
+            config(),
+        )
+        .await;
+        assert!(result.is_ok());
+
+        let kicked = rx.recv().await.unwrap();
+        assert!(kicked.to_str().unwrap().contains("griefing"));
+        assert!(rx.recv().await.unwrap().is_close());
+
+        let path = ActorPath::from("/user/player-p1");
+        assert!(system.get_actor::<PlayerActor>(&path).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_announce_reaches_every_player() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let mut receivers = Vec::new();
+        for id in ["p1", "p2"] {
+            let (tx, rx) = outbound_channel(1024);
+            let actor = PlayerActor::new(
+                id.to_string(),
+                "wallet".to_string(),
+                "Nick".to_string(),
+                tx,
+                config(),
+                Arc::new(Metrics::new()),
+                &SharedRng::default(),
+            );
+            system
+                .create_actor(&format!("player-{id}"), actor)
+                .await
+                .unwrap();
+            receivers.push(rx);
+        }
+
+        let result = handle_admin_announce(
+            Some(ADMIN_TOKEN.to_string()),
+            AnnounceRequest {
+                text: "restart in 5 minutes".to_string(),
+            },
+            system,
+            config(),
+        )
+        .await;
+        assert!(result.is_ok());
+
+        for rx in receivers.iter_mut() {
+            let frame = rx.recv().await.unwrap();
+            let text = frame.to_str().unwrap();
+            assert!(text.contains("Announcement"));
+            assert!(text.contains("restart in 5 minutes"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blank_announcement_rejected() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+
+        let rejection = rejection_of(
+            handle_admin_announce(
+                Some(ADMIN_TOKEN.to_string()),
+                AnnounceRequest {
+                    text: "   ".to_string(),
+                },
+                system,
+                config(),
+            )
+            .await,
+        );
+        assert!(matches!(
+            rejection.find::<AdminError>(),
+            Some(AdminError::EmptyAnnouncement)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_audit_returns_moves_in_order() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (tx, _rx) = outbound_channel(1024);
+        let mut actor = PlayerActor::new(
+            "p1".to_string(),
+            "wallet".to_string(),
+            "Nick".to_string(),
+            tx,
+            config(),
+            Arc::new(Metrics::new()),
+            &SharedRng::default(),
+        );
+        actor.position = Position::default();
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        // Two clean steps, then a teleport.
+        for x in [1.0, 2.0, 500.0] {
+            actor_ref
+                .ask(PlayerCommand::Move(MovePlayer {
+                    position: Position::new(x, 0.0, 0.0),
+                    velocity: Position::new(10.0, 0.0, 0.0),
+                    delta_time: 0.1,
+                    client_time_ms: None,
+                }))
+                .await
+                .unwrap();
+        }
+
+        let reply = handle_admin_audit(
+            "p1".to_string(),
+            Some(ADMIN_TOKEN.to_string()),
+            system.clone(),
+            config(),
+        )
+        .await
+        .unwrap();
+        let body = warp::hyper::body::to_bytes(reply.into_response().into_body())
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        let moves = body["moves"].as_array().unwrap();
+        let summary: Vec<(f64, f64, &str)> = moves
+            .iter()
+            .map(|entry| {
+                (
+                    entry["from"]["x"].as_f64().unwrap(),
+                    entry["to"]["x"].as_f64().unwrap(),
+                    entry["result"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (0.0, 1.0, "Valid"),
+                (1.0, 2.0, "Valid"),
+                (2.0, 500.0, "Teleport")
+            ]
+        );
+        assert!(moves[0]["timestamp_ms"].as_u64().unwrap() > 0);
+
+        let rejection = rejection_of(
+            handle_admin_audit(
+                "missing".to_string(),
+                Some(ADMIN_TOKEN.to_string()),
+                system,
+                config(),
+            )
+            .await,
+        );
+        assert!(matches!(
+            rejection.find::<AdminError>(),
+            Some(AdminError::PlayerNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_warp_moves_player_past_teleport_check() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (tx, mut rx) = outbound_channel(1024);
+        let mut actor = PlayerActor::new(
+            "p1".to_string(),
+            "wallet".to_string(),
+            "Nick".to_string(),
+            tx,
+            config(),
+            Arc::new(Metrics::new()),
+            &SharedRng::default(),
+        );
+        actor.position = Position::default();
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        let target = Position::new(500.0, 0.0, 0.0);
+        let result = handle_admin_warp(
+            Some(ADMIN_TOKEN.to_string()),
+            WarpRequest {
+                player_id: "p1".to_string(),
+                target: target.clone(),
+            },
+            system.clone(),
+            config(),
+        )
+        .await;
+        assert!(result.is_ok());
+
+        let frame: serde_json::Value =
+            serde_json::from_str(rx.recv().await.unwrap().to_str().unwrap()).unwrap();
+        assert_eq!(frame["type"], "Correction");
+        assert_eq!(frame["position"]["x"], 500.0);
+
+        actor_ref
+            .ask(PlayerCommand::Move(MovePlayer {
+                position: target.clone(),
+                velocity: Position::default(),
+                delta_time: 0.1,
+                client_time_ms: None,
+            }))
+            .await
+            .unwrap();
+        let (command, state) = PlayerCommand::get_state();
+        actor_ref.ask(command).await.unwrap();
+        let state = state.await.unwrap();
+        assert_eq!(state.position, target);
+        assert_eq!(state.violations, 0);
+
+        let rejection = rejection_of(
+            handle_admin_warp(
+                Some(ADMIN_TOKEN.to_string()),
+                WarpRequest {
+                    player_id: "missing".to_string(),
+                    target,
+                },
+                system,
+                config(),
+            )
+            .await,
+        );
+        assert!(matches!(
+            rejection.find::<AdminError>(),
+            Some(AdminError::PlayerNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_ban_then_unban() {
+        let bans = BanList::new();
+        let ip: std::net::IpAddr = "203.0.113.7".parse().unwrap();
+
+        let result = handle_admin_ban(
+            Some(ADMIN_TOKEN.to_string()),
+            BanRequest {
+                ip,
+                duration_secs: None,
+            },
+            bans.clone(),
+            config(),
+        )
+        .await;
+        assert!(result.is_ok());
+        assert!(bans.is_banned(&ip));
+
+        let unban = || {
+            handle_admin_unban(
+                Some(ADMIN_TOKEN.to_string()),
+                UnbanRequest { ip },
+                bans.clone(),
+                config(),
+            )
+        };
+        assert!(unban().await.is_ok());
+        assert!(!bans.is_banned(&ip));
+
+        let rejection = rejection_of(unban().await);
+        assert!(matches!(
+            rejection.find::<AdminError>(),
+            Some(AdminError::NotBanned)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_anticheat_update_validated_and_returned() {
+        let anticheat = AntiCheatConfig::shared(&ServerConfig::default());
+        let before = anticheat.read().unwrap().clone();
+
+        let rejection = rejection_of(
+            handle_admin_anticheat(
+                Some(ADMIN_TOKEN.to_string()),
+                AntiCheatRequest {
+                    max_speed: Some(50.0),
+                    teleport_threshold: Some(0.0),
+                    ..AntiCheatRequest::default()
+                },
+                anticheat.clone(),
+                config(),
+            )
+            .await,
+        );
+        assert!(matches!(
+            rejection.find::<AdminError>(),
+            Some(AdminError::InvalidThresholds(_))
+        ));
+        assert_eq!(*anticheat.read().unwrap(), before);
+
+        let reply = handle_admin_anticheat(
+            Some(ADMIN_TOKEN.to_string()),
+            AntiCheatRequest {
+                max_speed: Some(50.0),
+                ..AntiCheatRequest::default()
+            },
+            anticheat.clone(),
+            config(),
+        )
+        .await
+        .unwrap();
+        let body = warp::hyper::body::to_bytes(reply.into_response().into_body())
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["max_speed"], 50.0);
+        assert_eq!(body["teleport_threshold"], before.teleport_threshold);
+        assert_eq!(anticheat.read().unwrap().max_speed, 50.0);
+    }
+
+    #[tokio::test]
+    async fn test_state_reflects_joined_and_moved_player() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let player_states = Arc::new(DashMap::new());
+        let (_shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let (broadcast_system, broadcast_states) = (system.clone(), player_states.clone());
+        tokio::spawn(async move {
+            crate::network::broadcast_positions(
+                broadcast_system,
+                ConnectionManager::new(),
+                &ServerConfig::default(),
+                shutdown_rx,
+                crate::network::BroadcastContext {
+                    player_states: broadcast_states,
+                    ..Default::default()
+                },
+            )
+            .await
+        });
+
+        // Events published before the simulation subscribes are lost, so repeat until seen.
+        let moved = Position::new(12.0, 0.0, -4.0);
+        let applied = tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                system.publish(GameEvent::PlayerJoined {
+                    player_id: "p1".to_string(),
+                    wallet: "wallet".to_string(),
+                    nickname: "Nick".to_string(),
+                    room: "lobby".to_string(),
+                    position: Position::default(),
+                });
+                system.publish(GameEvent::PlayerMoved {
+                    player_id: "p1".to_string(),
+                    room: "lobby".to_string(),
+                    position: moved.clone(),
+                    velocity: Position::default(),
+                    latency_ms: 0,
+                });
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                if player_states
+                    .get("p1")
+                    .is_some_and(|state| state.position == moved)
+                {
+                    break;
+                }
+            }
+        })
+        .await;
+        assert!(applied.is_ok());
+
+        let rejection = rejection_of(handle_api_state(None, player_states.clone(), config()).await);
+        assert!(matches!(
+            rejection.find::<AdminError>(),
+            Some(AdminError::Unauthorized)
+        ));
+
+        let reply = handle_api_state(Some(ADMIN_TOKEN.to_string()), player_states, config())
+            .await
+            .unwrap();
+        let body = warp::hyper::body::to_bytes(reply.into_response().into_body())
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        let players = body["players"].as_array().unwrap();
+        assert_eq!(players.len(), 1);
+        assert_eq!(players[0]["nickname"], "Nick");
+        assert_eq!(players[0]["position"]["x"], 12.0);
+        assert_eq!(players[0]["position"]["z"], -4.0);
+        assert_eq!(players[0]["violations"], 0);
+    }
+}
@@ -1,32 +1,201 @@
-use crate::types::{AuthRequest, AuthResponse, Claims, JWT_EXPIRATION_HOURS, SessionInfo};
+use crate::actor_system::ActorSystem;
+use crate::config::ServerConfig;
+use crate::handlers::admin::{AdminError, kick_player};
+use crate::handlers::request_id::with_request_id;
+use crate::metrics::Metrics;
+use crate::network::{ConnectionManager, OverloadBreaker};
+use crate::profanity::ProfanityFilter;
+use crate::types::{
+    AuthRequest, AuthResponse, ChallengeQuery, ChallengeResponse, Claims, GameEvent,
+    JWT_EXPIRATION_HOURS, MAX_CHARACTER_ID_LENGTH, MAX_NICKNAME_LENGTH, MAX_ROOM_LENGTH,
+    MIN_NICKNAME_LENGTH, RefreshRequest, SessionInfo,
+};
 use chrono::{Duration, Utc};
 use dashmap::DashMap;
-use jsonwebtoken::{EncodingKey, Header, encode};
+use dashmap::mapref::entry::Entry;
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
 use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_request::TokenAccountsFilter;
+use solana_client::rpc_response::RpcKeyedAccount;
 use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::net::SocketAddr;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
+use thiserror::Error;
+use unicode_normalization::UnicodeNormalization;
+use warp::http::StatusCode;
 use warp::{Rejection, Reply, reject, reply};
 
+/// Reasons an auth or refresh request is rejected.
+#[derive(Error, Debug)]
+pub enum AuthError {
+    #[error("invalid wallet signature")]
+    InvalidSignature,
+
+    #[error("wallet does not hold the required token")]
+    NoToken,
+
+    #[error("wallet holds {held} tokens but {required} are required")]
+    InsufficientBalance { held: u64, required: u64 },
+
+    #[error("invalid or expired session token")]
+    InvalidToken,
+
+    #[error("too many authentication attempts")]
+    RateLimited,
+
+    #[error("reconnecting too often, retry in {retry_after_secs}s")]
+    ReauthCooldown { retry_after_secs: u64 },
+
+    #[error("missing, expired or already used challenge")]
+    InvalidChallenge,
+
+    #[error("invalid wallet address")]
+    InvalidWallet,
+
+    #[error("{0}")]
+    InvalidNickname(String),
+
+    #[error("{0}")]
+    InvalidRoom(String),
+
+    #[error("{0}")]
+    InvalidCharacter(String),
+
+    #[error("server overloaded")]
+    Overloaded,
+
+    #[error("internal server error")]
+    Internal(String),
+}
+
+impl reject::Reject for AuthError {}
+
+impl AuthError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AuthError::InvalidSignature | AuthError::InvalidToken | AuthError::InvalidChallenge => {
+                StatusCode::UNAUTHORIZED
+            }
+            AuthError::NoToken | AuthError::InsufficientBalance { .. } => StatusCode::FORBIDDEN,
+            AuthError::RateLimited | AuthError::ReauthCooldown { .. } => {
+                StatusCode::TOO_MANY_REQUESTS
+            }
+            AuthError::InvalidNickname(_)
+            | AuthError::InvalidRoom(_)
+            | AuthError::InvalidCharacter(_)
+            | AuthError::InvalidWallet => StatusCode::BAD_REQUEST,
+            AuthError::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+            AuthError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// The slice of the Solana RPC API the verifier depends on.
+pub trait TokenAccountsProvider: Send + Sync {
+    fn get_token_accounts_by_owner(
+        &self,
+        owner: &Pubkey,
+        mint: &Pubkey,
+    ) -> Result<Vec<RpcKeyedAccount>, String>;
+
+    /// Cheap liveness probe of the RPC node.
+    fn get_health(&self) -> Result<(), String>;
+}
+
+impl TokenAccountsProvider for RpcClient {
+    fn get_token_accounts_by_owner(
+        &self,
+        owner: &Pubkey,
+        mint: &Pubkey,
+    ) -> Result<Vec<RpcKeyedAccount>, String> {
+        RpcClient::get_token_accounts_by_owner(self, owner, TokenAccountsFilter::Mint(*mint))
+            .map_err(|e| format!("Failed to fetch token accounts: {}", e))
+    }
+
+    fn get_health(&self) -> Result<(), String> {
+        RpcClient::get_health(self).map_err(|e| format!("RPC health check failed: {}", e))
+    }
+}
+
+/// How long an RPC health probe result is reused, so readiness polling can't flood the node.
+const RPC_HEALTH_TTL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Outcome of a token ownership check.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TokenBalance {
+    Sufficient,
+    /// No accepted mint reaches the minimum; carries the highest per-mint balance found.
+    Insufficient(u64),
+}
+
 pub struct SolanaVerifier {
-    rpc_client: RpcClient,
-    required_token_mint: Pubkey,
+    provider: Box<dyn TokenAccountsProvider>,
+    /// Holding any one of these mints grants entry.
+    required_token_mints: Vec<Pubkey>,
+    /// Base units of a single mint, summed across its accounts, needed to pass.
+    min_token_balance: u64,
     debug_mode: bool,
+    /// Last RPC health probe and when it ran.
+    rpc_health: Mutex<Option<(Instant, Result<(), String>)>>,
 }
 
 impl SolanaVerifier {
-    pub fn new(rpc_url: &str, token_mint: &str, debug_mode: bool) -> Result<Self, String> {
-        let required_token_mint =
-            Pubkey::from_str(token_mint).map_err(|e| format!("Invalid token mint: {}", e))?;
+    /// `token_mints` is a single mint or a comma separated list of mints.
+    pub fn new(
+        rpc_url: &str,
+        token_mints: &str,
+        min_token_balance: u64,
+        debug_mode: bool,
+    ) -> Result<Self, String> {
+        Self::with_provider(
+            Box::new(RpcClient::new(rpc_url.to_string())),
+            token_mints,
+            min_token_balance,
+            debug_mode,
+        )
+    }
 
+    pub fn with_provider(
+        provider: Box<dyn TokenAccountsProvider>,
+        token_mints: &str,
+        min_token_balance: u64,
+        debug_mode: bool,
+    ) -> Result<Self, String> {
         Ok(Self {
-            rpc_client: RpcClient::new(rpc_url.to_string()),
-            required_token_mint,
+            provider,
+            required_token_mints: parse_token_mints(token_mints)?,
+            min_token_balance,
             debug_mode,
+            rpc_health: Mutex::new(None),
         })
     }
 
+    pub fn min_token_balance(&self) -> u64 {
+        self.min_token_balance
+    }
+
+    /// Whether the RPC node answers, reusing a probe younger than `RPC_HEALTH_TTL`.
+    ///
+    /// Blocks on the RPC call; run it off the async runtime. Always healthy in debug mode,
+    /// where the RPC is never consulted.
+    pub fn check_rpc_health(&self) -> Result<(), String> {
+        if self.debug_mode {
+            return Ok(());
+        }
+
+        let mut cached = self.rpc_health.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((checked_at, result)) = cached.as_ref()
+            && checked_at.elapsed() < RPC_HEALTH_TTL
+        {
+            return result.clone();
+        }
+        let result = self.provider.get_health();
+        *cached = Some((Instant::now(), result.clone()));
+        result
+    }
+
     pub fn verify_signature(
         &self,
         wallet_address: &str,
@@ -52,87 +221,508 @@ impl SolanaVerifier {
         Ok(sig.verify(pubkey.as_ref(), message_bytes))
     }
 
-    pub async fn verify_token_ownership(&self, wallet_address: &str) -> Result<bool, String> {
+    pub async fn verify_token_ownership(
+        &self,
+        wallet_address: &str,
+    ) -> Result<TokenBalance, String> {
         if self.debug_mode {
             log::debug!(
                 "DEBUG MODE: Skipping token ownership verification for {}",
                 wallet_address
             );
-            return Ok(true);
+            return Ok(TokenBalance::Sufficient);
         }
 
         let wallet_pubkey = Pubkey::from_str(wallet_address)
             .map_err(|e| format!("Invalid wallet address: {}", e))?;
 
-        let token_accounts = self
-            .rpc_client
-            .get_token_accounts_by_owner(
-                &wallet_pubkey,
-                solana_client::rpc_request::TokenAccountsFilter::Mint(self.required_token_mint),
-            )
-            .map_err(|e| format!("Failed to fetch token accounts: {}", e))?;
+        let mut best = 0;
+        for mint in &self.required_token_mints {
+            let token_accounts = self
+                .provider
+                .get_token_accounts_by_owner(&wallet_pubkey, mint)?;
 
-        for _account in token_accounts {
-            return Ok(true);
+            let balance = token_accounts
+                .iter()
+                .map(token_account_amount)
+                .fold(0u64, u64::saturating_add);
+
+            if !token_accounts.is_empty() && balance >= self.min_token_balance {
+                return Ok(TokenBalance::Sufficient);
+            }
+            best = best.max(balance);
         }
 
-        Ok(false)
+        Ok(TokenBalance::Insufficient(best))
+    }
+}
+
+/// Reads the raw token amount from a `jsonParsed` SPL token account, treating anything
+/// unreadable as empty.
+fn token_account_amount(account: &RpcKeyedAccount) -> u64 {
+    let amount = serde_json::to_value(&account.account.data)
+        .ok()
+        .and_then(|data| {
+            data.pointer("/parsed/info/tokenAmount/amount")
+                .and_then(|amount| amount.as_str())
+                .and_then(|amount| amount.parse::<u64>().ok())
+        });
+
+    amount.unwrap_or_else(|| {
+        log::warn!("Unreadable token account {}", account.pubkey);
+        0
+    })
+}
+
+/// Parses a comma separated list of token mints, requiring at least one.
+fn parse_token_mints(token_mints: &str) -> Result<Vec<Pubkey>, String> {
+    let mints = token_mints
+        .split(',')
+        .map(str::trim)
+        .filter(|mint| !mint.is_empty())
+        .map(|mint| Pubkey::from_str(mint).map_err(|e| format!("Invalid token mint: {}", e)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if mints.is_empty() {
+        return Err("No token mint configured".to_string());
     }
+
+    Ok(mints)
+}
+
+/// Issues a one-time nonce the wallet must sign as the `message` of its next auth request.
+pub async fn handle_challenge(
+    query: ChallengeQuery,
+    challenges: Arc<DashMap<String, (String, Instant)>>,
+    config: Arc<ServerConfig>,
+) -> Result<impl Reply, Rejection> {
+    Pubkey::from_str(&query.wallet).map_err(|_| reject::custom(AuthError::InvalidWallet))?;
+
+    let nonce = uuid::Uuid::new_v4().to_string();
+    challenges.insert(nonce.clone(), (query.wallet, Instant::now()));
+
+    Ok(reply::json(&ChallengeResponse {
+        nonce,
+        expires_in: config.challenge_ttl_secs,
+    }))
+}
+
+/// State shared by the auth requests.
+#[derive(Clone)]
+pub struct AuthContext {
+    pub verifier: Arc<SolanaVerifier>,
+    pub sessions: Arc<DashMap<String, SessionInfo>>,
+    /// Failed signature checks per wallet and per client IP, with when their window opened.
+    pub auth_attempts: Arc<DashMap<String, (u32, Instant)>>,
+    /// Outstanding challenges by nonce, with the wallet each was issued to.
+    pub challenges: Arc<DashMap<String, (String, Instant)>>,
+    /// Lowercased nicknames of live sessions, with the player holding each.
+    pub nicknames: Arc<DashMap<String, String>>,
+    /// When each wallet logged in within the last `reauth_window_secs`.
+    pub logins: Arc<DashMap<String, Vec<Instant>>>,
+    pub config: Arc<ServerConfig>,
+    pub profanity: Arc<ProfanityFilter>,
+    pub overload: Arc<OverloadBreaker>,
+    pub metrics: Arc<Metrics>,
 }
 
 pub async fn handle_auth(
     auth_req: AuthRequest,
-    verifier: Arc<SolanaVerifier>,
-    sessions: Arc<DashMap<String, SessionInfo>>,
-    jwt_secret: String,
+    context: AuthContext,
+    remote: Option<SocketAddr>,
+    request_id: String,
 ) -> Result<impl Reply, Rejection> {
-    if !verifier
+    log::debug!(
+        "Auth request {} for wallet {}",
+        request_id,
+        auth_req.wallet_address
+    );
+    // Turned away before any verification work, so clients back off cheaply.
+    if context.overload.is_open() {
+        log::debug!("Auth request {} refused while overloaded", request_id);
+        return Err(reject::custom(AuthError::Overloaded));
+    }
+    let result = issue_session(auth_req, &context, remote, &request_id).await;
+    context.metrics.record_auth(result.is_ok());
+    result.map(|reply| with_request_id(reply, &request_id))
+}
+
+async fn issue_session(
+    auth_req: AuthRequest,
+    context: &AuthContext,
+    remote: Option<SocketAddr>,
+    request_id: &str,
+) -> Result<reply::Json, Rejection> {
+    let AuthContext {
+        verifier,
+        sessions,
+        auth_attempts,
+        challenges,
+        nicknames,
+        logins,
+        config,
+        profanity,
+        ..
+    } = context;
+
+    let attempt_keys: Vec<String> = std::iter::once(auth_req.wallet_address.clone())
+        .chain(remote.map(|addr| addr.ip().to_string()))
+        .collect();
+    if attempt_keys.iter().any(|key| {
+        is_rate_limited(
+            auth_attempts,
+            key,
+            config.auth_rate_limit,
+            config.auth_rate_window_secs,
+        )
+    }) {
+        log::warn!(
+            "Auth request {} rate limited for wallet {}",
+            request_id,
+            auth_req.wallet_address
+        );
+        return Err(reject::custom(AuthError::RateLimited));
+    }
+
+    if let Some(retry_after_secs) = reauth_cooldown(
+        logins,
+        &auth_req.wallet_address,
+        config.reauth_limit,
+        config.reauth_window_secs,
+    ) {
+        log::warn!(
+            "Auth request {} throttled for wallet {}: reconnecting too often",
+            request_id,
+            auth_req.wallet_address
+        );
+        return Err(reject::custom(AuthError::ReauthCooldown {
+            retry_after_secs,
+        }));
+    }
+
+    let character_id = auth_req
+        .character_id
+        .as_deref()
+        .map(validate_character_id)
+        .transpose()
+        .map_err(|e| reject::custom(AuthError::InvalidCharacter(e)))?;
+    let player_id = player_id_for(&auth_req.wallet_address, character_id.as_deref());
+    if config.max_characters_per_wallet > 0
+        && characters_signed_in(sessions, &auth_req.wallet_address, &player_id)
+            >= config.max_characters_per_wallet
+    {
+        return Err(reject::custom(AuthError::InvalidCharacter(format!(
+            "a wallet may play at most {} characters at once",
+            config.max_characters_per_wallet
+        ))));
+    }
+
+    let nickname = validate_nickname(&auth_req.nickname)
+        .map_err(|e| reject::custom(AuthError::InvalidNickname(e)))?;
+    // Checked early so a taken name fails before the challenge is spent; reserved at the end.
+    if nicknames
+        .get(&nickname.to_lowercase())
+        .is_some_and(|owner| *owner != player_id)
+    {
+        return Err(reject::custom(AuthError::InvalidNickname(
+            NICKNAME_TAKEN.to_string(),
+        )));
+    }
+    if profanity.is_blocked(&nickname) {
+        log::warn!(
+            "Auth request {} rejected for wallet {}: blocked nickname",
+            request_id,
+            auth_req.wallet_address
+        );
+        return Err(reject::custom(AuthError::InvalidNickname(
+            "nickname is not allowed".to_string(),
+        )));
+    }
+
+    let room = auth_req
+        .room
+        .as_deref()
+        .map(validate_room)
+        .transpose()
+        .map_err(|e| reject::custom(AuthError::InvalidRoom(e)))?;
+
+    if !config.debug_mode
+        && !challenge_is_fresh(
+            challenges,
+            &auth_req.wallet_address,
+            &auth_req.message,
+            config.challenge_ttl_secs,
+        )
+    {
+        log::warn!(
+            "Auth request {} rejected for wallet {}: bad challenge",
+            request_id,
+            auth_req.wallet_address
+        );
+        return Err(reject::custom(AuthError::InvalidChallenge));
+    }
+
+    let signed = verifier
         .verify_signature(
             &auth_req.wallet_address,
             &auth_req.message,
             &auth_req.signature,
         )
-        .map_err(|_| reject::reject())?
+        .unwrap_or(false);
+    if !signed {
+        for key in &attempt_keys {
+            record_failure(auth_attempts, key, config.auth_rate_window_secs);
+        }
+        return Err(reject::custom(AuthError::InvalidSignature));
+    }
+
+    // Only a signed answer uses the nonce up, so a forged request can't burn the wallet's
+    // challenge. Fails if a concurrent request with the same nonce got here first.
+    if !config.debug_mode
+        && challenges
+            .remove_if(&auth_req.message, |_, (wallet, _)| {
+                *wallet == auth_req.wallet_address
+            })
+            .is_none()
     {
-        return Err(reject::reject());
+        return Err(reject::custom(AuthError::InvalidChallenge));
     }
 
-    let has_token = verifier
+    let balance = verifier
         .verify_token_ownership(&auth_req.wallet_address)
         .await
-        .map_err(|_| reject::reject())?;
+        .map_err(|e| reject::custom(AuthError::Internal(e)))?;
 
-    if !has_token {
-        return Err(reject::reject());
+    match balance {
+        TokenBalance::Sufficient => {}
+        TokenBalance::Insufficient(0) => return Err(reject::custom(AuthError::NoToken)),
+        TokenBalance::Insufficient(held) => {
+            return Err(reject::custom(AuthError::InsufficientBalance {
+                held,
+                required: verifier.min_token_balance(),
+            }));
+        }
     }
 
-    let expiration = Utc::now()
-        .checked_add_signed(Duration::hours(JWT_EXPIRATION_HOURS))
-        .unwrap()
-        .timestamp() as usize;
-
     let claims = Claims {
         wallet_address: auth_req.wallet_address.clone(),
-        player_id: auth_req.wallet_address.clone(),
-        nickname: auth_req.nickname.clone(),
-        exp: expiration,
+        player_id: player_id.clone(),
+        nickname: nickname.clone(),
+        room,
+        exp: expiration_timestamp(),
     };
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(jwt_secret.as_bytes()),
-    )
-    .map_err(|_| reject::reject())?;
+    let token = encode_token(&claims, &config.jwt_secret)
+        .map_err(|e| reject::custom(AuthError::Internal(e.to_string())))?;
 
-    sessions.insert(
-        auth_req.wallet_address.clone(),
+    if !reserve_nickname(nicknames, &nickname, &player_id) {
+        return Err(reject::custom(AuthError::InvalidNickname(
+            NICKNAME_TAKEN.to_string(),
+        )));
+    }
+    let previous = sessions.insert(
+        player_id.clone(),
         SessionInfo {
+            wallet: auth_req.wallet_address.clone(),
             jwt_token: token.clone(),
-            nickname: auth_req.nickname,
-            created_at: Instant::now(),
+            nickname: nickname.clone(),
+            token_exp: claims.exp,
+            request_id: request_id.to_string(),
         },
     );
+    if let Some(previous) = previous
+        && previous.nickname.to_lowercase() != nickname.to_lowercase()
+    {
+        release_nickname(nicknames, &previous.nickname, &player_id);
+    }
+    logins
+        .entry(auth_req.wallet_address.clone())
+        .or_default()
+        .push(Instant::now());
+    log::info!(
+        "Auth request {} opened session for player {}",
+        request_id,
+        claims.player_id
+    );
+
+    Ok(reply::json(&AuthResponse {
+        jwt_token: token,
+        player_id: claims.player_id,
+        expires_in: (JWT_EXPIRATION_HOURS * 3600) as u64,
+    }))
+}
+
+/// Kick reason sent to players whose wallet no longer passes the token check.
+const TOKEN_REVOKED_REASON: &str = "token no longer held";
+
+/// Re-checks the token ownership of every live session each `reverify_interval_secs`, so a
+/// wallet that sells its token mid-session is kicked instead of playing until its JWT expires.
+///
+/// Returns immediately in debug mode or when the interval is 0.
+pub async fn reverify_sessions(
+    context: AuthContext,
+    system: ActorSystem<GameEvent>,
+    connection_manager: ConnectionManager,
+) {
+    let config = &context.config;
+    if config.debug_mode || config.reverify_interval_secs == 0 {
+        return;
+    }
+
+    let interval = std::time::Duration::from_secs(config.reverify_interval_secs);
+    let mut timer = tokio::time::interval(interval);
+    // The first tick completes immediately, and every session was only just verified.
+    timer.tick().await;
+    loop {
+        timer.tick().await;
+        reverify_cycle(&context, &system, &connection_manager, interval).await;
+    }
+}
+
+/// One pass over the sessions' wallets, with the RPC calls spread across half of `interval` so a full
+/// server doesn't hit the node in a single burst.
+///
+/// RPC failures keep the sessions; only a confirmed insufficient balance revokes every
+/// character of the wallet.
+async fn reverify_cycle(
+    context: &AuthContext,
+    system: &ActorSystem<GameEvent>,
+    connection_manager: &ConnectionManager,
+    interval: std::time::Duration,
+) {
+    let AuthContext {
+        verifier, sessions, ..
+    } = context;
+    let mut wallets: Vec<String> = sessions.iter().map(|entry| entry.wallet.clone()).collect();
+    wallets.sort_unstable();
+    wallets.dedup();
+    let spacing = interval / 2 / wallets.len().max(1) as u32;
+
+    for (i, wallet) in wallets.iter().enumerate() {
+        if i > 0 {
+            tokio::time::sleep(spacing).await;
+        }
+
+        match verifier.verify_token_ownership(wallet).await {
+            Ok(TokenBalance::Sufficient) => {}
+            Ok(TokenBalance::Insufficient(held)) => {
+                log::warn!(
+                    "Wallet {} no longer holds the required token (balance {}), revoking session",
+                    wallet,
+                    held
+                );
+                let player_ids: Vec<String> = sessions
+                    .iter()
+                    .filter(|entry| entry.wallet == *wallet)
+                    .map(|entry| entry.key().clone())
+                    .collect();
+                for player_id in player_ids {
+                    end_session(context, &player_id);
+                    kick_player(
+                        system,
+                        connection_manager,
+                        &player_id,
+                        TOKEN_REVOKED_REASON.to_string(),
+                    )
+                    .await;
+                }
+            }
+            Err(e) => log::warn!("Could not re-verify wallet {}: {}", wallet, e),
+        }
+    }
+}
+
+/// Whether `message` is a nonce issued to `wallet` less than `ttl_secs` ago and not used yet.
+fn challenge_is_fresh(
+    challenges: &DashMap<String, (String, Instant)>,
+    wallet: &str,
+    message: &str,
+    ttl_secs: u64,
+) -> bool {
+    challenges
+        .get(message)
+        .is_some_and(|entry| entry.0 == wallet && entry.1.elapsed().as_secs() < ttl_secs)
+}
+
+/// Drops challenges nobody answered within their TTL, failure counts whose window has passed,
+/// logins older than `reauth_window_secs` and sessions too old to refresh, freeing their
+/// nicknames. Run periodically, so the auth path never scans the whole maps.
+pub fn prune_auth_state(context: &AuthContext) {
+    let grace_secs = context.config.refresh_grace_hours * 3600;
+    let refreshable_after = (Utc::now().timestamp() - grace_secs as i64).max(0) as usize;
+    let expired: Vec<String> = context
+        .sessions
+        .iter()
+        .filter(|session| session.token_exp < refreshable_after)
+        .map(|session| session.key().clone())
+        .collect();
+    for player_id in expired {
+        // Skips sessions refreshed or replaced since the scan.
+        if let Some((_, session)) = context.sessions.remove_if(&player_id, |_, session| {
+            session.token_exp < refreshable_after
+        }) {
+            release_nickname(&context.nicknames, &session.nickname, &player_id);
+        }
+    }
+
+    let ttl_secs = context.config.challenge_ttl_secs;
+    context
+        .challenges
+        .retain(|_, (_, issued)| issued.elapsed().as_secs() < ttl_secs);
+    let window_secs = context.config.auth_rate_window_secs;
+    context
+        .auth_attempts
+        .retain(|_, (_, window_start)| window_start.elapsed().as_secs() < window_secs);
+    let window = std::time::Duration::from_secs(context.config.reauth_window_secs);
+    context.logins.retain(|_, logins| {
+        logins.retain(|at| at.elapsed() < window);
+        !logins.is_empty()
+    });
+}
+
+/// Exchanges a valid, or recently expired, token for a fresh one.
+///
+/// The token must still be the one recorded for the player's active session, and may be
+/// at most `refresh_grace_hours` past its expiry.
+pub async fn handle_refresh(
+    refresh_req: RefreshRequest,
+    sessions: Arc<DashMap<String, SessionInfo>>,
+    config: Arc<ServerConfig>,
+) -> Result<impl Reply, Rejection> {
+    let mut validation = Validation::default();
+    validation.leeway = config.refresh_grace_hours * 3600;
+
+    let claims = decode::<Claims>(
+        &refresh_req.jwt_token,
+        &DecodingKey::from_secret(config.jwt_secret.as_bytes()),
+        &validation,
+    )
+    .map_err(|e| {
+        log::debug!("Refresh rejected: {}", e);
+        reject::custom(AuthError::InvalidToken)
+    })?
+    .claims;
+
+    let mut session = sessions
+        .get_mut(&claims.player_id)
+        .ok_or_else(|| reject::custom(AuthError::InvalidToken))?;
+
+    if session.jwt_token != refresh_req.jwt_token {
+        log::debug!(
+            "Refresh rejected: token superseded for {}",
+            claims.player_id
+        );
+        return Err(reject::custom(AuthError::InvalidToken));
+    }
+
+    let claims = Claims {
+        exp: expiration_timestamp(),
+        ..claims
+    };
+    let token = encode_token(&claims, &config.jwt_secret)
+        .map_err(|e| reject::custom(AuthError::Internal(e.to_string())))?;
+    session.jwt_token = token.clone();
+    session.token_exp = claims.exp;
 
     Ok(reply::json(&AuthResponse {
         jwt_token: token,
@@ -140,3 +730,1226 @@ pub async fn handle_auth(
         expires_in: (JWT_EXPIRATION_HOURS * 3600) as u64,
     }))
 }
+
+/// Maps rejections raised by the auth and admin handlers to a status code and a JSON error body.
+pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Rejection> {
+    if let Some(error) = err.find::<AuthError>() {
+        if let AuthError::Internal(details) = error {
+            log::error!("Auth internal error: {}", details);
+        }
+
+        return Ok(error_reply(error.to_string(), error.status()));
+    }
+
+    if let Some(error) = err.find::<AdminError>() {
+        return Ok(error_reply(error.to_string(), error.status()));
+    }
+
+    Err(err)
+}
+
+fn error_reply(message: String, status: StatusCode) -> impl Reply {
+    reply::with_status(
+        reply::json(&serde_json::json!({ "error": message })),
+        status,
+    )
+}
+
+/// Punctuation allowed in nicknames alongside letters and digits.
+const NICKNAME_PUNCTUATION: &[char] = &['_', '-', '.'];
+
+const NICKNAME_TAKEN: &str = "nickname is already in use";
+
+/// Trims and NFC-normalizes `raw`, then checks its length and characters. Returns the
+/// normalized nickname.
+pub fn validate_nickname(raw: &str) -> Result<String, String> {
+    let nickname: String = raw.trim().nfc().collect();
+
+    let length = nickname.chars().count();
+    if !(MIN_NICKNAME_LENGTH..=MAX_NICKNAME_LENGTH).contains(&length) {
+        return Err(format!(
+            "nickname must be between {} and {} characters",
+            MIN_NICKNAME_LENGTH, MAX_NICKNAME_LENGTH
+        ));
+    }
+
+    if !nickname
+        .chars()
+        .all(|c| c.is_alphanumeric() || NICKNAME_PUNCTUATION.contains(&c))
+    {
+        return Err("nickname may only contain letters, digits, '_', '-' and '.'".to_string());
+    }
+
+    Ok(nickname)
+}
+
+/// Claims `nickname`, ignoring case, for `player_id`. False if another player holds it.
+fn reserve_nickname(nicknames: &DashMap<String, String>, nickname: &str, player_id: &str) -> bool {
+    match nicknames.entry(nickname.to_lowercase()) {
+        Entry::Occupied(owner) => owner.get() == player_id,
+        Entry::Vacant(slot) => {
+            slot.insert(player_id.to_string());
+            true
+        }
+    }
+}
+
+/// Frees `nickname` if `player_id` still holds it.
+fn release_nickname(nicknames: &DashMap<String, String>, nickname: &str, player_id: &str) {
+    nicknames.remove_if(&nickname.to_lowercase(), |_, owner| owner == player_id);
+}
+
+/// Removes `player_id`'s session and frees its nickname.
+fn end_session(context: &AuthContext, player_id: &str) {
+    if let Some((_, session)) = context.sessions.remove(player_id) {
+        release_nickname(&context.nicknames, &session.nickname, player_id);
+    }
+}
+
+/// Trims `raw` and checks it is a non-empty room name of ASCII letters, digits, '_' and '-'.
+pub fn validate_room(raw: &str) -> Result<String, String> {
+    let room = raw.trim();
+
+    if room.is_empty() || room.len() > MAX_ROOM_LENGTH {
+        return Err(format!(
+            "room must be between 1 and {} characters",
+            MAX_ROOM_LENGTH
+        ));
+    }
+
+    if !room
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err("room may only contain letters, digits, '_' and '-'".to_string());
+    }
+
+    Ok(room.to_string())
+}
+
+/// Trims `raw` and checks it is a non-empty character id of ASCII letters, digits, '_' and '-'.
+pub fn validate_character_id(raw: &str) -> Result<String, String> {
+    let character_id = raw.trim();
+
+    if character_id.is_empty() || character_id.len() > MAX_CHARACTER_ID_LENGTH {
+        return Err(format!(
+            "character id must be between 1 and {} characters",
+            MAX_CHARACTER_ID_LENGTH
+        ));
+    }
+
+    if !character_id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err("character id may only contain letters, digits, '_' and '-'".to_string());
+    }
+
+    Ok(character_id.to_string())
+}
+
+/// Characters of `wallet` other than `player_id` that currently hold a session.
+fn characters_signed_in(
+    sessions: &DashMap<String, SessionInfo>,
+    wallet: &str,
+    player_id: &str,
+) -> usize {
+    sessions
+        .iter()
+        .filter(|entry| entry.value().wallet == wallet && entry.key() != player_id)
+        .count()
+}
+
+/// The wallet itself for its default character, `wallet:character_id` for any other.
+pub fn player_id_for(wallet: &str, character_id: Option<&str>) -> String {
+    match character_id {
+        Some(character_id) => format!("{}:{}", wallet, character_id),
+        None => wallet.to_string(),
+    }
+}
+
+/// Whether `key` already failed `limit` times in its current fixed window.
+fn is_rate_limited(
+    attempts: &DashMap<String, (u32, Instant)>,
+    key: &str,
+    limit: u32,
+    window_secs: u64,
+) -> bool {
+    attempts.get(key).is_some_and(|entry| {
+        let (count, window_start) = *entry;
+        count >= limit && window_start.elapsed().as_secs() < window_secs
+    })
+}
+
+/// Counts a failed attempt for `key`, opening a new window if its last one has passed.
+fn record_failure(attempts: &DashMap<String, (u32, Instant)>, key: &str, window_secs: u64) {
+    let mut entry = attempts
+        .entry(key.to_string())
+        .or_insert((0, Instant::now()));
+    let (count, window_start) = entry.value_mut();
+
+    if window_start.elapsed().as_secs() >= window_secs {
+        *count = 0;
+        *window_start = Instant::now();
+    }
+
+    *count += 1;
+}
+
+/// Seconds until `wallet` may authenticate again, if it logged in `limit` times within the
+/// last `window_secs`.
+fn reauth_cooldown(
+    logins: &DashMap<String, Vec<Instant>>,
+    wallet: &str,
+    limit: u32,
+    window_secs: u64,
+) -> Option<u64> {
+    if limit == 0 {
+        return None;
+    }
+    let window = std::time::Duration::from_secs(window_secs);
+    let mut recent: Vec<Instant> = logins
+        .get(wallet)?
+        .iter()
+        .copied()
+        .filter(|at| at.elapsed() < window)
+        .collect();
+    if recent.len() < limit as usize {
+        return None;
+    }
+
+    // One more is allowed once all but `limit - 1` of them have aged out.
+    recent.sort();
+    let frees_slot = recent[recent.len() - limit as usize];
+    Some(window.saturating_sub(frees_slot.elapsed()).as_secs().max(1))
+}
+
+fn expiration_timestamp() -> usize {
+    Utc::now()
+        .checked_add_signed(Duration::hours(JWT_EXPIRATION_HOURS))
+        .unwrap()
+        .timestamp() as usize
+}
+
+fn encode_token(claims: &Claims, jwt_secret: &str) -> Result<String, jsonwebtoken::errors::Error> {
+    encode(
+        &Header::default(),
+        claims,
+        &EncodingKey::from_secret(jwt_secret.as_bytes()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor_system::EventBusStats;
+    use solana_sdk::signature::{Keypair, Signer};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    const SECRET: &str = "test-secret";
+    const MINT_A: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+    const MINT_B: &str = "So11111111111111111111111111111111111111112";
+    const WALLET: &str = "11111111111111111111111111111111";
+
+    /// Serves canned token accounts per mint and records which mints were queried.
+    #[derive(Default)]
+    struct MockProvider {
+        accounts: HashMap<Pubkey, Vec<RpcKeyedAccount>>,
+        queried: Arc<Mutex<Vec<Pubkey>>>,
+    }
+
+    impl MockProvider {
+        fn holding(mut self, mint: &str, amounts: &[u64]) -> Self {
+            let accounts = amounts
+                .iter()
+                .map(|amount| token_account(*amount))
+                .collect();
+            self.accounts
+                .insert(Pubkey::from_str(mint).unwrap(), accounts);
+            self
+        }
+    }
+
+    impl TokenAccountsProvider for MockProvider {
+        fn get_token_accounts_by_owner(
+            &self,
+            _owner: &Pubkey,
+            mint: &Pubkey,
+        ) -> Result<Vec<RpcKeyedAccount>, String> {
+            self.queried.lock().unwrap().push(*mint);
+            Ok(self.accounts.get(mint).cloned().unwrap_or_default())
+        }
+
+        fn get_health(&self) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn token_account(amount: u64) -> RpcKeyedAccount {
+        serde_json::from_value(serde_json::json!({
+            "pubkey": WALLET,
+            "account": {
+                "lamports": 2039280,
+                "data": {
+                    "program": "spl-token",
+                    "parsed": {
+                        "type": "account",
+                        "info": {
+                            "tokenAmount": {
+                                "amount": amount.to_string(),
+                                "decimals": 0,
+                                "uiAmount": amount as f64,
+                                "uiAmountString": amount.to_string()
+                            }
+                        }
+                    },
+                    "space": 165
+                },
+                "owner": MINT_A,
+                "executable": false,
+                "rentEpoch": 0,
+                "space": 165
+            }
+        }))
+        .unwrap()
+    }
+
+    fn verifier(provider: MockProvider, mints: &str, debug_mode: bool) -> SolanaVerifier {
+        SolanaVerifier::with_provider(Box::new(provider), mints, 1, debug_mode).unwrap()
+    }
+
+    fn config() -> Arc<ServerConfig> {
+        Arc::new(ServerConfig {
+            jwt_secret: SECRET.to_string(),
+            refresh_grace_hours: 1,
+            ..ServerConfig::default()
+        })
+    }
+
+    fn session_with_token(
+        wallet: &str,
+        expired_secs_ago: i64,
+    ) -> (Arc<DashMap<String, SessionInfo>>, String) {
+        let claims = Claims {
+            wallet_address: wallet.to_string(),
+            player_id: wallet.to_string(),
+            nickname: "Nick".to_string(),
+            room: None,
+            exp: (Utc::now().timestamp() - expired_secs_ago) as usize,
+        };
+        let token = encode_token(&claims, SECRET).unwrap();
+        let sessions = Arc::new(DashMap::new());
+        sessions.insert(
+            wallet.to_string(),
+            SessionInfo {
+                wallet: wallet.to_string(),
+                jwt_token: token.clone(),
+                nickname: "Nick".to_string(),
+                token_exp: claims.exp,
+                request_id: "test-request".to_string(),
+            },
+        );
+        (sessions, token)
+    }
+
+    fn auth_request(wallet: &str) -> AuthRequest {
+        AuthRequest {
+            wallet_address: wallet.to_string(),
+            signature: String::new(),
+            message: String::new(),
+            nickname: format!("Nick_{}", wallet),
+            room: None,
+            character_id: None,
+        }
+    }
+
+    /// A context around `config` with a verifier honouring its debug mode and fresh state.
+    fn auth_context(config: ServerConfig) -> AuthContext {
+        AuthContext {
+            verifier: Arc::new(
+                SolanaVerifier::new(
+                    "http://localhost:8899",
+                    &config.token_mint,
+                    1,
+                    config.debug_mode,
+                )
+                .unwrap(),
+            ),
+            sessions: Arc::new(DashMap::new()),
+            auth_attempts: Arc::new(DashMap::new()),
+            challenges: Arc::new(DashMap::new()),
+            nicknames: Arc::new(DashMap::new()),
+            logins: Arc::new(DashMap::new()),
+            config: Arc::new(config),
+            profanity: Arc::new(ProfanityFilter::default()),
+            overload: Arc::new(OverloadBreaker::default()),
+            metrics: Arc::new(Metrics::new()),
+        }
+    }
+
+    fn assert_rate_limited(result: Result<(), Rejection>) {
+        let rejection = result.err().unwrap();
+        assert!(matches!(
+            rejection.find::<AuthError>(),
+            Some(AuthError::RateLimited)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_failed_signatures_rate_limited_per_wallet() {
+        let context = signed_context(ServerConfig {
+            auth_rate_limit: 5,
+            auth_rate_window_secs: 60,
+            ..(*config()).clone()
+        });
+        let (keypair, other) = (Keypair::new(), Keypair::new());
+
+        // Successful logins don't spend the budget.
+        for _ in 0..6 {
+            assert!(signed_login(&keypair, &context).await.is_ok());
+        }
+        for _ in 0..5 {
+            let rejection = forged_login(&keypair, &context, None).await.err().unwrap();
+            assert!(matches!(
+                rejection.find::<AuthError>(),
+                Some(AuthError::InvalidSignature)
+            ));
+        }
+        assert_rate_limited(signed_login(&keypair, &context).await);
+
+        // Other wallets have their own budget.
+        assert!(signed_login(&other, &context).await.is_ok());
+
+        let rendered = context
+            .metrics
+            .render(0, &[], &[], EventBusStats::default());
+        assert!(rendered.contains("auth_attempts_total{result=\"success\"} 7"));
+        assert!(rendered.contains("auth_attempts_total{result=\"failure\"} 6"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_signatures_rate_limited_per_ip() {
+        let context = signed_context(ServerConfig {
+            auth_rate_limit: 3,
+            auth_rate_window_secs: 60,
+            ..(*config()).clone()
+        });
+        let remote: SocketAddr = "203.0.113.7:40000".parse().unwrap();
+
+        for _ in 0..3 {
+            assert!(
+                forged_login(&Keypair::new(), &context, Some(remote))
+                    .await
+                    .is_err()
+            );
+        }
+        let keypair = Keypair::new();
+        let nonce = challenge_nonce(&keypair, &context).await;
+        assert_rate_limited(
+            login_from(
+                signed_auth_request(&keypair, &nonce),
+                &context,
+                Some(remote),
+            )
+            .await,
+        );
+
+        let elsewhere = "198.51.100.1:40000".parse().ok();
+        assert!(
+            login_from(signed_auth_request(&keypair, &nonce), &context, elsewhere)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rapid_reauth_throttled_per_wallet() {
+        let context = auth_context(ServerConfig {
+            debug_mode: true,
+            auth_rate_limit: 100,
+            reauth_limit: 2,
+            reauth_window_secs: 60,
+            ..ServerConfig::default()
+        });
+        let login = |wallet: &str| {
+            handle_auth(
+                auth_request(wallet),
+                context.clone(),
+                None,
+                "test-request".to_string(),
+            )
+        };
+
+        for _ in 0..2 {
+            assert!(login("wallet").await.is_ok());
+        }
+        // The history outlives the session, so dropping it doesn't reset the count.
+        context.sessions.clear();
+        let rejection = login("wallet").await.err().unwrap();
+        match rejection.find::<AuthError>() {
+            Some(AuthError::ReauthCooldown { retry_after_secs }) => {
+                assert!((1..=60).contains(retry_after_secs));
+            }
+            other => panic!("expected a cooldown, got {:?}", other),
+        }
+
+        assert!(login("other").await.is_ok());
+        assert!(login("other").await.is_ok());
+
+        prune_auth_state(&context);
+        assert_eq!(context.logins.len(), 2);
+    }
+
+    #[test]
+    fn test_aged_logins_pruned() {
+        let context = auth_context(ServerConfig {
+            reauth_window_secs: 0,
+            ..ServerConfig::default()
+        });
+        context
+            .logins
+            .insert("wallet".to_string(), vec![Instant::now()]);
+        assert_eq!(reauth_cooldown(&context.logins, "wallet", 1, 0), None);
+
+        prune_auth_state(&context);
+        assert!(context.logins.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_auth_refused_while_overloaded() {
+        let context = AuthContext {
+            overload: Arc::new(OverloadBreaker::new(10, 5)),
+            ..auth_context(ServerConfig {
+                debug_mode: true,
+                ..ServerConfig::default()
+            })
+        };
+        context.overload.update(11);
+
+        let login = || {
+            handle_auth(
+                auth_request("wallet"),
+                context.clone(),
+                None,
+                "test-request".to_string(),
+            )
+        };
+        let rejection = login().await.err().unwrap();
+        assert!(matches!(
+            rejection.find::<AuthError>(),
+            Some(AuthError::Overloaded)
+        ));
+        assert!(context.sessions.is_empty());
+
+        context.overload.update(4);
+        assert!(login().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_characters_of_one_wallet_keep_separate_sessions() {
+        let context = auth_context(ServerConfig {
+            debug_mode: true,
+            ..ServerConfig::default()
+        });
+
+        for character_id in ["warrior", "mage"] {
+            let result = handle_auth(
+                AuthRequest {
+                    character_id: Some(character_id.to_string()),
+                    nickname: format!("Nick_{}", character_id),
+                    ..auth_request("wallet")
+                },
+                context.clone(),
+                None,
+                "test-request".to_string(),
+            )
+            .await;
+            assert!(result.is_ok());
+        }
+
+        assert_eq!(context.sessions.len(), 2);
+        for player_id in ["wallet:warrior", "wallet:mage"] {
+            let session = context.sessions.get(player_id).unwrap();
+            assert_eq!(session.wallet, "wallet");
+            let claims = decode::<Claims>(
+                &session.jwt_token,
+                &DecodingKey::from_secret(context.config.jwt_secret.as_bytes()),
+                &Validation::default(),
+            )
+            .unwrap()
+            .claims;
+            assert_eq!(claims.player_id, player_id);
+            assert_eq!(claims.wallet_address, "wallet");
+        }
+
+        let invalid = handle_auth(
+            AuthRequest {
+                character_id: Some("a:b".to_string()),
+                ..auth_request("wallet")
+            },
+            context,
+            None,
+            "test-request".to_string(),
+        )
+        .await
+        .err()
+        .unwrap();
+        assert!(matches!(
+            invalid.find::<AuthError>(),
+            Some(AuthError::InvalidCharacter(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_characters_per_wallet_capped() {
+        let context = auth_context(ServerConfig {
+            debug_mode: true,
+            max_characters_per_wallet: 2,
+            ..ServerConfig::default()
+        });
+        let character = |character_id: &str| AuthRequest {
+            character_id: Some(character_id.to_string()),
+            nickname: format!("Nick_{}", character_id),
+            ..auth_request("wallet")
+        };
+
+        for character_id in ["warrior", "mage"] {
+            assert!(
+                login_from(character(character_id), &context, None)
+                    .await
+                    .is_ok()
+            );
+        }
+        let rejection = login_from(character("rogue"), &context, None)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            rejection.find::<AuthError>(),
+            Some(AuthError::InvalidCharacter(_))
+        ));
+
+        // Characters already signed in can log in again, and other wallets are unaffected.
+        assert!(login_from(character("mage"), &context, None).await.is_ok());
+        assert!(
+            login_from(auth_request("other"), &context, None)
+                .await
+                .is_ok()
+        );
+        assert_eq!(context.sessions.len(), 3);
+    }
+
+    #[test]
+    fn test_attempt_window_resets_and_is_pruned() {
+        let context = auth_context(ServerConfig {
+            auth_rate_window_secs: 0,
+            ..ServerConfig::default()
+        });
+        record_failure(&context.auth_attempts, "wallet", 0);
+        assert!(!is_rate_limited(&context.auth_attempts, "wallet", 1, 0));
+        prune_auth_state(&context);
+        assert!(context.auth_attempts.is_empty());
+
+        let attempts = DashMap::new();
+        record_failure(&attempts, "other", 60);
+        assert!(is_rate_limited(&attempts, "other", 1, 60));
+        assert!(!is_rate_limited(&attempts, "other", 2, 60));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_recently_expired_token() {
+        let (sessions, token) = session_with_token("wallet", 600);
+
+        let result = handle_refresh(
+            RefreshRequest {
+                jwt_token: token.clone(),
+            },
+            sessions.clone(),
+            config(),
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert_ne!(sessions.get("wallet").unwrap().jwt_token, token);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_rejects_too_old_token() {
+        let (sessions, token) = session_with_token("wallet", 2 * 3600);
+
+        let result = handle_refresh(RefreshRequest { jwt_token: token }, sessions, config()).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_rejects_removed_session() {
+        let (sessions, token) = session_with_token("wallet", -3600);
+        sessions.remove("wallet");
+
+        let result = handle_refresh(RefreshRequest { jwt_token: token }, sessions, config()).await;
+
+        assert!(result.is_err());
+    }
+
+    async fn rejection_response(error: AuthError) -> (StatusCode, serde_json::Value) {
+        let response = handle_rejection(reject::custom(error))
+            .await
+            .unwrap()
+            .into_response();
+        let status = response.status();
+        let body = warp::hyper::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[test]
+    fn test_nickname_validation_errors() {
+        for nickname in ["", " ", "a", &"x".repeat(21), "Bad\u{7}Name", "new\nline"] {
+            assert!(
+                validate_nickname(nickname).is_err(),
+                "{:?} should be rejected",
+                nickname
+            );
+        }
+    }
+
+    #[test]
+    fn test_nickname_normalized() {
+        assert_eq!(validate_nickname("  Player_1.x  ").unwrap(), "Player_1.x");
+        // "e" followed by a combining acute accent composes to a single "é".
+        assert_eq!(validate_nickname("Rene\u{301}").unwrap(), "Ren\u{e9}");
+    }
+
+    #[test]
+    fn test_nickname_reserved_per_player_ignoring_case() {
+        let nicknames = DashMap::new();
+        assert!(reserve_nickname(&nicknames, "Nick", "wallet"));
+        assert!(reserve_nickname(&nicknames, "NICK", "wallet"));
+        assert!(!reserve_nickname(&nicknames, "nick", "other"));
+
+        // Only the holder can free it.
+        release_nickname(&nicknames, "nick", "other");
+        assert!(!reserve_nickname(&nicknames, "nick", "other"));
+        release_nickname(&nicknames, "nick", "wallet");
+        assert!(reserve_nickname(&nicknames, "nick", "other"));
+    }
+
+    #[tokio::test]
+    async fn test_nickname_freed_when_session_expires() {
+        let context = auth_context(ServerConfig {
+            debug_mode: true,
+            refresh_grace_hours: 1,
+            ..ServerConfig::default()
+        });
+        let login = |wallet: &str| {
+            handle_auth(
+                AuthRequest {
+                    nickname: "Shared".to_string(),
+                    ..auth_request(wallet)
+                },
+                context.clone(),
+                None,
+                "test-request".to_string(),
+            )
+        };
+
+        assert!(login("wallet").await.is_ok());
+        // A new name frees the old one.
+        let renamed = AuthRequest {
+            nickname: "Renamed".to_string(),
+            ..auth_request("wallet")
+        };
+        assert!(
+            handle_auth(renamed, context.clone(), None, "rename".to_string())
+                .await
+                .is_ok()
+        );
+        assert!(login("other").await.is_ok());
+        let rejection = login("wallet").await.err().unwrap();
+        assert!(matches!(
+            rejection.find::<AuthError>(),
+            Some(AuthError::InvalidNickname(message)) if message == NICKNAME_TAKEN
+        ));
+
+        prune_auth_state(&context);
+        assert_eq!(context.sessions.len(), 2);
+
+        context.sessions.get_mut("other").unwrap().token_exp =
+            (Utc::now().timestamp() - 2 * 3600) as usize;
+        prune_auth_state(&context);
+        assert!(!context.sessions.contains_key("other"));
+        assert!(login("wallet").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rejection_status_and_body() {
+        let cases = [
+            (
+                AuthError::InvalidSignature,
+                StatusCode::UNAUTHORIZED,
+                "invalid wallet signature",
+            ),
+            (
+                AuthError::NoToken,
+                StatusCode::FORBIDDEN,
+                "wallet does not hold the required token",
+            ),
+            (
+                AuthError::InsufficientBalance {
+                    held: 3,
+                    required: 10,
+                },
+                StatusCode::FORBIDDEN,
+                "wallet holds 3 tokens but 10 are required",
+            ),
+            (
+                AuthError::InvalidToken,
+                StatusCode::UNAUTHORIZED,
+                "invalid or expired session token",
+            ),
+            (
+                AuthError::RateLimited,
+                StatusCode::TOO_MANY_REQUESTS,
+                "too many authentication attempts",
+            ),
+            (
+                AuthError::InvalidNickname("nickname is already in use".to_string()),
+                StatusCode::BAD_REQUEST,
+                "nickname is already in use",
+            ),
+            (
+                AuthError::ReauthCooldown {
+                    retry_after_secs: 30,
+                },
+                StatusCode::TOO_MANY_REQUESTS,
+                "reconnecting too often, retry in 30s",
+            ),
+            (
+                AuthError::Overloaded,
+                StatusCode::SERVICE_UNAVAILABLE,
+                "server overloaded",
+            ),
+            (
+                AuthError::Internal("rpc down".to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal server error",
+            ),
+        ];
+
+        for (error, status, message) in cases {
+            let (actual_status, body) = rejection_response(error).await;
+            assert_eq!(actual_status, status);
+            assert_eq!(body, serde_json::json!({ "error": message }));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unrelated_rejection_passes_through() {
+        assert!(handle_rejection(reject::not_found()).await.is_err());
+    }
+
+    #[test]
+    fn test_parse_token_mints_single_and_list() {
+        assert_eq!(
+            parse_token_mints(MINT_A).unwrap(),
+            vec![Pubkey::from_str(MINT_A).unwrap()]
+        );
+        assert_eq!(
+            parse_token_mints(&format!("{}, {},", MINT_A, MINT_B)).unwrap(),
+            vec![
+                Pubkey::from_str(MINT_A).unwrap(),
+                Pubkey::from_str(MINT_B).unwrap()
+            ]
+        );
+        assert!(parse_token_mints(&format!("{},not-a-mint", MINT_A)).is_err());
+        assert!(parse_token_mints(" , ").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ownership_matches_any_mint() {
+        let mints = format!("{},{}", MINT_A, MINT_B);
+        let holds_b = verifier(MockProvider::default().holding(MINT_B, &[1]), &mints, false);
+        let holds_none = verifier(MockProvider::default(), &mints, false);
+
+        assert_eq!(
+            holds_b.verify_token_ownership(WALLET).await.unwrap(),
+            TokenBalance::Sufficient
+        );
+        assert_eq!(
+            holds_none.verify_token_ownership(WALLET).await.unwrap(),
+            TokenBalance::Insufficient(0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ownership_short_circuits_on_first_match() {
+        let provider = MockProvider::default()
+            .holding(MINT_A, &[1])
+            .holding(MINT_B, &[1]);
+        let queried = provider.queried.clone();
+        let verifier = verifier(provider, &format!("{},{}", MINT_A, MINT_B), false);
+
+        assert_eq!(
+            verifier.verify_token_ownership(WALLET).await.unwrap(),
+            TokenBalance::Sufficient
+        );
+        assert_eq!(
+            *queried.lock().unwrap(),
+            vec![Pubkey::from_str(MINT_A).unwrap()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_debug_mode_skips_ownership_lookup() {
+        let provider = MockProvider::default();
+        let queried = provider.queried.clone();
+        let verifier = verifier(provider, MINT_A, true);
+
+        assert_eq!(
+            verifier.verify_token_ownership(WALLET).await.unwrap(),
+            TokenBalance::Sufficient
+        );
+        assert!(queried.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_balance_summed_across_accounts_meets_threshold() {
+        let provider = MockProvider::default().holding(MINT_A, &[4, 6]);
+        let verifier =
+            SolanaVerifier::with_provider(Box::new(provider), MINT_A, 10, false).unwrap();
+
+        assert_eq!(
+            verifier.verify_token_ownership(WALLET).await.unwrap(),
+            TokenBalance::Sufficient
+        );
+    }
+
+    #[tokio::test]
+    async fn test_balance_below_threshold_reports_held_amount() {
+        let provider = MockProvider::default()
+            .holding(MINT_A, &[4, 5])
+            .holding(MINT_B, &[0]);
+        let verifier = SolanaVerifier::with_provider(
+            Box::new(provider),
+            &format!("{},{}", MINT_A, MINT_B),
+            10,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            verifier.verify_token_ownership(WALLET).await.unwrap(),
+            TokenBalance::Insufficient(9)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_empty_token_account_does_not_count_as_ownership() {
+        let verifier = verifier(MockProvider::default().holding(MINT_A, &[0]), MINT_A, false);
+
+        assert_eq!(
+            verifier.verify_token_ownership(WALLET).await.unwrap(),
+            TokenBalance::Insufficient(0)
+        );
+    }
+
+    fn signed_auth_request(keypair: &Keypair, message: &str) -> AuthRequest {
+        AuthRequest {
+            wallet_address: keypair.pubkey().to_string(),
+            signature: keypair.sign_message(message.as_bytes()).to_string(),
+            message: message.to_string(),
+            nickname: format!("Signer_{}", &keypair.pubkey().to_string()[..8]),
+            room: None,
+            character_id: None,
+        }
+    }
+
+    /// A context checking real signatures, in which every wallet holds a `MINT_A` token.
+    fn signed_context(config: ServerConfig) -> AuthContext {
+        let provider = MockProvider::default().holding(MINT_A, &[1]);
+        AuthContext {
+            verifier: Arc::new(verifier(provider, MINT_A, false)),
+            ..auth_context(config)
+        }
+    }
+
+    async fn challenge_nonce(keypair: &Keypair, context: &AuthContext) -> String {
+        let reply = handle_challenge(
+            ChallengeQuery {
+                wallet: keypair.pubkey().to_string(),
+            },
+            context.challenges.clone(),
+            context.config.clone(),
+        )
+        .await
+        .unwrap();
+        let body = warp::hyper::body::to_bytes(reply.into_response().into_body())
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        body["nonce"].as_str().unwrap().to_string()
+    }
+
+    /// Answers a fresh challenge for `keypair` with a valid signature.
+    async fn signed_login(keypair: &Keypair, context: &AuthContext) -> Result<(), Rejection> {
+        let nonce = challenge_nonce(keypair, context).await;
+        login_from(signed_auth_request(keypair, &nonce), context, None).await
+    }
+
+    /// Answers a fresh challenge for `keypair` signed by some other key.
+    async fn forged_login(
+        keypair: &Keypair,
+        context: &AuthContext,
+        remote: Option<SocketAddr>,
+    ) -> Result<(), Rejection> {
+        let nonce = challenge_nonce(keypair, context).await;
+        let request = AuthRequest {
+            signature: Keypair::new().sign_message(nonce.as_bytes()).to_string(),
+            ..signed_auth_request(keypair, &nonce)
+        };
+        login_from(request, context, remote).await
+    }
+
+    async fn login_from(
+        request: AuthRequest,
+        context: &AuthContext,
+        remote: Option<SocketAddr>,
+    ) -> Result<(), Rejection> {
+        handle_auth(request, context.clone(), remote, "test-request".to_string())
+            .await
+            .map(|_| ())
+    }
+
+    fn assert_invalid_challenge(result: Result<reply::Json, Rejection>) {
+        let Err(rejection) = result else {
+            panic!("expected a rejection");
+        };
+        assert!(matches!(
+            rejection.find::<AuthError>(),
+            Some(AuthError::InvalidChallenge)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_replayed_challenge_rejected() {
+        let keypair = Keypair::new();
+        let context = signed_context((*config()).clone());
+        let nonce = challenge_nonce(&keypair, &context).await;
+
+        let first = issue_session(
+            signed_auth_request(&keypair, &nonce),
+            &context,
+            None,
+            "first",
+        )
+        .await;
+        assert!(first.is_ok());
+
+        let replay = issue_session(
+            signed_auth_request(&keypair, &nonce),
+            &context,
+            None,
+            "replay",
+        )
+        .await;
+        assert_invalid_challenge(replay);
+    }
+
+    #[tokio::test]
+    async fn test_unissued_message_rejected() {
+        let keypair = Keypair::new();
+        let context = signed_context((*config()).clone());
+        challenge_nonce(&keypair, &context).await;
+
+        let forged = issue_session(
+            signed_auth_request(&keypair, "any message"),
+            &context,
+            None,
+            "test-request",
+        )
+        .await;
+        assert_invalid_challenge(forged);
+    }
+
+    #[tokio::test]
+    async fn test_challenge_of_another_wallet_rejected() {
+        let (keypair, other) = (Keypair::new(), Keypair::new());
+        let context = signed_context((*config()).clone());
+        let nonce = challenge_nonce(&other, &context).await;
+
+        let stolen = issue_session(
+            signed_auth_request(&keypair, &nonce),
+            &context,
+            None,
+            "stolen",
+        )
+        .await;
+        assert_invalid_challenge(stolen);
+        assert!(context.challenges.contains_key(&nonce));
+    }
+
+    #[tokio::test]
+    async fn test_bad_signature_keeps_challenge() {
+        let keypair = Keypair::new();
+        let context = signed_context((*config()).clone());
+        let nonce = challenge_nonce(&keypair, &context).await;
+
+        let forged = AuthRequest {
+            signature: Keypair::new().sign_message(nonce.as_bytes()).to_string(),
+            ..signed_auth_request(&keypair, &nonce)
+        };
+        let rejection = issue_session(forged, &context, None, "forged")
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            rejection.find::<AuthError>(),
+            Some(AuthError::InvalidSignature)
+        ));
+
+        let genuine = issue_session(
+            signed_auth_request(&keypair, &nonce),
+            &context,
+            None,
+            "genuine",
+        )
+        .await;
+        assert!(genuine.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_outstanding_challenges_of_one_wallet_all_usable() {
+        let keypair = Keypair::new();
+        let context = signed_context((*config()).clone());
+        let first = challenge_nonce(&keypair, &context).await;
+        let second = challenge_nonce(&keypair, &context).await;
+
+        for nonce in [second, first] {
+            let result =
+                issue_session(signed_auth_request(&keypair, &nonce), &context, None, "tab").await;
+            assert!(result.is_ok());
+        }
+        assert!(context.challenges.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_expired_challenge_rejected_and_pruned() {
+        let keypair = Keypair::new();
+        let context = signed_context(ServerConfig {
+            challenge_ttl_secs: 0,
+            ..(*config()).clone()
+        });
+        let nonce = challenge_nonce(&keypair, &context).await;
+
+        let expired = issue_session(
+            signed_auth_request(&keypair, &nonce),
+            &context,
+            None,
+            "late",
+        )
+        .await;
+        assert_invalid_challenge(expired);
+        assert_eq!(context.challenges.len(), 1);
+
+        prune_auth_state(&context);
+        assert!(context.challenges.is_empty());
+    }
+
+    #[test]
+    fn test_validate_room() {
+        assert_eq!(validate_room("  arena-1 ").unwrap(), "arena-1");
+        assert!(validate_room("").is_err());
+        assert!(validate_room("no spaces").is_err());
+        assert!(validate_room(&"r".repeat(MAX_ROOM_LENGTH + 1)).is_err());
+    }
+
+    /// Holds one `MINT_A` token for as long as `holds` is set.
+    struct SwitchProvider {
+        holds: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    impl TokenAccountsProvider for SwitchProvider {
+        fn get_token_accounts_by_owner(
+            &self,
+            _owner: &Pubkey,
+            _mint: &Pubkey,
+        ) -> Result<Vec<RpcKeyedAccount>, String> {
+            let holds = self.holds.load(std::sync::atomic::Ordering::SeqCst);
+            Ok(if holds {
+                vec![token_account(1)]
+            } else {
+                vec![]
+            })
+        }
+
+        fn get_health(&self) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reverify_kicks_wallet_that_lost_its_token() {
+        use crate::actor_system::EventBus;
+        use crate::player::{PlayerActor, player_actor_name, player_path};
+        use crate::rng::SharedRng;
+
+        let holds = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let verifier = SolanaVerifier::with_provider(
+            Box::new(SwitchProvider {
+                holds: holds.clone(),
+            }),
+            MINT_A,
+            1,
+            false,
+        )
+        .unwrap();
+        let (sessions, _) = session_with_token(WALLET, -3600);
+        let context = AuthContext {
+            verifier: Arc::new(verifier),
+            sessions: sessions.clone(),
+            ..auth_context((*config()).clone())
+        };
+        context
+            .nicknames
+            .insert("nick".to_string(), WALLET.to_string());
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let manager = ConnectionManager::new();
+        let (tx, mut rx) = crate::network::outbound_channel(1024);
+        manager.add(WALLET.to_string(), tx.clone());
+        let actor = PlayerActor::new(
+            WALLET.to_string(),
+            WALLET.to_string(),
+            "Nick".to_string(),
+            tx,
+            config(),
+            Arc::new(Metrics::new()),
+            &SharedRng::default(),
+        );
+        let path = player_path(WALLET);
+        system
+            .create_actor(&player_actor_name(WALLET), actor)
+            .await
+            .unwrap();
+        let interval = std::time::Duration::from_secs(1);
+
+        reverify_cycle(&context, &system, &manager, interval).await;
+        assert!(sessions.contains_key(WALLET));
+        assert!(system.get_actor::<PlayerActor>(&path).await.is_some());
+
+        holds.store(false, std::sync::atomic::Ordering::SeqCst);
+        reverify_cycle(&context, &system, &manager, interval).await;
+        assert!(!sessions.contains_key(WALLET));
+        assert!(context.nicknames.is_empty());
+        assert!(system.get_actor::<PlayerActor>(&path).await.is_none());
+
+        let kicked = std::iter::from_fn(|| rx.try_recv().ok()).any(|frame| {
+            frame
+                .to_str()
+                .is_ok_and(|text| text.contains(TOKEN_REVOKED_REASON))
+        });
+        assert!(kicked);
+    }
+}
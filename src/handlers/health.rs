@@ -0,0 +1,94 @@
+use crate::handlers::auth::SolanaVerifier;
+use std::sync::Arc;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply, reply};
+
+/// Liveness: the process is up and serving HTTP.
+pub async fn handle_healthz() -> Result<impl Reply, Rejection> {
+    Ok(reply::json(&serde_json::json!({ "status": "ok" })))
+}
+
+/// Readiness: the Solana RPC that auth depends on is reachable.
+pub async fn handle_readyz(verifier: Arc<SolanaVerifier>) -> Result<impl Reply, Rejection> {
+    let health = tokio::task::spawn_blocking(move || verifier.check_rpc_health())
+        .await
+        .unwrap_or_else(|e| Err(format!("health check panicked: {}", e)));
+
+    Ok(match health {
+        Ok(()) => reply::with_status(
+            reply::json(&serde_json::json!({ "status": "ok" })),
+            StatusCode::OK,
+        ),
+        Err(e) => {
+            log::warn!("Readiness check failed: {}", e);
+            reply::with_status(
+                reply::json(&serde_json::json!({ "status": "unavailable", "error": e })),
+                StatusCode::SERVICE_UNAVAILABLE,
+            )
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::auth::TokenAccountsProvider;
+    use solana_client::rpc_response::RpcKeyedAccount;
+    use solana_sdk::pubkey::Pubkey;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use warp::Filter;
+
+    const MINT: &str = "So11111111111111111111111111111111111111112";
+
+    /// An RPC node that never answers, counting how often it was probed.
+    struct UnreachableRpc {
+        probes: Arc<AtomicUsize>,
+    }
+
+    impl TokenAccountsProvider for UnreachableRpc {
+        fn get_token_accounts_by_owner(
+            &self,
+            _owner: &Pubkey,
+            _mint: &Pubkey,
+        ) -> Result<Vec<RpcKeyedAccount>, String> {
+            Err("connection refused".to_string())
+        }
+
+        fn get_health(&self) -> Result<(), String> {
+            self.probes.fetch_add(1, Ordering::SeqCst);
+            Err("connection refused".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_healthz() {
+        let route = warp::path("healthz")
+            .and(warp::get())
+            .and_then(handle_healthz);
+        let response = warp::test::request().path("/healthz").reply(&route).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body, serde_json::json!({ "status": "ok" }));
+    }
+
+    #[tokio::test]
+    async fn test_readyz_unreachable_rpc() {
+        let probes = Arc::new(AtomicUsize::new(0));
+        let provider = UnreachableRpc {
+            probes: probes.clone(),
+        };
+        let verifier =
+            Arc::new(SolanaVerifier::with_provider(Box::new(provider), MINT, 1, false).unwrap());
+
+        for _ in 0..2 {
+            let response = handle_readyz(verifier.clone())
+                .await
+                .unwrap()
+                .into_response();
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+        // The second request is served from the cached probe.
+        assert_eq!(probes.load(Ordering::SeqCst), 1);
+    }
+}
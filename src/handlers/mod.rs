@@ -1,5 +1,17 @@
+mod admin;
 mod auth;
+mod health;
+mod request_id;
 mod websocket;
 
-pub use auth::{SolanaVerifier, handle_auth};
-pub use websocket::handle_connection;
+pub use admin::{
+    handle_admin_announce, handle_admin_anticheat, handle_admin_audit, handle_admin_ban,
+    handle_admin_kick, handle_admin_unban, handle_admin_warp, handle_api_state,
+};
+pub use auth::{
+    AuthContext, SolanaVerifier, handle_auth, handle_challenge, handle_refresh, handle_rejection,
+    prune_auth_state, reverify_sessions,
+};
+pub use health::{handle_healthz, handle_readyz};
+pub use request_id::request_id;
+pub use websocket::{ConnectionContext, handle_connection, handle_spectator};
@@ -0,0 +1,68 @@
+use std::convert::Infallible;
+use warp::http::HeaderMap;
+use warp::{Filter, Reply, reply};
+
+/// Header carrying a request's correlation id. Echoed back on the responses that use it.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest client-supplied id that is kept; anything longer is replaced with a fresh one.
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+/// The request's `X-Request-Id`, or a new UUID when it is missing or not a plausible id.
+pub fn request_id() -> impl Filter<Extract = (String,), Error = Infallible> + Clone {
+    warp::header::headers_cloned().map(|headers: HeaderMap| {
+        headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| is_valid_request_id(id))
+            .map(str::to_string)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+    })
+}
+
+/// Adds `request_id` to `reply` as its `X-Request-Id` header.
+pub fn with_request_id<R: Reply>(reply: R, request_id: &str) -> reply::WithHeader<R> {
+    reply::with_header(reply, REQUEST_ID_HEADER, request_id)
+}
+
+/// Ids end up in log lines and response headers, so only short printable tokens are accepted.
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route() -> impl Filter<Extract = (impl Reply,), Error = Infallible> + Clone {
+        request_id().map(|id: String| with_request_id(reply(), &id))
+    }
+
+    #[tokio::test]
+    async fn test_generated_id_returned_in_header() {
+        let response = warp::test::request().reply(&route()).await;
+
+        let id = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(uuid::Uuid::parse_str(id).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_client_id_kept_unless_invalid() {
+        let response = warp::test::request()
+            .header(REQUEST_ID_HEADER, "trace-42")
+            .reply(&route())
+            .await;
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "trace-42");
+
+        let response = warp::test::request()
+            .header(REQUEST_ID_HEADER, "bad id\twith spaces")
+            .reply(&route())
+            .await;
+        let id = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(uuid::Uuid::parse_str(id).is_ok());
+    }
+}
@@ -1,125 +1,669 @@
-use crate::actor_system::{ActorPath, ActorRef, ActorSystem};
-use crate::network::ConnectionManager;
-use crate::player::{MovePlayer, PlayerActor};
-use crate::types::{Claims, ClientMessage, GameEvent, SessionInfo};
+use crate::actor_system::{Actor, ActorError, ActorPath, ActorRef, ActorSystem};
+use crate::anticheat::SharedAntiCheatConfig;
+use crate::config::{ServerConfig, WireFormat};
+use crate::metrics::Metrics;
+use crate::network::{
+    BanList, ConnectionManager, ConnectionSlot, MessageKind, OutboundQueue, OutboundSender,
+    ProtocolVersion, RateLimiter, decode_client_message, encode_server_message, outbound_channel,
+    server_time_ms,
+};
+use crate::player::{
+    Attack, MovePlayer, PlayerActor, PlayerCommand, PlayerStore, QUERY_TIMEOUT, RebindSocket,
+    SendChat, player_actor_name, player_path,
+};
+use crate::rng::SharedRng;
+use crate::types::{Claims, ClientMessage, DEFAULT_ROOM, GameEvent, ServerMessage, SessionInfo};
 use dashmap::DashMap;
+use futures::stream::SplitSink;
 use futures::{SinkExt, StreamExt};
 use jsonwebtoken::{DecodingKey, Validation, decode};
+use rand::Rng;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use tokio::sync::mpsc;
-use tokio_stream::wrappers::UnboundedReceiverStream;
-use warp::ws::WebSocket;
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use warp::ws::{Message as WsMessage, WebSocket};
 
-const MAX_MOVES_PER_SECOND: u32 = 60;
+/// Most samples a single `MoveBatch` may carry.
+const MAX_MOVE_BATCH: usize = 16;
 
-const RATE_LIMIT_WINDOW_MS: u128 = 1000;
+/// Tracks when the peer last answered a ping.
+struct Heartbeat {
+    last_pong: std::time::Instant,
+    timeout: std::time::Duration,
+}
+
+impl Heartbeat {
+    fn new(timeout_ms: u64) -> Self {
+        Self {
+            last_pong: std::time::Instant::now(),
+            timeout: std::time::Duration::from_millis(timeout_ms),
+        }
+    }
+
+    fn record_pong(&mut self) {
+        self.last_pong = std::time::Instant::now();
+    }
+
+    fn is_expired(&self) -> bool {
+        self.last_pong.elapsed() > self.timeout
+    }
+}
+
+/// How a game connection's read loop ended, for the disconnect log line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Disconnect {
+    /// The client sent a close frame. Clients may close without a code.
+    ClientClosed { code: Option<u16>, reason: String },
+    /// The stream ended without a close frame.
+    StreamEnded,
+    /// Reading from the socket failed.
+    TransportError(String),
+    /// The server closed the socket for `CloseReason`.
+    ServerClosed(CloseReason),
+    /// The client fell too far behind and its socket was dropped.
+    Overflowed,
+}
+
+impl Disconnect {
+    fn from_close_frame(msg: &WsMessage) -> Self {
+        let (code, reason) = match msg.close_frame() {
+            Some((code, reason)) => (Some(code), reason.to_string()),
+            None => (None, String::new()),
+        };
+        Disconnect::ClientClosed { code, reason }
+    }
+
+    /// Clients that closed or dropped off may come back within the reconnect grace. Ones the
+    /// server cut off may not.
+    fn allows_resume(&self) -> bool {
+        !matches!(self, Disconnect::ServerClosed(_) | Disconnect::Overflowed)
+    }
+}
+
+impl std::fmt::Display for Disconnect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Disconnect::ClientClosed { code: None, .. } => write!(f, "client closed (no code)"),
+            Disconnect::ClientClosed {
+                code: Some(code),
+                reason,
+            } if reason.is_empty() => write!(f, "client closed ({})", code),
+            Disconnect::ClientClosed {
+                code: Some(code),
+                reason,
+            } => write!(f, "client closed ({}: {})", code, reason),
+            Disconnect::StreamEnded => write!(f, "stream ended without close frame"),
+            Disconnect::TransportError(e) => write!(f, "transport error: {}", e),
+            Disconnect::ServerClosed(reason) => {
+                write!(f, "server closed ({}: {})", reason.code(), reason.reason())
+            }
+            Disconnect::Overflowed => write!(f, "outbound queue overflowed"),
+        }
+    }
+}
+
+/// Why the server closes a socket. Sent as the close frame's code and reason, after a final
+/// message for clients that understand the protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CloseReason {
+    UnsupportedProtocol,
+    Banned,
+    InvalidToken,
+    SessionExpired,
+    ServerFull,
+    ServerError,
+    InvalidMessages,
+    HeartbeatTimeout,
+    /// The same player opened a newer connection.
+    Replaced,
+}
+
+impl CloseReason {
+    /// Standard close codes where one fits, otherwise from the 4000-4999 application range.
+    fn code(self) -> u16 {
+        match self {
+            CloseReason::UnsupportedProtocol => 1002,
+            CloseReason::InvalidMessages => 1008,
+            CloseReason::ServerError => 1011,
+            CloseReason::ServerFull => 1013,
+            CloseReason::InvalidToken => 4001,
+            CloseReason::SessionExpired => 4002,
+            CloseReason::Banned => 4003,
+            CloseReason::HeartbeatTimeout => 4004,
+            CloseReason::Replaced => 4005,
+        }
+    }
+
+    fn reason(self) -> &'static str {
+        match self {
+            CloseReason::UnsupportedProtocol => "unsupported subprotocol",
+            CloseReason::Banned => "banned",
+            CloseReason::InvalidToken => "invalid or expired token",
+            CloseReason::SessionExpired => "session expired",
+            CloseReason::ServerFull => "server full",
+            CloseReason::ServerError => "server error",
+            CloseReason::InvalidMessages => "too many invalid messages",
+            CloseReason::HeartbeatTimeout => "heartbeat timeout",
+            CloseReason::Replaced => "connected from another location",
+        }
+    }
+
+    /// The message sent ahead of the close frame, if the client is worth telling.
+    fn message(self) -> Option<ServerMessage> {
+        match self {
+            // The client can't decode our messages, has already been answered with an
+            // error per bad frame, or has stopped listening.
+            CloseReason::UnsupportedProtocol
+            | CloseReason::InvalidMessages
+            | CloseReason::HeartbeatTimeout => None,
+            CloseReason::Banned | CloseReason::ServerFull | CloseReason::Replaced => {
+                Some(ServerMessage::Kicked {
+                    reason: self.reason().to_string(),
+                })
+            }
+            CloseReason::InvalidToken | CloseReason::SessionExpired | CloseReason::ServerError => {
+                Some(ServerMessage::Error {
+                    message: self.reason().to_string(),
+                })
+            }
+        }
+    }
+
+    /// The final message, if any, followed by the close frame.
+    fn frames(self, wire_format: WireFormat) -> impl Iterator<Item = WsMessage> {
+        let close = WsMessage::close_with(self.code(), self.reason());
+        self.message()
+            .and_then(|msg| encode_server_message(&msg, wire_format))
+            .into_iter()
+            .chain(std::iter::once(close))
+    }
+}
+
+/// State shared by every game and spectator socket.
+#[derive(Clone)]
+pub struct ConnectionContext {
+    pub system: ActorSystem<GameEvent>,
+    pub sessions: Arc<DashMap<String, SessionInfo>>,
+    pub config: Arc<ServerConfig>,
+    pub connection_manager: ConnectionManager,
+    pub store: Option<Arc<dyn PlayerStore>>,
+    pub metrics: Arc<Metrics>,
+    pub bans: BanList,
+    pub anticheat: SharedAntiCheatConfig,
+    /// Draws debug session ids and spawn jitter.
+    pub rng: SharedRng,
+}
 
 pub async fn handle_connection(
     token: String,
-    system: ActorSystem<GameEvent>,
-    sessions: Arc<DashMap<String, SessionInfo>>,
-    jwt_secret: String,
     websocket: WebSocket,
-    debug_mode: bool,
-    connection_manager: ConnectionManager,
+    context: ConnectionContext,
+    remote: Option<SocketAddr>,
+    protocol: Option<ProtocolVersion>,
+    request_id: String,
 ) {
-    let claims = match authenticate(&token, &jwt_secret, debug_mode) {
-        Some(c) => c,
-        None => return,
+    let remote_ip = remote.map(|addr| addr.ip());
+    let Some((websocket, claims, protocol, _slot)) =
+        admit(websocket, &token, &context, remote_ip, protocol, false).await
+    else {
+        return;
     };
+    let ConnectionContext {
+        system,
+        sessions,
+        config,
+        connection_manager,
+        store,
+        metrics,
+        bans,
+        anticheat,
+        rng,
+    } = context;
 
-    if !debug_mode && !sessions.contains_key(&claims.wallet_address) {
-        log::error!("Session not found for wallet: {}", claims.wallet_address);
-        return;
+    let (ws_tx, mut ws_rx) = websocket.split();
+    let (sender, receiver) = outbound_channel(config.send_buffer);
+
+    // Ties the socket back to the `/auth` call whose token it presented.
+    let auth_request_id = sessions
+        .get(&claims.player_id)
+        .map(|session| session.request_id.clone());
+    log::info!(
+        "WebSocket connected - Player: {}, Wallet: {}, Protocol: {}, Request: {}, Auth request: {}",
+        claims.player_id,
+        claims.wallet_address,
+        protocol.name(),
+        request_id,
+        auth_request_id.as_deref().unwrap_or("none")
+    );
+
+    let actor_name = player_actor_name(&claims.player_id);
+    let actor_path = player_path(&claims.player_id);
+
+    // The older socket is told why it is being dropped before its actor is replaced.
+    if connection_manager.is_connected(&claims.player_id) {
+        log::info!(
+            "Player {} connected from another location, closing the previous connection",
+            claims.player_id
+        );
+        for frame in CloseReason::Replaced.frames(config.wire_format) {
+            connection_manager.send_to(&claims.player_id, frame);
+        }
     }
+    connection_manager.remove(&claims.player_id);
+    let queue = connection_manager.add(claims.player_id.clone(), sender.clone());
 
-    let (mut ws_tx, mut ws_rx) = websocket.split();
-    let (sender, receiver) = mpsc::unbounded_channel();
-    let mut receiver_stream = UnboundedReceiverStream::new(receiver);
+    let forwarder = spawn_forwarder(ws_tx, receiver, queue.clone());
 
-    log::debug!(
-        "WebSocket connected - Player: {}, Wallet: {}",
+    let actor_ref = match reattach_actor(
+        &system,
+        &connection_manager,
+        &actor_path,
+        &claims.player_id,
+        &sender,
+        remote_ip,
+    )
+    .await
+    {
+        Some(r) => {
+            log::debug!("Reattached actor for player: {}", claims.player_id);
+            r
+        }
+        None => {
+            let new_actor = || {
+                let mut actor = PlayerActor::new(
+                    claims.player_id.clone(),
+                    claims.wallet_address.clone(),
+                    claims.nickname.clone(),
+                    sender.clone(),
+                    config.clone(),
+                    metrics.clone(),
+                    &rng,
+                )
+                .with_store(store.clone())
+                .with_bans(bans.clone())
+                .with_anticheat(anticheat.clone());
+                actor.room = claims
+                    .room
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_ROOM.to_string());
+                actor.remote_ip = remote_ip;
+                actor
+            };
+
+            match replace_actor(&system, &actor_name, &actor_path, new_actor).await {
+                Ok(r) => {
+                    log::debug!("Created actor for player: {}", claims.player_id);
+                    r
+                }
+                Err(e) => {
+                    log::error!(
+                        "Failed to create actor for player {}: {:?}",
+                        claims.player_id,
+                        e
+                    );
+                    close_connection(&sender, CloseReason::ServerError, config.wire_format);
+                    connection_manager.remove_if_current(&claims.player_id, &sender);
+                    return;
+                }
+            }
+        }
+    };
+
+    let mut limiter = RateLimiter::new(&config);
+
+    // Pings are control frames, so they go straight onto the socket rather than
+    // through ServerMessage encoding. An interval of 0 disables the heartbeat.
+    let heartbeat_enabled = config.heartbeat_interval_ms > 0;
+    let mut heartbeat = Heartbeat::new(config.heartbeat_timeout_ms);
+    let mut ping_timer =
+        tokio::time::interval(Duration::from_millis(config.heartbeat_interval_ms.max(1)));
+    let mut invalid_messages = 0u32;
+
+    let disconnect = loop {
+        tokio::select! {
+            result = ws_rx.next() => match result {
+                Some(Ok(msg)) => {
+                    if msg.is_close() {
+                        break Disconnect::from_close_frame(&msg);
+                    } else if msg.is_pong() {
+                        heartbeat.record_pong();
+                    } else if msg.is_text() || msg.is_binary() {
+                        match decode_client_message(&msg, config.strict_protocol) {
+                            Ok(client_msg) => {
+                                invalid_messages = 0;
+                                metrics.record_message();
+                                process_message(
+                                    client_msg,
+                                    &actor_ref,
+                                    &claims.player_id,
+                                    &sender,
+                                    config.wire_format,
+                                    &mut limiter,
+                                );
+                            }
+                            Err(e) => {
+                                invalid_messages += 1;
+                                let error = ServerMessage::Error {
+                                    message: format!("invalid message format: {}", e),
+                                };
+                                if let Some(frame) =
+                                    encode_server_message(&error, config.wire_format)
+                                {
+                                    let _ = sender.send(frame);
+                                }
+                                if config.max_invalid_messages > 0
+                                    && invalid_messages >= config.max_invalid_messages
+                                {
+                                    log::warn!(
+                                        "Closing connection for player {} after {} invalid messages",
+                                        claims.player_id,
+                                        invalid_messages
+                                    );
+                                    close_connection(
+                                        &sender,
+                                        CloseReason::InvalidMessages,
+                                        config.wire_format,
+                                    );
+                                    break Disconnect::ServerClosed(CloseReason::InvalidMessages);
+                                }
+                            }
+                        }
+                    }
+                }
+                Some(Err(e)) => break Disconnect::TransportError(e.to_string()),
+                None => break Disconnect::StreamEnded,
+            },
+            _ = ping_timer.tick(), if heartbeat_enabled => {
+                if heartbeat.is_expired() {
+                    log::warn!("Heartbeat timed out for player {}", claims.player_id);
+                    close_connection(&sender, CloseReason::HeartbeatTimeout, config.wire_format);
+                    break Disconnect::ServerClosed(CloseReason::HeartbeatTimeout);
+                }
+                let _ = sender.send(WsMessage::ping(Vec::new()));
+            }
+            _ = queue.overflowed() => {
+                // A close frame would sit behind the backlog, so drop the socket outright.
+                forwarder.abort();
+                break Disconnect::Overflowed;
+            }
+        }
+    };
+
+    // Transport errors point at a network or client bug, so they stand out from clean closes.
+    let level = match disconnect {
+        Disconnect::TransportError(_) => log::Level::Warn,
+        _ => log::Level::Info,
+    };
+    log::log!(
+        level,
+        "WebSocket disconnected - Player: {}, Nickname: {}, Request: {}, Reason: {}",
         claims.player_id,
-        claims.wallet_address
+        claims.nickname,
+        request_id,
+        disconnect
     );
+    if !connection_manager.remove_if_current(&claims.player_id, &sender) {
+        log::debug!(
+            "Player {} already reconnected on another socket",
+            claims.player_id
+        );
+        return;
+    }
 
-    let actor_name = format!("player-{}", claims.player_id);
-    let actor_path = ActorPath::from(format!("/user/{}", actor_name));
+    // A peer that stopped answering pings is gone, and one shed for sending garbage or
+    // falling too far behind shouldn't get to resume, so there is nothing to wait for.
+    if !disconnect.allows_resume() || config.reconnect_grace_ms == 0 {
+        system.stop_actor(actor_ref.path()).await;
+        return;
+    }
 
-    system.stop_actor(&actor_path).await;
-    connection_manager.remove(&claims.player_id);
-    connection_manager.add(claims.player_id.clone(), sender.clone());
+    let grace = Duration::from_millis(config.reconnect_grace_ms);
+    let reaper_system = system.clone();
+    let reaper_manager = connection_manager.clone();
+    let player_id = claims.player_id.clone();
+    let reaper = tokio::spawn(async move {
+        tokio::time::sleep(grace).await;
+        if reaper_manager.finish_reap(&player_id) {
+            log::debug!("Reconnect grace expired for player {}", player_id);
+            reaper_system.stop_actor(actor_ref.path()).await;
+        }
+    });
+    connection_manager.schedule_reap(claims.player_id.clone(), reaper.abort_handle());
+}
+
+/// Runs the checks every game socket goes through before it is accepted: subprotocol, ban,
+/// token, session and a free player or spectator slot. Closes the socket with the reason and
+/// returns `None` if one fails.
+///
+/// Hold the returned slot for the lifetime of the connection, so every early return frees it.
+async fn admit(
+    websocket: WebSocket,
+    token: &str,
+    context: &ConnectionContext,
+    remote_ip: Option<IpAddr>,
+    protocol: Option<ProtocolVersion>,
+    spectator: bool,
+) -> Option<(WebSocket, Claims, ProtocolVersion, ConnectionSlot)> {
+    let ConnectionContext {
+        sessions,
+        config,
+        connection_manager,
+        bans,
+        rng,
+        ..
+    } = context;
+    let Some(protocol) = protocol else {
+        log::warn!("Rejecting connection offering only unsupported subprotocols");
+        reject_connection(
+            websocket,
+            CloseReason::UnsupportedProtocol,
+            config.wire_format,
+        )
+        .await;
+        return None;
+    };
 
+    if let Some(ip) = remote_ip
+        && bans.is_banned(&ip)
+    {
+        log::warn!("Rejecting connection from banned address {}", ip);
+        reject_connection(websocket, CloseReason::Banned, config.wire_format).await;
+        return None;
+    }
+
+    let Some(claims) = authenticate(token, &config.jwt_secret, config.debug_mode, rng) else {
+        reject_connection(websocket, CloseReason::InvalidToken, config.wire_format).await;
+        return None;
+    };
+
+    if !config.debug_mode && !sessions.contains_key(&claims.player_id) {
+        log::error!("Session not found for player: {}", claims.player_id);
+        reject_connection(websocket, CloseReason::SessionExpired, config.wire_format).await;
+        return None;
+    }
+
+    let slot = if spectator {
+        connection_manager.try_reserve_spectator_slot(config.max_spectators)
+    } else {
+        connection_manager.try_reserve_slot(config.max_players)
+    };
+    let Some(slot) = slot else {
+        log::warn!("Server full, rejecting player {}", claims.player_id);
+        reject_connection(websocket, CloseReason::ServerFull, config.wire_format).await;
+        return None;
+    };
+
+    Some((websocket, claims, protocol, slot))
+}
+
+/// Writes queued frames to the socket, keeping `queue` in step with the channel backlog.
+fn spawn_forwarder(
+    mut ws_tx: SplitSink<WebSocket, WsMessage>,
+    mut receiver: mpsc::Receiver<WsMessage>,
+    queue: OutboundQueue,
+) -> JoinHandle<()> {
     tokio::spawn(async move {
-        while let Some(msg) = receiver_stream.next().await {
+        while let Some(msg) = receiver.recv().await {
             if ws_tx.send(msg).await.is_err() {
                 break;
             }
+            queue.set_len(receiver.len());
         }
-    });
+    })
+}
+
+/// Serves a spectator: the room's state stream without a player actor.
+///
+/// Spectators are admitted like players but never join the world, and anything they send
+/// other than pongs is ignored.
+pub async fn handle_spectator(
+    token: String,
+    websocket: WebSocket,
+    context: ConnectionContext,
+    remote: Option<SocketAddr>,
+    protocol: Option<ProtocolVersion>,
+) {
+    let Some((websocket, claims, _protocol, _slot)) = admit(
+        websocket,
+        &token,
+        &context,
+        remote.map(|addr| addr.ip()),
+        protocol,
+        true,
+    )
+    .await
+    else {
+        return;
+    };
+    let ConnectionContext {
+        config,
+        connection_manager,
+        ..
+    } = context;
 
-    let actor = PlayerActor::new(
-        claims.player_id.clone(),
-        claims.wallet_address.clone(),
-        claims.nickname.clone(),
-        sender,
+    let (ws_tx, mut ws_rx) = websocket.split();
+    let (sender, receiver) = outbound_channel(config.send_buffer);
+    let spectator_id = format!("spectator-{}", uuid::Uuid::new_v4());
+    let room = claims.room.unwrap_or_else(|| DEFAULT_ROOM.to_string());
+    log::debug!(
+        "Spectator {} connected - Wallet: {}, Room: {}",
+        spectator_id,
+        claims.wallet_address,
+        room
     );
 
-    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    let queue = connection_manager.add_spectator(spectator_id.clone(), room, sender.clone());
+    let forwarder = spawn_forwarder(ws_tx, receiver, queue.clone());
 
-    let actor_ref = match system.create_actor(&actor_name, actor).await {
-        Ok(r) => {
-            log::debug!("Created actor for player: {}", claims.player_id);
-            r
-        }
-        Err(e) => {
-            log::error!(
-                "Failed to create actor for player {}: {:?}",
-                claims.player_id,
-                e
-            );
-            return;
-        }
-    };
+    let heartbeat_enabled = config.heartbeat_interval_ms > 0;
+    let mut heartbeat = Heartbeat::new(config.heartbeat_timeout_ms);
+    let mut ping_timer =
+        tokio::time::interval(Duration::from_millis(config.heartbeat_interval_ms.max(1)));
 
-    let mut move_count: u32 = 0;
-    let mut window_start = std::time::Instant::now();
-
-    while let Some(result) = ws_rx.next().await {
-        match result {
-            Ok(msg) => {
-                if let Ok(text) = msg.to_str() {
-                    if let Ok(client_msg) = serde_json::from_str::<ClientMessage>(text) {
-                        process_message(
-                            client_msg,
-                            &actor_ref,
-                            &claims.player_id,
-                            &mut move_count,
-                            &mut window_start,
-                        );
-                    }
+    loop {
+        tokio::select! {
+            result = ws_rx.next() => match result {
+                Some(Ok(msg)) if msg.is_pong() => heartbeat.record_pong(),
+                Some(Ok(_)) => {}
+                _ => break,
+            },
+            _ = ping_timer.tick(), if heartbeat_enabled => {
+                if heartbeat.is_expired() {
+                    log::warn!("Heartbeat timed out for spectator {}", spectator_id);
+                    close_connection(&sender, CloseReason::HeartbeatTimeout, config.wire_format);
+                    break;
                 }
+                let _ = sender.send(WsMessage::ping(Vec::new()));
+            }
+            _ = queue.overflowed() => {
+                forwarder.abort();
+                break;
             }
-            Err(_) => break,
         }
     }
 
-    log::debug!(
-        "WebSocket disconnected - Player: {}, Nickname: {}",
-        claims.player_id,
-        claims.nickname
-    );
-    connection_manager.remove(&claims.player_id);
-    system.stop_actor(actor_ref.path()).await;
+    log::debug!("Spectator {} disconnected", spectator_id);
+    connection_manager.remove_if_current(&spectator_id, &sender);
+}
+
+/// Stops whatever actor is at `path` and creates a fresh one from `new_actor` in its place.
+///
+/// The old actor has finished its `post_stop` by the time the new one starts. A concurrent
+/// reconnect can still claim the path in between, in which case its actor is replaced in turn,
+/// so the latest connection wins instead of failing with `ActorError::Exists`.
+async fn replace_actor<A: Actor<GameEvent>>(
+    system: &ActorSystem<GameEvent>,
+    name: &str,
+    path: &ActorPath,
+    new_actor: impl Fn() -> A,
+) -> Result<ActorRef<GameEvent, A>, ActorError> {
+    loop {
+        system.stop_actor_and_wait(path).await;
+        match system.create_actor(name, new_actor()).await {
+            Err(ActorError::Exists(_)) => {
+                log::debug!(
+                    "Actor {} was recreated concurrently, replacing it again",
+                    path
+                );
+            }
+            result => return result,
+        }
+    }
 }
 
-fn authenticate(token: &str, jwt_secret: &str, debug_mode: bool) -> Option<Claims> {
+/// Rebinds a detached player's actor to the new socket if it is still within its grace period.
+async fn reattach_actor(
+    system: &ActorSystem<GameEvent>,
+    connection_manager: &ConnectionManager,
+    actor_path: &ActorPath,
+    player_id: &str,
+    sender: &OutboundSender,
+    remote_ip: Option<IpAddr>,
+) -> Option<ActorRef<GameEvent, PlayerActor>> {
+    if !connection_manager.cancel_reap(player_id) {
+        return None;
+    }
+
+    let actor_ref = system.get_actor::<PlayerActor>(actor_path).await?;
+    actor_ref
+        .tell(RebindSocket {
+            ws_sender: sender.clone(),
+            remote_ip,
+        })
+        .ok()?;
+    Some(actor_ref)
+}
+
+/// Tells the client why it is being turned away and closes the socket.
+async fn reject_connection(mut websocket: WebSocket, reason: CloseReason, wire_format: WireFormat) {
+    for frame in reason.frames(wire_format) {
+        let _ = websocket.send(frame).await;
+    }
+    let _ = websocket.close().await;
+}
+
+/// Queues the close for a socket that has already been handed to its forwarding task.
+fn close_connection(sender: &OutboundSender, reason: CloseReason, wire_format: WireFormat) {
+    for frame in reason.frames(wire_format) {
+        let _ = sender.send(frame);
+    }
+}
+
+fn authenticate(
+    token: &str,
+    jwt_secret: &str,
+    debug_mode: bool,
+    rng: &SharedRng,
+) -> Option<Claims> {
     if debug_mode && token.starts_with("debug_") {
-        let session_id = rand::random::<u64>().to_string();
+        let session_id = rng.with(|rng| rng.random::<u64>()).to_string();
         return Some(Claims {
             wallet_address: format!("debug_{}", session_id),
             player_id: format!("player_{}", session_id),
             nickname: format!("Player_{}", session_id),
+            room: None,
             exp: (chrono::Utc::now() + chrono::Duration::hours(24)).timestamp() as usize,
         });
     }
@@ -141,42 +685,913 @@ fn process_message(
     msg: ClientMessage,
     actor_ref: &ActorRef<GameEvent, PlayerActor>,
     player_id: &str,
-    move_count: &mut u32,
-    window_start: &mut std::time::Instant,
+    sender: &OutboundSender,
+    wire_format: WireFormat,
+    limiter: &mut RateLimiter,
 ) {
+    let mut within_budget = |kind: MessageKind, count: u32| {
+        if limiter.allow(kind, count) {
+            return true;
+        }
+        log::debug!("Rate limited player {}: {:?}", player_id, kind);
+        if let Some(frame) = encode_server_message(
+            &ServerMessage::Error {
+                message: "rate limited".to_string(),
+            },
+            wire_format,
+        ) {
+            let _ = sender.send(frame);
+        }
+        false
+    };
+
     match msg {
         ClientMessage::Move {
             position,
             velocity,
             delta_time,
+            client_time_ms,
         } => {
-            let now = std::time::Instant::now();
-            let elapsed_ms = now.duration_since(*window_start).as_millis();
-
-            if elapsed_ms >= RATE_LIMIT_WINDOW_MS {
-                *window_start = now;
-                *move_count = 0;
+            if !within_budget(MessageKind::Move, 1) {
+                return;
             }
 
-            *move_count += 1;
-
-            if *move_count > MAX_MOVES_PER_SECOND {
+            let _ = actor_ref.tell(PlayerCommand::Move(MovePlayer {
+                position,
+                velocity,
+                delta_time,
+                client_time_ms,
+            }));
+        }
+        ClientMessage::MoveBatch { moves } => {
+            if moves.len() > MAX_MOVE_BATCH {
                 log::debug!(
-                    "Rate limited player {}: {} moves/sec",
-                    player_id,
-                    move_count
+                    "Rejected batch of {} moves from player {}",
+                    moves.len(),
+                    player_id
                 );
+                if let Some(frame) = encode_server_message(
+                    &ServerMessage::Error {
+                        message: format!("Move batch exceeds {} samples", MAX_MOVE_BATCH),
+                    },
+                    wire_format,
+                ) {
+                    let _ = sender.send(frame);
+                }
                 return;
             }
 
-            let _ = actor_ref.tell(MovePlayer {
-                position,
-                velocity,
-                delta_time,
-            });
+            if moves.is_empty() {
+                return;
+            }
+
+            if !within_budget(MessageKind::Move, moves.len() as u32) {
+                return;
+            }
+
+            let moves = moves.into_iter().map(MovePlayer::from).collect();
+            let _ = actor_ref.tell(PlayerCommand::MoveBatch(moves));
         }
         ClientMessage::GetState => {
-            // Handled by broadcast loop
+            if !within_budget(MessageKind::GetState, 1) {
+                return;
+            }
+
+            // Answered off the read loop so a busy actor doesn't stall incoming frames.
+            let actor_ref = actor_ref.clone();
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                let (get_state, state) = PlayerCommand::get_state();
+                if actor_ref
+                    .ask_timeout(get_state, QUERY_TIMEOUT)
+                    .await
+                    .is_ok()
+                    && let Ok(state) = state.await
+                    && let Some(frame) =
+                        encode_server_message(&ServerMessage::SelfState { state }, wire_format)
+                {
+                    let _ = sender.send(frame);
+                }
+            });
+        }
+        ClientMessage::Chat { text } => {
+            if !within_budget(MessageKind::Chat, 1) {
+                return;
+            }
+
+            let _ = actor_ref.tell(SendChat { text });
+        }
+        ClientMessage::Attack { target_id } => {
+            if !within_budget(MessageKind::Attack, 1) {
+                return;
+            }
+
+            let _ = actor_ref.tell(Attack { target_id });
+        }
+        ClientMessage::TimeSync { client_send_ms } => {
+            // A connection-level concern, answered without a round trip through the actor.
+            let server_recv_ms = server_time_ms();
+            if !within_budget(MessageKind::TimeSync, 1) {
+                return;
+            }
+
+            let reply = ServerMessage::TimeSyncReply {
+                client_send_ms,
+                server_recv_ms,
+                server_send_ms: server_time_ms(),
+            };
+            if let Some(frame) = encode_server_message(&reply, wire_format) {
+                let _ = sender.send(frame);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor_system::EventBus;
+    use crate::anticheat::AntiCheatConfig;
+    use crate::types::{MAX_VIOLATIONS, MoveSample, Position};
+    use std::collections::HashMap;
+    use tokio::io::AsyncWriteExt;
+    use warp::Filter;
+
+    /// What the test `/game` route hands to the connection handlers. Tests override only the
+    /// parts they check.
+    #[derive(Clone)]
+    struct TestServer {
+        system: ActorSystem<GameEvent>,
+        manager: ConnectionManager,
+        sessions: Arc<DashMap<String, SessionInfo>>,
+        config: Arc<ServerConfig>,
+        bans: BanList,
+        remote: Option<SocketAddr>,
+    }
+
+    impl Default for TestServer {
+        fn default() -> Self {
+            Self {
+                system: ActorSystem::new("test", EventBus::new(16)),
+                manager: ConnectionManager::new(),
+                sessions: Arc::new(DashMap::new()),
+                config: Arc::new(ServerConfig {
+                    debug_mode: true,
+                    heartbeat_interval_ms: 0,
+                    ..ServerConfig::default()
+                }),
+                bans: BanList::new(),
+                remote: None,
+            }
+        }
+    }
+
+    impl TestServer {
+        fn context(&self) -> ConnectionContext {
+            ConnectionContext {
+                system: self.system.clone(),
+                sessions: self.sessions.clone(),
+                config: self.config.clone(),
+                connection_manager: self.manager.clone(),
+                store: None,
+                metrics: Arc::new(Metrics::new()),
+                bans: self.bans.clone(),
+                anticheat: AntiCheatConfig::shared(&self.config),
+                rng: SharedRng::default(),
+            }
+        }
+    }
+
+    /// `/game?token=..[&spectator=true]`, upgraded and negotiated the way `main` does it.
+    fn spawn_test_server(
+        server: TestServer,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path("game")
+            .and(warp::query::<HashMap<String, String>>())
+            .and(warp::header::optional::<String>("sec-websocket-protocol"))
+            .and(warp::ws())
+            .map(
+                move |params: HashMap<String, String>,
+                      requested: Option<String>,
+                      ws: warp::ws::Ws| {
+                    let (context, remote) = (server.context(), server.remote);
+                    let token = params.get("token").cloned().unwrap_or_default();
+                    let spectator = params.contains_key("spectator");
+                    let protocol = ProtocolVersion::negotiate(requested.as_deref());
+                    ws.on_upgrade(move |websocket| async move {
+                        if spectator {
+                            handle_spectator(token, websocket, context, remote, protocol).await
+                        } else {
+                            handle_connection(
+                                token,
+                                websocket,
+                                context,
+                                remote,
+                                protocol,
+                                "test-request".to_string(),
+                            )
+                            .await
+                        }
+                    })
+                },
+            )
+    }
+
+    /// A valid hour-long token for `player_id`, signed with `config`'s secret.
+    fn signed_token(config: &ServerConfig, player_id: &str, nickname: &str) -> String {
+        let claims = Claims {
+            wallet_address: "wallet".to_string(),
+            player_id: player_id.to_string(),
+            nickname: nickname.to_string(),
+            room: None,
+            exp: (chrono::Utc::now() + chrono::Duration::hours(1)).timestamp() as usize,
+        };
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(config.jwt_secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    /// A live session holding `token`, as `/auth` would have left it.
+    fn session_for(token: &str, nickname: &str) -> SessionInfo {
+        SessionInfo {
+            wallet: "wallet".to_string(),
+            jwt_token: token.to_string(),
+            nickname: nickname.to_string(),
+            token_exp: usize::MAX,
+            request_id: "test-request".to_string(),
+        }
+    }
+    /// A player connection to a fresh `server` route. Its actor is up once the first reply comes
+    /// back, since the socket is only read after the actor has started.
+    async fn connect(server: &TestServer, token: &str) -> warp::test::WsClient {
+        warp::test::ws()
+            .path(&format!("/game?token={}", token))
+            .handshake(spawn_test_server(server.clone()))
+            .await
+            .unwrap()
+    }
+
+    /// Sends `msg` as a JSON text frame without waiting for a reply.
+    async fn send_only(client: &mut warp::test::WsClient, msg: &ClientMessage) {
+        client.send_text(serde_json::to_string(msg).unwrap()).await;
+    }
+
+    /// Sends `msg` and decodes the next frame the server answers with.
+    async fn send_json(
+        client: &mut warp::test::WsClient,
+        msg: &ClientMessage,
+    ) -> serde_json::Value {
+        send_only(client, msg).await;
+        let reply = client.recv().await.unwrap();
+        serde_json::from_str(reply.to_str().unwrap()).unwrap()
+    }
+
+    /// The position in a `SelfState` reply.
+    fn position_of(reply: &serde_json::Value) -> Position {
+        assert_eq!(reply["type"], "SelfState");
+        serde_json::from_value(reply["state"]["position"].clone()).unwrap()
+    }
+
+    #[test]
+    fn test_heartbeat_expires_without_pong() {
+        let mut heartbeat = Heartbeat::new(50);
+        assert!(!heartbeat.is_expired());
+
+        heartbeat.last_pong -= std::time::Duration::from_millis(100);
+        assert!(heartbeat.is_expired());
+
+        heartbeat.record_pong();
+        assert!(!heartbeat.is_expired());
+    }
+
+    #[test]
+    fn test_client_close_frame_logged_apart_from_transport_error() {
+        let closed = Disconnect::from_close_frame(&WsMessage::close_with(4000u16, "going home"));
+        assert_eq!(
+            closed,
+            Disconnect::ClientClosed {
+                code: Some(4000),
+                reason: "going home".to_string(),
+            }
+        );
+        assert_eq!(closed.to_string(), "client closed (4000: going home)");
+        assert_eq!(
+            Disconnect::from_close_frame(&WsMessage::close()).to_string(),
+            "client closed (no code)"
+        );
+
+        let failed = Disconnect::TransportError("connection reset".to_string());
+        assert_eq!(failed.to_string(), "transport error: connection reset");
+        assert!(closed.allows_resume() && failed.allows_resume());
+        assert!(!Disconnect::ServerClosed(CloseReason::HeartbeatTimeout).allows_resume());
+    }
+
+    #[test]
+    fn test_same_debug_seed_repeats_session_ids() {
+        let session_ids = |seed| {
+            let rng = SharedRng::seeded(seed);
+            (0..3)
+                .map(|_| {
+                    authenticate("debug_player", "secret", true, &rng)
+                        .unwrap()
+                        .player_id
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let first = session_ids(42);
+        assert_eq!(first, session_ids(42));
+        assert_ne!(first, session_ids(43));
+        assert_ne!(first[0], first[1]);
+    }
+
+    #[tokio::test]
+    async fn test_silent_client_is_reaped() {
+        let server = TestServer {
+            config: Arc::new(ServerConfig {
+                debug_mode: true,
+                heartbeat_interval_ms: 10,
+                heartbeat_timeout_ms: 50,
+                ..ServerConfig::default()
+            }),
+            ..TestServer::default()
+        };
+
+        // warp's test client answers pings on its own, so upgrade a raw TCP stream
+        // instead and never read from it.
+        let (addr, serve) =
+            warp::serve(spawn_test_server(server.clone())).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(serve);
+        let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(
+                b"GET /game?token=debug_silent HTTP/1.1\r\n\
+                  Host: localhost\r\n\
+                  Connection: upgrade\r\n\
+                  Upgrade: websocket\r\n\
+                  Sec-WebSocket-Version: 13\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+            )
+            .await
+            .unwrap();
+
+        let reaped = tokio::time::timeout(Duration::from_secs(2), async {
+            while server.manager.count() == 0 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            while server.manager.count() > 0 || !server.system.mailbox_depths().await.is_empty() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await;
+
+        assert!(reaped.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_connection_over_max_players_rejected() {
+        let server = TestServer {
+            config: Arc::new(ServerConfig {
+                debug_mode: true,
+                max_players: 2,
+                heartbeat_interval_ms: 0,
+                ..ServerConfig::default()
+            }),
+            ..TestServer::default()
+        };
+        let route = spawn_test_server(server.clone());
+
+        let mut players = Vec::new();
+        for _ in 0..2 {
+            let client = warp::test::ws()
+                .path("/game?token=debug_full")
+                .handshake(route.clone())
+                .await
+                .unwrap();
+            players.push(client);
         }
+        let connected = tokio::time::timeout(Duration::from_secs(1), async {
+            while server.manager.count() < 2 || server.system.list_actors().await.len() < 2 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await;
+        assert!(connected.is_ok());
+
+        let mut extra = warp::test::ws()
+            .path("/game?token=debug_full")
+            .handshake(route)
+            .await
+            .unwrap();
+        let kicked = extra.recv().await.unwrap();
+        assert!(kicked.to_str().unwrap().contains("server full"));
+        assert!(extra.recv().await.map_or(true, |msg| msg.is_close()));
+
+        assert_eq!(server.manager.count(), 2);
+        assert_eq!(server.system.list_actors().await.len(), 2);
+
+        // Spectators have their own slots, so a full server still takes one.
+        let _spectator = warp::test::ws()
+            .path("/game?token=debug_watcher&spectator=true")
+            .handshake(spawn_test_server(server.clone()))
+            .await
+            .unwrap();
+        let spectating = tokio::time::timeout(Duration::from_secs(1), async {
+            while server.manager.spectators().is_empty() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await;
+        assert!(spectating.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_spectator_over_max_spectators_rejected() {
+        let server = TestServer {
+            config: Arc::new(ServerConfig {
+                debug_mode: true,
+                max_spectators: 0,
+                heartbeat_interval_ms: 0,
+                ..ServerConfig::default()
+            }),
+            ..TestServer::default()
+        };
+
+        let mut spectator = warp::test::ws()
+            .path("/game?token=debug_watcher&spectator=true")
+            .handshake(spawn_test_server(server.clone()))
+            .await
+            .unwrap();
+        let kicked = spectator.recv().await.unwrap();
+        assert!(kicked.to_str().unwrap().contains("server full"));
+        assert!(server.manager.spectators().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rapid_reconnects_replace_actor_in_order() {
+        let server = TestServer {
+            system: ActorSystem::new("test", EventBus::new(64)),
+            // Debug mode lets connections in without a session.
+            config: Arc::new(ServerConfig {
+                heartbeat_interval_ms: 0,
+                ..ServerConfig::default()
+            }),
+            ..TestServer::default()
+        };
+        let mut events = server.system.events_filtered(|event| {
+            matches!(
+                event,
+                GameEvent::PlayerJoined { .. } | GameEvent::PlayerLeft { .. }
+            )
+        });
+        let token = signed_token(&server.config, "wallet", "Nick");
+        server
+            .sessions
+            .insert("wallet".to_string(), session_for(&token, "Nick"));
+        let route = spawn_test_server(server.clone());
+
+        let reconnects: Vec<_> = (0..8)
+            .map(|_| {
+                let (route, path) = (route.clone(), format!("/game?token={}", token));
+                tokio::spawn(async move { warp::test::ws().path(&path).handshake(route).await })
+            })
+            .collect();
+        let mut clients = Vec::new();
+        for reconnect in reconnects {
+            clients.push(reconnect.await.unwrap().unwrap());
+        }
+        let settled = tokio::time::timeout(Duration::from_secs(1), async {
+            while server.system.list_actors().await != vec![player_path("wallet")]
+                || server.manager.count() != 1
+            {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await;
+        assert!(settled.is_ok());
+
+        // Each replaced actor left before the next one joined.
+        let mut joined = false;
+        while let Ok(Some(event)) =
+            tokio::time::timeout(Duration::from_millis(50), events.recv()).await
+        {
+            let joining = matches!(event, GameEvent::PlayerJoined { .. });
+            assert_ne!(joining, joined, "out of order: {:?}", event);
+            joined = joining;
+        }
+        assert!(joined);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_move_batch_rejected() {
+        let server = TestServer {
+            config: Arc::new(ServerConfig {
+                debug_mode: true,
+                heartbeat_interval_ms: 0,
+                move_rate_limit: 1,
+                ..ServerConfig::default()
+            }),
+            ..TestServer::default()
+        };
+        let mut client = connect(&server, "debug_batch").await;
+        let spawn = position_of(&send_json(&mut client, &ClientMessage::GetState).await);
+
+        let moves = (1..=MAX_MOVE_BATCH + 1)
+            .map(|i| MoveSample {
+                position: Position::new(spawn.x + i as f64 * 0.1, spawn.y, spawn.z),
+                velocity: Position::new(1.0, 0.0, 0.0),
+                delta_time: 0.1,
+                client_time_ms: None,
+            })
+            .collect();
+        let reply = send_json(&mut client, &ClientMessage::MoveBatch { moves }).await;
+        assert_eq!(reply["type"], "Error");
+        assert_eq!(
+            position_of(&send_json(&mut client, &ClientMessage::GetState).await),
+            spawn
+        );
+
+        // The rejected batch spent none of the move budget.
+        let step = Position::new(spawn.x + 1.0, spawn.y, spawn.z);
+        send_only(
+            &mut client,
+            &ClientMessage::Move {
+                position: step.clone(),
+                velocity: Position::new(10.0, 0.0, 0.0),
+                delta_time: 0.1,
+                client_time_ms: None,
+            },
+        )
+        .await;
+        let moved = position_of(&send_json(&mut client, &ClientMessage::GetState).await);
+        assert!(moved.distance_to(&step) < 1e-3);
+    }
+
+    #[tokio::test]
+    async fn test_get_state_returns_last_accepted_move() {
+        let mut client = connect(&TestServer::default(), "debug_state").await;
+
+        send_only(
+            &mut client,
+            &ClientMessage::Move {
+                position: Position::new(1.0, 0.0, 0.0),
+                velocity: Position::new(10.0, 0.0, 0.0),
+                delta_time: 0.1,
+                client_time_ms: None,
+            },
+        )
+        .await;
+        let reply = send_json(&mut client, &ClientMessage::GetState).await;
+
+        assert_eq!(reply["type"], "SelfState");
+        assert_eq!(
+            reply["state"]["position"],
+            serde_json::json!({ "x": 1.0, "y": 0.0, "z": 0.0 })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_time_sync_echoes_client_time_with_server_times() {
+        let mut client = connect(&TestServer::default(), "debug_clock").await;
+
+        let before = server_time_ms();
+        let reply = send_json(
+            &mut client,
+            &ClientMessage::TimeSync {
+                client_send_ms: 123_456,
+            },
+        )
+        .await;
+        let after = server_time_ms();
+
+        assert_eq!(reply["type"], "TimeSyncReply");
+        assert_eq!(reply["client_send_ms"], 123_456);
+        let recv = reply["server_recv_ms"].as_u64().unwrap();
+        let send = reply["server_send_ms"].as_u64().unwrap();
+        assert!(before <= recv && recv <= send && send <= after);
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_move_budget_replies_rate_limited() {
+        let server = TestServer {
+            config: Arc::new(ServerConfig {
+                debug_mode: true,
+                heartbeat_interval_ms: 0,
+                move_rate_limit: 1,
+                ..ServerConfig::default()
+            }),
+            ..TestServer::default()
+        };
+        let mut client = connect(&server, "debug_budget").await;
+
+        let step = |x: f64| ClientMessage::Move {
+            position: Position::new(x, 0.0, 0.0),
+            velocity: Position::new(10.0, 0.0, 0.0),
+            delta_time: 0.1,
+            client_time_ms: None,
+        };
+        send_only(&mut client, &step(1.0)).await;
+        let reply = send_json(&mut client, &step(2.0)).await;
+        assert_eq!(reply["type"], "Error");
+        assert_eq!(reply["message"], "rate limited");
+
+        // GetState has its own budget, and only the first move was applied.
+        let reply = send_json(&mut client, &ClientMessage::GetState).await;
+        assert_eq!(reply["type"], "SelfState");
+        assert_eq!(reply["state"]["position"]["x"], serde_json::json!(1.0));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_messages_answered_then_closed() {
+        let server = TestServer {
+            config: Arc::new(ServerConfig {
+                debug_mode: true,
+                heartbeat_interval_ms: 0,
+                max_invalid_messages: 3,
+                ..ServerConfig::default()
+            }),
+            ..TestServer::default()
+        };
+
+        let mut client = warp::test::ws()
+            .path("/game?token=debug_garbage")
+            .handshake(spawn_test_server(server.clone()))
+            .await
+            .unwrap();
+
+        for garbage in ["not json", r#"{"type":"Teleport"}"#, "{"] {
+            client.send_text(garbage).await;
+            let reply = client.recv().await.unwrap();
+            assert!(reply.to_str().unwrap().contains("invalid message format"));
+        }
+        assert!(client.recv().await.map_or(true, |msg| msg.is_close()));
+    }
+
+    #[tokio::test]
+    async fn test_banned_address_rejected_before_auth() {
+        let remote: SocketAddr = "203.0.113.7:40000".parse().unwrap();
+        let server = TestServer {
+            remote: Some(remote),
+            ..TestServer::default()
+        };
+        server.bans.ban(remote.ip(), Duration::from_secs(60));
+
+        let mut client = warp::test::ws()
+            .path("/game?token=not-a-token")
+            .handshake(spawn_test_server(server.clone()))
+            .await
+            .unwrap();
+        let kicked = client.recv().await.unwrap();
+        assert!(kicked.to_str().unwrap().contains("banned"));
+        assert!(client.recv().await.map_or(true, |msg| msg.is_close()));
+        assert_eq!(server.manager.count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_anticheat_kick_bans_the_connecting_address() {
+        let remote: SocketAddr = "203.0.113.7:40000".parse().unwrap();
+        let server = TestServer {
+            config: Arc::new(ServerConfig {
+                debug_mode: true,
+                heartbeat_interval_ms: 0,
+                auto_ban_secs: 60,
+                ..ServerConfig::default()
+            }),
+            remote: Some(remote),
+            ..TestServer::default()
+        };
+        let mut client = connect(&server, "debug_cheater").await;
+        let spawn = position_of(&send_json(&mut client, &ClientMessage::GetState).await);
+
+        let teleport = ClientMessage::Move {
+            position: Position::new(spawn.x + 400.0, spawn.y, spawn.z),
+            velocity: Position::new(10.0, 0.0, 0.0),
+            delta_time: 0.1,
+            client_time_ms: None,
+        };
+        for _ in 0..MAX_VIOLATIONS {
+            send_only(&mut client, &teleport).await;
+        }
+
+        // Corrections come back until the last violation kicks the player.
+        loop {
+            let frame = client.recv().await.unwrap();
+            if frame.to_str().is_ok_and(|text| text.contains("Kicked")) {
+                break;
+            }
+        }
+        let banned = tokio::time::timeout(Duration::from_secs(1), async {
+            while !server.bans.is_banned(&remote.ip()) {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await;
+        assert!(banned.is_ok());
+
+        let mut again = connect(&server, "debug_cheater").await;
+        let rejected = again.recv().await.unwrap();
+        assert!(rejected.to_str().unwrap().contains("banned"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_subprotocol_rejected() {
+        let server = TestServer::default();
+
+        let mut client = warp::test::ws()
+            .path("/game?token=debug_versioned")
+            .header("sec-websocket-protocol", "game-v99")
+            .handshake(spawn_test_server(server.clone()))
+            .await
+            .unwrap();
+
+        assert!(client.recv().await.map_or(true, |msg| msg.is_close()));
+        assert_eq!(server.manager.count(), 0);
+        assert!(server.system.list_actors().await.is_empty());
+    }
+
+    #[test]
+    fn test_close_reason_frames() {
+        let frames: Vec<WsMessage> = CloseReason::SessionExpired
+            .frames(WireFormat::Json)
+            .collect();
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].to_str().unwrap().contains("session expired"));
+        assert_eq!(frames[1].close_frame(), Some((4002, "session expired")));
+
+        // Clients that can't parse our messages only get the close frame.
+        let frames: Vec<WsMessage> = CloseReason::UnsupportedProtocol
+            .frames(WireFormat::Json)
+            .collect();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(
+            frames[0].close_frame(),
+            Some((1002, "unsupported subprotocol"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_session_closed_with_reason() {
+        let server = TestServer {
+            // Debug mode lets connections in without a session.
+            config: Arc::new(ServerConfig {
+                heartbeat_interval_ms: 0,
+                ..ServerConfig::default()
+            }),
+            ..TestServer::default()
+        };
+        let token = signed_token(&server.config, "p1", "Nick");
+
+        let mut client = warp::test::ws()
+            .path(&format!("/game?token={}", token))
+            .handshake(spawn_test_server(server.clone()))
+            .await
+            .unwrap();
+
+        let error = client.recv().await.unwrap();
+        assert!(error.to_str().unwrap().contains("session expired"));
+        // The test client hides close frames, so their code is checked on the frames below.
+        assert!(client.recv().await.map_or(true, |msg| msg.is_close()));
+        assert_eq!(server.manager.count(), 0);
+        assert!(server.system.list_actors().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_characters_of_one_wallet_get_distinct_actors() {
+        let server = TestServer {
+            // Debug mode lets connections in without a session.
+            config: Arc::new(ServerConfig {
+                heartbeat_interval_ms: 0,
+                ..ServerConfig::default()
+            }),
+            ..TestServer::default()
+        };
+        let mut tokens = Vec::new();
+        for player_id in ["wallet:warrior", "wallet:mage"] {
+            let nickname = player_id.replace(':', "_");
+            let token = signed_token(&server.config, player_id, &nickname);
+            server
+                .sessions
+                .insert(player_id.to_string(), session_for(&token, &nickname));
+            tokens.push(token);
+        }
+        let mut clients = Vec::new();
+        for token in &tokens {
+            clients.push(connect(&server, token).await);
+        }
+        let connected = tokio::time::timeout(Duration::from_secs(1), async {
+            while server.system.actor_count().await < 2 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await;
+        assert!(connected.is_ok());
+
+        assert_eq!(server.manager.count(), 2);
+        for player_id in ["wallet:warrior", "wallet:mage"] {
+            assert!(server.system.contains(&player_path(player_id)).await);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_second_session_displaces_first() {
+        let server = TestServer {
+            // Debug mode lets connections in without a session.
+            config: Arc::new(ServerConfig {
+                heartbeat_interval_ms: 0,
+                ..ServerConfig::default()
+            }),
+            ..TestServer::default()
+        };
+        let token = signed_token(&server.config, "wallet", "Nick");
+        server
+            .sessions
+            .insert("wallet".to_string(), session_for(&token, "Nick"));
+        let mut first = connect(&server, &token).await;
+        let connected = tokio::time::timeout(Duration::from_secs(1), async {
+            while !server.system.contains(&player_path("wallet")).await {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await;
+        assert!(connected.is_ok());
+
+        let _second = connect(&server, &token).await;
+
+        let kicked = tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                let frame = first.recv().await.unwrap();
+                if let Ok(text) = frame.to_str()
+                    && text.contains("Kicked")
+                {
+                    return text.to_string();
+                }
+            }
+        })
+        .await
+        .expect("displaced connection was not told");
+        assert!(kicked.contains("connected from another location"));
+        assert!(first.recv().await.map_or(true, |msg| msg.is_close()));
+        assert_eq!(server.manager.count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_spectator_watches_without_playing() {
+        let server = TestServer::default();
+        let (_shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let (broadcast_system, broadcast_manager, broadcast_config) = (
+            server.system.clone(),
+            server.manager.clone(),
+            server.config.clone(),
+        );
+        tokio::spawn(async move {
+            crate::network::broadcast_positions(
+                broadcast_system,
+                broadcast_manager,
+                &broadcast_config,
+                shutdown_rx,
+                crate::network::BroadcastContext::default(),
+            )
+            .await
+        });
+        let route = spawn_test_server(server.clone());
+
+        let _player = warp::test::ws()
+            .path("/game?token=debug_player")
+            .handshake(route.clone())
+            .await
+            .unwrap();
+        let mut spectator = warp::test::ws()
+            .path("/game?token=debug_watcher&spectator=true")
+            .handshake(route)
+            .await
+            .unwrap();
+
+        let players = tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                let frame = spectator.recv().await.unwrap();
+                let msg: serde_json::Value = serde_json::from_str(frame.to_str().unwrap()).unwrap();
+                if msg["type"] == "StateUpdate" && !msg["players"].as_array().unwrap().is_empty() {
+                    return msg["players"].as_array().unwrap().clone();
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(players.len(), 1);
+        assert!(
+            players[0]["player_id"]
+                .as_str()
+                .unwrap()
+                .starts_with("player_")
+        );
+        let connected = server.manager.get_connected_players();
+        assert_eq!(connected.len(), 1);
+        assert!(connected[0].starts_with("player_"));
+        assert_eq!(server.manager.spectators().len(), 1);
+        assert_eq!(server.system.list_actors().await.len(), 1);
     }
 }
@@ -0,0 +1,228 @@
+use crate::actor_system::ActorSystem;
+use crate::types::{GameEvent, Position};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use warp::{Filter, Rejection, Reply};
+
+/// Entries returned when `/leaderboard` is called without a `limit`.
+const DEFAULT_LIMIT: usize = 10;
+
+/// Most entries a single `/leaderboard` request may ask for.
+const MAX_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct LeaderboardQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LeaderboardEntry {
+    pub player_id: String,
+    /// Distance traveled, in world units.
+    pub score: f64,
+}
+
+/// Distance each player has traveled, accumulated from `PlayerJoined`, `PlayerMoved` and
+/// `PlayerLeft` events.
+#[derive(Clone, Default)]
+pub struct Leaderboard {
+    scores: Arc<DashMap<String, f64>>,
+    /// Last known position of every player in the game, which the next move is measured from.
+    positions: Arc<DashMap<String, Position>>,
+    /// Drop a player's score when they leave instead of keeping it for the server's lifetime.
+    reset_on_leave: bool,
+}
+
+impl Leaderboard {
+    pub fn new(reset_on_leave: bool) -> Self {
+        Self {
+            reset_on_leave,
+            ..Self::default()
+        }
+    }
+
+    /// Consumes the system's game events until the bus closes.
+    pub async fn run(self, system: ActorSystem<GameEvent>) {
+        let mut events = system.events_filtered(|event| {
+            matches!(
+                event,
+                GameEvent::PlayerJoined { .. }
+                    | GameEvent::PlayerMoved { .. }
+                    | GameEvent::PlayerLeft { .. }
+            )
+        });
+        while let Some(event) = events.recv().await {
+            self.record(&event);
+        }
+    }
+
+    pub fn record(&self, event: &GameEvent) {
+        match event {
+            GameEvent::PlayerJoined {
+                player_id,
+                position,
+                ..
+            } => {
+                self.positions.insert(player_id.clone(), position.clone());
+            }
+            GameEvent::PlayerMoved {
+                player_id,
+                position,
+                ..
+            } => {
+                // A move without a known starting point only sets one.
+                if let Some(previous) = self.positions.insert(player_id.clone(), position.clone()) {
+                    let distance = previous.distance_to(position);
+                    *self.scores.entry(player_id.clone()).or_insert(0.0) += distance;
+                }
+            }
+            GameEvent::PlayerLeft { player_id, .. } => {
+                self.positions.remove(player_id);
+                if self.reset_on_leave {
+                    self.scores.remove(player_id);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The `limit` highest scores, best first. Ties are broken by player id.
+    pub fn top(&self, limit: usize) -> Vec<LeaderboardEntry> {
+        let mut entries: Vec<LeaderboardEntry> = self
+            .scores
+            .iter()
+            .map(|entry| LeaderboardEntry {
+                player_id: entry.key().clone(),
+                score: *entry.value(),
+            })
+            .collect();
+        entries.sort_unstable_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.player_id.cmp(&b.player_id))
+        });
+        entries.truncate(limit);
+        entries
+    }
+}
+
+/// `GET /leaderboard?limit=N`: the top `N` players (default 10, at most 100).
+pub fn route(
+    leaderboard: Leaderboard,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::path("leaderboard")
+        .and(warp::get())
+        .and(warp::query::<LeaderboardQuery>())
+        .map(move |query: LeaderboardQuery| {
+            let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+            warp::reply::json(&serde_json::json!({
+                "leaderboard": leaderboard.top(limit)
+            }))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DEFAULT_ROOM;
+
+    fn joined(player_id: &str) -> GameEvent {
+        GameEvent::PlayerJoined {
+            player_id: player_id.to_string(),
+            wallet: player_id.to_string(),
+            nickname: player_id.to_string(),
+            room: DEFAULT_ROOM.to_string(),
+            position: Position::default(),
+        }
+    }
+
+    fn moved(player_id: &str, x: f64) -> GameEvent {
+        GameEvent::PlayerMoved {
+            player_id: player_id.to_string(),
+            room: DEFAULT_ROOM.to_string(),
+            position: Position::new(x, 0.0, 0.0),
+            velocity: Position::default(),
+            latency_ms: 0,
+        }
+    }
+
+    fn left(player_id: &str) -> GameEvent {
+        GameEvent::PlayerLeft {
+            player_id: player_id.to_string(),
+            room: DEFAULT_ROOM.to_string(),
+        }
+    }
+
+    fn ranking(leaderboard: &Leaderboard, limit: usize) -> Vec<(String, f64)> {
+        leaderboard
+            .top(limit)
+            .into_iter()
+            .map(|entry| (entry.player_id, entry.score))
+            .collect()
+    }
+
+    #[test]
+    fn test_ranks_by_distance_traveled() {
+        let leaderboard = Leaderboard::new(false);
+        for player in ["a", "b", "c"] {
+            leaderboard.record(&joined(player));
+        }
+
+        // a: 0 -> 5 -> 2 is 8 units; b: 0 -> 10 is 10; c: 0 -> 1 is 1.
+        for event in [
+            moved("a", 5.0),
+            moved("b", 10.0),
+            moved("a", 2.0),
+            moved("c", 1.0),
+        ] {
+            leaderboard.record(&event);
+        }
+
+        assert_eq!(
+            ranking(&leaderboard, 10),
+            vec![
+                ("b".to_string(), 10.0),
+                ("a".to_string(), 8.0),
+                ("c".to_string(), 1.0)
+            ]
+        );
+        assert_eq!(ranking(&leaderboard, 1), vec![("b".to_string(), 10.0)]);
+    }
+
+    #[test]
+    fn test_leaving_keeps_or_resets_score() {
+        for (reset_on_leave, expected) in [(false, 1), (true, 0)] {
+            let leaderboard = Leaderboard::new(reset_on_leave);
+            leaderboard.record(&joined("a"));
+            leaderboard.record(&moved("a", 3.0));
+            leaderboard.record(&left("a"));
+
+            assert_eq!(leaderboard.top(10).len(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_applies_limit() {
+        let leaderboard = Leaderboard::new(false);
+        for (player, x) in [("a", 1.0), ("b", 2.0), ("c", 3.0)] {
+            leaderboard.record(&joined(player));
+            leaderboard.record(&moved(player, x));
+        }
+
+        let response = warp::test::request()
+            .method("GET")
+            .path("/leaderboard?limit=2")
+            .reply(&route(leaderboard))
+            .await;
+
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(
+            body["leaderboard"],
+            serde_json::json!([
+                { "player_id": "c", "score": 3.0 },
+                { "player_id": "b", "score": 2.0 }
+            ])
+        );
+    }
+}
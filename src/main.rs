@@ -2,118 +2,556 @@ mod actor_system;
 mod anticheat;
 mod config;
 mod handlers;
+mod leaderboard;
+mod metrics;
 mod network;
 mod player;
+mod profanity;
+mod recorder;
+mod replay;
+mod rng;
 mod types;
 
-use actor_system::{ActorSystem, EventBus};
+use actor_system::{ActorPath, ActorSystem, EventBus, SystemLifecycleEvent};
+use anticheat::AntiCheatConfig;
 use config::ServerConfig;
 use dashmap::DashMap;
-use handlers::{SolanaVerifier, handle_auth};
-use network::{ConnectionManager, broadcast_positions};
-use std::collections::HashMap;
+use handlers::{
+    AuthContext, ConnectionContext, SolanaVerifier, handle_admin_announce, handle_admin_anticheat,
+    handle_admin_audit, handle_admin_ban, handle_admin_kick, handle_admin_unban, handle_admin_warp,
+    handle_api_state, handle_auth, handle_challenge, handle_healthz, handle_readyz, handle_refresh,
+    handle_rejection, prune_auth_state, request_id, reverify_sessions,
+};
+use leaderboard::Leaderboard;
+use metrics::Metrics;
+use network::{
+    BanList, BroadcastContext, ConnectionManager, OverloadBreaker, ProtocolVersion, Simulation,
+    broadcast_positions, encode_server_message,
+};
+use player::{JsonFileStore, PlayerStore};
+use profanity::ProfanityFilter;
+use recorder::EventRecorder;
+use rng::SharedRng;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use types::{AuthRequest, GameEvent, SessionInfo};
+use tokio::sync::watch;
+use tokio::time::{Duration, Instant};
+use types::{
+    AnnounceRequest, AntiCheatRequest, AuthRequest, BanRequest, ChallengeQuery, GameEvent,
+    KickRequest, RefreshRequest, ServerMessage, SessionInfo, UnbanRequest, WarpRequest,
+};
 use warp::Filter;
 
+/// How long to wait for clients to acknowledge the close frame during shutdown.
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often expired challenges, auth failure counts, login history and sessions are dropped.
+const AUTH_PRUNE_INTERVAL: Duration = Duration::from_secs(30);
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+
+    log::info!("Shutdown signal received");
+}
+
 #[tokio::main]
 async fn main() {
     dotenvy::dotenv().ok();
     env_logger::init();
 
-    let config = ServerConfig::from_env();
+    let config = match ServerConfig::load() {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            log::error!("Refusing to start: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     if config.debug_mode {
         log::warn!("DEBUG MODE ENABLED - Wallet verification disabled!");
+        if config.has_weak_jwt_secret() {
+            log::warn!("Weak or default JWT_SECRET in use; tokens can be forged");
+        }
     }
 
     let verifier = Arc::new(
-        SolanaVerifier::new(&config.rpc_url, &config.token_mint, config.debug_mode)
-            .expect("Failed to initialize Solana verifier"),
+        SolanaVerifier::new(
+            &config.rpc_url,
+            &config.token_mint,
+            config.min_token_balance,
+            config.debug_mode,
+        )
+        .expect("Failed to initialize Solana verifier"),
     );
 
-    let bus = EventBus::<GameEvent>::new(1000);
-    let system = ActorSystem::new("game", bus);
+    let bus = EventBus::<GameEvent>::new(config.event_bus_capacity)
+        .with_overflow(config.event_bus_overflow);
+    let system = ActorSystem::new("game", bus).with_slow_handler_threshold(
+        (config.slow_handler_threshold_ms > 0)
+            .then(|| Duration::from_millis(config.slow_handler_threshold_ms)),
+    );
     let sessions: Arc<DashMap<String, SessionInfo>> = Arc::new(DashMap::new());
-    let connection_manager = ConnectionManager::new();
+    let auth_attempts: Arc<DashMap<String, (u32, std::time::Instant)>> = Arc::new(DashMap::new());
+    let challenges: Arc<DashMap<String, (String, std::time::Instant)>> = Arc::new(DashMap::new());
+    let connection_manager = ConnectionManager::new().with_backpressure(
+        config.max_send_queue,
+        Duration::from_millis(config.slow_consumer_timeout_ms),
+    );
+    let metrics = Arc::new(Metrics::new());
+
+    if config.debug_mode {
+        let mut dead_letters = system.dead_letters();
+        tokio::spawn(async move {
+            while let Ok(dead_letter) = dead_letters.recv().await {
+                log::debug!(
+                    "Dead letter: {} -> {}",
+                    dead_letter.message_type,
+                    dead_letter.path
+                );
+            }
+        });
+
+        let mut lifecycle = system.lifecycle_events();
+        tokio::spawn(async move {
+            while let Ok(event) = lifecycle.recv().await {
+                match event {
+                    SystemLifecycleEvent::Started { path } => {
+                        log::debug!("Actor started: {}", path)
+                    }
+                    SystemLifecycleEvent::Stopped { path } => {
+                        log::debug!("Actor stopped: {}", path)
+                    }
+                    SystemLifecycleEvent::Restarted { path, retries } => {
+                        log::debug!("Actor restarted: {} (restart {})", path, retries)
+                    }
+                }
+            }
+        });
+    }
+
+    let mut players_left =
+        system.events_filtered(|event| matches!(event, GameEvent::PlayerLeft { .. }));
+    let left_metrics = metrics.clone();
+    tokio::spawn(async move {
+        while players_left.recv().await.is_some() {
+            left_metrics.record_player_left();
+        }
+    });
+
+    let (recorder_stop_tx, recorder_stop_rx) = watch::channel(false);
+    let recorder_handle = config.record_events_path.as_ref().map(|path| {
+        let recorder = EventRecorder::create(path).expect("Failed to create event recording");
+        log::info!("Recording game events to {}", path.display());
+        tokio::spawn(recorder.run(system.clone(), recorder_stop_rx))
+    });
+
+    let store: Option<Arc<dyn PlayerStore>> = config.player_store_path.as_ref().map(|path| {
+        let store = JsonFileStore::open(path).expect("Failed to open player store");
+        log::info!("Persisting player state to {}", path);
+        Arc::new(store) as Arc<dyn PlayerStore>
+    });
+
+    if let Some(store) = store.clone()
+        && config.store_flush_interval_secs > 0
+    {
+        system.schedule(
+            Duration::from_secs(config.store_flush_interval_secs),
+            move || {
+                let store = store.clone();
+                tokio::task::spawn_blocking(move || {
+                    if let Err(e) = store.flush() {
+                        log::error!("Failed to flush player store: {}", e);
+                    }
+                });
+            },
+        );
+    }
+
+    let profanity = Arc::new(
+        ProfanityFilter::from_config(config.nickname_blocklist_path.as_deref())
+            .expect("Failed to read nickname word list"),
+    );
+
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let shutdown_system = system.clone();
+    let shutdown_manager = connection_manager.clone();
+    let shutdown_store = store.clone();
 
     let broadcast_system = system.clone();
     let broadcast_manager = connection_manager.clone();
     let broadcast_config = config.clone();
-    tokio::spawn(async move {
-        broadcast_positions(broadcast_system, broadcast_manager, &broadcast_config).await;
+    let broadcast_metrics = metrics.clone();
+    let overload = Arc::new(OverloadBreaker::from_config(&config));
+    let broadcast_overload = overload.clone();
+    let player_states = Arc::new(DashMap::new());
+    let broadcast_states = player_states.clone();
+    let broadcast_filter =
+        (config.censor_nicknames && profanity.is_enabled()).then(|| profanity.clone());
+
+    // Replayed before the live simulation starts, so it picks up from the recording's end.
+    if let Some(path) = &config.replay_path {
+        let events = replay::load(path).expect("Failed to read replay file");
+        log::warn!("Replaying {} into the world", path.display());
+        let mut simulation = Simulation::new(
+            connection_manager.clone(),
+            config.world_bounds.clone(),
+            config.wire_format,
+            metrics.clone(),
+        )
+        .with_states(player_states.clone());
+        if let Some(filter) = broadcast_filter.clone() {
+            simulation = simulation.with_nickname_filter(filter);
+        }
+        replay::replay(&simulation, events, config.sim_hz);
+    }
+
+    let broadcast_handle = tokio::spawn(async move {
+        broadcast_positions(
+            broadcast_system,
+            broadcast_manager,
+            &broadcast_config,
+            shutdown_rx,
+            BroadcastContext {
+                metrics: broadcast_metrics,
+                overload: broadcast_overload,
+                player_states: broadcast_states,
+                nickname_filter: broadcast_filter,
+            },
+        )
+        .await;
     });
 
+    let leaderboard = Leaderboard::new(config.leaderboard_reset_on_leave);
+    tokio::spawn(leaderboard.clone().run(system.clone()));
+    let leaderboard_route = leaderboard::route(leaderboard);
+
+    let metrics_route = metrics::route(metrics.clone(), connection_manager.clone(), system.clone());
+
+    let auth_context = AuthContext {
+        verifier: verifier.clone(),
+        sessions: sessions.clone(),
+        auth_attempts,
+        challenges: challenges.clone(),
+        nicknames: Arc::new(DashMap::new()),
+        logins: Arc::new(DashMap::new()),
+        config: config.clone(),
+        profanity,
+        overload,
+        metrics: metrics.clone(),
+    };
+    tokio::spawn(reverify_sessions(
+        auth_context.clone(),
+        system.clone(),
+        connection_manager.clone(),
+    ));
+    let prune_context = auth_context.clone();
+    system.schedule(AUTH_PRUNE_INTERVAL, move || {
+        prune_auth_state(&prune_context)
+    });
+    let auth_context_filter = warp::any().map(move || auth_context.clone());
+    let bans = BanList::new();
+    let anticheat = AntiCheatConfig::shared(&config);
+    let connection_context = ConnectionContext {
+        system: system.clone(),
+        sessions: sessions.clone(),
+        config: config.clone(),
+        connection_manager: connection_manager.clone(),
+        store,
+        metrics,
+        bans: bans.clone(),
+        anticheat: anticheat.clone(),
+        rng: SharedRng::from_config(&config),
+    };
+    let connection_context_filter = warp::any().map(move || connection_context.clone());
     let verifier_filter = warp::any().map(move || verifier.clone());
     let sessions_filter = warp::any().map(move || sessions.clone());
-    let system_filter = warp::any().map(move || system.clone());
-    let jwt_secret = config.jwt_secret.clone();
-    let jwt_secret_filter = warp::any().map(move || jwt_secret.clone());
-    let debug_mode = config.debug_mode;
-    let debug_mode_filter = warp::any().map(move || debug_mode);
-    let connection_manager_game = connection_manager.clone();
+    let challenges_filter = warp::any().map(move || challenges.clone());
+    let debug_system = system.clone();
+    let admin_system = system.clone();
+    let debug_system_filter = warp::any().map(move || debug_system.clone());
+    let admin_system_filter = warp::any().map(move || admin_system.clone());
+    let game_config = config.clone();
+    let config_filter = warp::any().map(move || game_config.clone());
+    let bans_filter = warp::any().map(move || bans.clone());
+    let anticheat_filter = warp::any().map(move || anticheat.clone());
+    let player_states_filter = warp::any().map(move || player_states.clone());
     let connection_manager_debug = connection_manager.clone();
-    let connection_manager_filter = warp::any().map(move || connection_manager_game.clone());
+    let connection_manager_admin = connection_manager.clone();
     let debug_manager_filter = warp::any().map(move || connection_manager_debug.clone());
+    let admin_manager_filter = warp::any().map(move || connection_manager_admin.clone());
+
+    let healthz_route = warp::path("healthz")
+        .and(warp::get())
+        .and_then(handle_healthz);
+
+    let readyz_route = warp::path("readyz")
+        .and(warp::get())
+        .and(verifier_filter.clone())
+        .and_then(handle_readyz);
+
+    // Challenge route
+    let challenge_route = warp::path("challenge")
+        .and(warp::get())
+        .and(warp::query::<ChallengeQuery>())
+        .and(challenges_filter.clone())
+        .and(config_filter.clone())
+        .and_then(handle_challenge);
 
     // Auth route
     let auth_route = warp::path("auth")
         .and(warp::post())
         .and(warp::body::json::<AuthRequest>())
-        .and(verifier_filter.clone())
-        .and(sessions_filter.clone())
-        .and(jwt_secret_filter.clone())
+        .and(auth_context_filter)
+        .and(warp::addr::remote())
+        .and(request_id())
         .and_then(handle_auth);
 
+    // Token refresh route
+    let refresh_route = warp::path("refresh")
+        .and(warp::post())
+        .and(warp::body::json::<RefreshRequest>())
+        .and(sessions_filter.clone())
+        .and(config_filter.clone())
+        .and_then(handle_refresh);
+
+    // Admin kick route
+    let admin_kick_route = warp::path!("admin" / "kick")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("x-admin-token"))
+        .and(warp::body::json::<KickRequest>())
+        .and(admin_system_filter.clone())
+        .and(admin_manager_filter)
+        .and(config_filter.clone())
+        .and_then(handle_admin_kick);
+
+    // Admin movement audit route
+    let admin_audit_route = warp::path!("admin" / "audit" / String)
+        .and(warp::get())
+        .and(warp::header::optional::<String>("x-admin-token"))
+        .and(admin_system_filter.clone())
+        .and(config_filter.clone())
+        .and_then(handle_admin_audit);
+
+    // Admin announcement route
+    let admin_announce_route = warp::path!("admin" / "announce")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("x-admin-token"))
+        .and(warp::body::json::<AnnounceRequest>())
+        .and(admin_system_filter.clone())
+        .and(config_filter.clone())
+        .and_then(handle_admin_announce);
+
+    // Admin warp route
+    let admin_warp_route = warp::path!("admin" / "warp")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("x-admin-token"))
+        .and(warp::body::json::<WarpRequest>())
+        .and(admin_system_filter)
+        .and(config_filter.clone())
+        .and_then(handle_admin_warp);
+
+    // Admin ban routes
+    let admin_ban_route = warp::path!("admin" / "ban")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("x-admin-token"))
+        .and(warp::body::json::<BanRequest>())
+        .and(bans_filter.clone())
+        .and(config_filter.clone())
+        .and_then(handle_admin_ban);
+
+    let admin_unban_route = warp::path!("admin" / "unban")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("x-admin-token"))
+        .and(warp::body::json::<UnbanRequest>())
+        .and(bans_filter.clone())
+        .and(config_filter.clone())
+        .and_then(handle_admin_unban);
+
+    // Admin anti-cheat tuning route
+    let admin_anticheat_route = warp::path!("admin" / "anticheat")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("x-admin-token"))
+        .and(warp::body::json::<AntiCheatRequest>())
+        .and(anticheat_filter.clone())
+        .and(config_filter.clone())
+        .and_then(handle_admin_anticheat);
+
+    // World snapshot route for external services
+    let api_state_route = warp::path!("api" / "state")
+        .and(warp::get())
+        .and(warp::header::optional::<String>("x-admin-token"))
+        .and(player_states_filter)
+        .and(config_filter.clone())
+        .and_then(handle_api_state);
+
     // Game WebSocket route
     let game_route = warp::path("game")
         .and(warp::query::<HashMap<String, String>>())
-        .and(system_filter)
-        .and(sessions_filter)
-        .and(jwt_secret_filter)
-        .and(debug_mode_filter)
-        .and(connection_manager_filter)
+        .and(connection_context_filter)
+        .and(warp::addr::remote())
+        .and(warp::header::optional::<String>("sec-websocket-protocol"))
+        .and(request_id())
         .and(warp::ws())
         .map(
             |params: HashMap<String, String>,
-             system: ActorSystem<GameEvent>,
-             sessions: Arc<DashMap<String, SessionInfo>>,
-             jwt_secret: String,
-             debug_mode: bool,
-             connection_manager: ConnectionManager,
+             context: ConnectionContext,
+             remote: Option<SocketAddr>,
+             requested_protocol: Option<String>,
+             request_id: String,
              ws: warp::ws::Ws| {
                 let token = params.get("token").cloned().unwrap_or_default();
-                ws.on_upgrade(move |websocket| {
-                    handlers::handle_connection(
-                        token,
-                        system,
-                        sessions,
-                        jwt_secret,
-                        websocket,
-                        debug_mode,
-                        connection_manager,
-                    )
-                })
+                let spectator = params.get("spectator").is_some_and(|value| value == "true");
+                let protocol = ProtocolVersion::negotiate(requested_protocol.as_deref());
+                let upgrade = ws.on_upgrade(move |websocket| async move {
+                    if spectator {
+                        handlers::handle_spectator(token, websocket, context, remote, protocol)
+                            .await
+                    } else {
+                        handlers::handle_connection(
+                            token, websocket, context, remote, protocol, request_id,
+                        )
+                        .await
+                    }
+                });
+
+                // Only echo a subprotocol the client actually asked for.
+                match protocol.filter(|_| requested_protocol.is_some()) {
+                    Some(protocol) => Box::new(warp::reply::with_header(
+                        upgrade,
+                        "sec-websocket-protocol",
+                        protocol.name(),
+                    )) as Box<dyn warp::Reply>,
+                    None => Box::new(upgrade),
+                }
             },
         );
 
     // Debug route
     let debug_route = warp::path("debug")
         .and(warp::path("players"))
-        .and(debug_manager_filter)
+        .and(debug_manager_filter.clone())
         .map(|connection_manager: ConnectionManager| {
             let players = connection_manager.get_connected_players();
             warp::reply::json(&serde_json::json!({
                 "connected_players": players,
-                "count": players.len()
+                "count": players.len(),
+                "spectators": connection_manager.spectators().len()
             }))
         });
 
-    let routes = auth_route
+    let debug_actors_route = warp::path("debug")
+        .and(warp::path("actors"))
+        .and(debug_system_filter)
+        .and(debug_manager_filter)
+        .and_then(
+            |system: ActorSystem<GameEvent>, connection_manager: ConnectionManager| async move {
+                let connected: HashSet<String> = connection_manager
+                    .get_connected_players()
+                    .into_iter()
+                    .collect();
+                let depths: HashMap<ActorPath, usize> =
+                    system.mailbox_depths().await.into_iter().collect();
+
+                // Player actors without a live socket are either within their reconnect
+                // grace period or leaked.
+                let actors: Vec<serde_json::Value> = system
+                    .list_actors()
+                    .await
+                    .into_iter()
+                    .map(|path| {
+                        let path_str = path.to_string();
+                        let detached = path_str
+                            .strip_prefix("/user/player-")
+                            .is_some_and(|player_id| !connected.contains(player_id));
+                        serde_json::json!({
+                            "path": path_str,
+                            "pending_messages": depths.get(&path).copied().unwrap_or(0),
+                            "detached": detached
+                        })
+                    })
+                    .collect();
+                Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({
+                    "count": actors.len(),
+                    "actors": actors
+                })))
+            },
+        );
+
+    let routes = healthz_route
+        .or(readyz_route)
+        .or(challenge_route)
+        .or(auth_route)
+        .or(refresh_route)
+        .or(admin_kick_route)
+        .or(admin_audit_route)
+        .or(admin_announce_route)
+        .or(admin_warp_route)
+        .or(admin_ban_route)
+        .or(admin_unban_route)
+        .or(admin_anticheat_route)
+        .or(api_state_route)
         .or(game_route)
         .or(debug_route)
+        .or(debug_actors_route)
+        .or(metrics_route)
+        .or(leaderboard_route)
+        .recover(handle_rejection)
         .with(warp::log("game-server"));
 
     log::info!("Game server starting on port {}", config.port);
-    warp::serve(routes).run(([0, 0, 0, 0], config.port)).await;
+    let (_, server) = warp::serve(routes)
+        .bind_with_graceful_shutdown(([0, 0, 0, 0], config.port), shutdown_signal());
+    server.await;
+
+    log::info!("Shutting down game server...");
+    let _ = shutdown_tx.send(true);
+    let _ = broadcast_handle.await;
+
+    let farewell = ServerMessage::Kicked {
+        reason: "server shutting down".to_string(),
+    };
+    if let Some(frame) = encode_server_message(&farewell, config.wire_format) {
+        shutdown_manager.disconnect_all(frame);
+    }
+
+    let deadline = Instant::now() + SHUTDOWN_DRAIN_TIMEOUT;
+    while shutdown_manager.count() > 0 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    shutdown_system.stop_all().await;
+    // Every actor has saved its state by now; write it out off the runtime threads.
+    if let Some(store) = shutdown_store {
+        match tokio::task::spawn_blocking(move || store.flush()).await {
+            Ok(Err(e)) => log::error!("Failed to flush player store: {}", e),
+            Err(e) => log::error!("Player store flush panicked: {}", e),
+            Ok(Ok(())) => {}
+        }
+    }
+    if let Some(handle) = recorder_handle {
+        let _ = recorder_stop_tx.send(true);
+        let _ = handle.await;
+    }
+    log::info!("Game server stopped");
 }
@@ -0,0 +1,337 @@
+use crate::actor_system::{ActorPath, ActorSystem, EventBusStats};
+use crate::anticheat::ValidationResult;
+use crate::network::ConnectionManager;
+use crate::types::GameEvent;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use warp::{Filter, Rejection, Reply};
+
+/// Process-wide counters and gauges, rendered in Prometheus text format on `/metrics`.
+#[derive(Default)]
+pub struct Metrics {
+    auth_successes: AtomicU64,
+    auth_failures: AtomicU64,
+    speed_hacks: AtomicU64,
+    teleports: AtomicU64,
+    out_of_bounds: AtomicU64,
+    velocity_mismatches: AtomicU64,
+    impossible_accelerations: AtomicU64,
+    invalid_moves: AtomicU64,
+    messages_processed: AtomicU64,
+    players_left: AtomicU64,
+    broadcast_ticks: AtomicU64,
+    /// Game events the broadcast loop skipped after falling behind the event bus.
+    events_lagged: AtomicU64,
+    /// `f64` bits of the tick rate measured over the last stats window.
+    broadcast_tick_rate: AtomicU64,
+    /// 1 while the overload breaker refuses new logins.
+    overloaded: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_auth(&self, success: bool) {
+        let counter = if success {
+            &self.auth_successes
+        } else {
+            &self.auth_failures
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a failed movement check. `Valid` results are ignored.
+    pub fn record_violation(&self, result: &ValidationResult) {
+        let counter = match result {
+            ValidationResult::Valid => return,
+            ValidationResult::SpeedHack => &self.speed_hacks,
+            ValidationResult::Teleport => &self.teleports,
+            ValidationResult::OutOfBounds => &self.out_of_bounds,
+            ValidationResult::VelocityMismatch => &self.velocity_mismatches,
+            ValidationResult::ImpossibleAcceleration => &self.impossible_accelerations,
+            ValidationResult::Invalid => &self.invalid_moves,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_message(&self) {
+        self.messages_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_player_left(&self) {
+        self.players_left.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_tick(&self) {
+        self.broadcast_ticks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_events_lagged(&self, skipped: u64) {
+        self.events_lagged.fetch_add(skipped, Ordering::Relaxed);
+    }
+
+    pub fn set_tick_rate(&self, ticks_per_second: f64) {
+        self.broadcast_tick_rate
+            .store(ticks_per_second.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn set_overloaded(&self, overloaded: bool) {
+        self.overloaded
+            .store(u64::from(overloaded), Ordering::Relaxed);
+    }
+
+    pub fn render(
+        &self,
+        connected_players: usize,
+        actor_processing: &[(ActorPath, Duration)],
+        actor_restarts: &[(String, u64)],
+        event_bus: EventBusStats,
+    ) -> String {
+        let mut out = String::new();
+
+        write_metric(
+            &mut out,
+            "connected_players",
+            "gauge",
+            "Players with an open websocket.",
+            &[("", connected_players.to_string())],
+        );
+        write_metric(
+            &mut out,
+            "auth_attempts_total",
+            "counter",
+            "Authentication attempts by outcome.",
+            &[
+                ("result=\"success\"", load(&self.auth_successes).to_string()),
+                ("result=\"failure\"", load(&self.auth_failures).to_string()),
+            ],
+        );
+        write_metric(
+            &mut out,
+            "anticheat_violations_total",
+            "counter",
+            "Rejected movements by violation type.",
+            &[
+                ("type=\"speed_hack\"", load(&self.speed_hacks).to_string()),
+                ("type=\"teleport\"", load(&self.teleports).to_string()),
+                (
+                    "type=\"out_of_bounds\"",
+                    load(&self.out_of_bounds).to_string(),
+                ),
+                (
+                    "type=\"velocity_mismatch\"",
+                    load(&self.velocity_mismatches).to_string(),
+                ),
+                (
+                    "type=\"impossible_acceleration\"",
+                    load(&self.impossible_accelerations).to_string(),
+                ),
+                ("type=\"invalid\"", load(&self.invalid_moves).to_string()),
+            ],
+        );
+        write_metric(
+            &mut out,
+            "messages_processed_total",
+            "counter",
+            "Client messages decoded and dispatched.",
+            &[("", load(&self.messages_processed).to_string())],
+        );
+        write_metric(
+            &mut out,
+            "players_left_total",
+            "counter",
+            "Player actors that have stopped.",
+            &[("", load(&self.players_left).to_string())],
+        );
+        write_metric(
+            &mut out,
+            "broadcast_ticks_total",
+            "counter",
+            "Broadcast loop ticks.",
+            &[("", load(&self.broadcast_ticks).to_string())],
+        );
+        write_metric(
+            &mut out,
+            "events_lagged_total",
+            "counter",
+            "Game events the broadcast loop skipped after lagging behind the event bus.",
+            &[("", load(&self.events_lagged).to_string())],
+        );
+        write_metric(
+            &mut out,
+            "broadcast_tick_rate",
+            "gauge",
+            "Broadcast ticks per second over the last stats window.",
+            &[(
+                "",
+                f64::from_bits(load(&self.broadcast_tick_rate)).to_string(),
+            )],
+        );
+        write_metric(
+            &mut out,
+            "server_overloaded",
+            "gauge",
+            "1 while new logins are refused for too many connections.",
+            &[("", load(&self.overloaded).to_string())],
+        );
+        write_metric(
+            &mut out,
+            "event_bus_subscribers",
+            "gauge",
+            "Live subscribers of the game event bus.",
+            &[("", event_bus.subscribers.to_string())],
+        );
+        write_metric(
+            &mut out,
+            "event_bus_queued",
+            "gauge",
+            "Game events the slowest subscriber has yet to read.",
+            &[("", event_bus.queued.to_string())],
+        );
+        write_metric(
+            &mut out,
+            "event_bus_capacity",
+            "gauge",
+            "Unread game events the bus holds before its overflow policy applies.",
+            &[("", event_bus.capacity.to_string())],
+        );
+        write_metric(
+            &mut out,
+            "event_bus_dropped_total",
+            "counter",
+            "Game events overwritten before every subscriber had read them.",
+            &[("", event_bus.dropped.to_string())],
+        );
+        write_metric(
+            &mut out,
+            "event_bus_rejected_total",
+            "counter",
+            "Game events refused because the bus was full.",
+            &[("", event_bus.rejected.to_string())],
+        );
+
+        let actor_labels: Vec<(String, String)> = actor_processing
+            .iter()
+            .map(|(path, total)| {
+                (
+                    format!("path=\"{}\"", path),
+                    total.as_secs_f64().to_string(),
+                )
+            })
+            .collect();
+        let actor_samples: Vec<(&str, String)> = actor_labels
+            .iter()
+            .map(|(labels, value)| (labels.as_str(), value.clone()))
+            .collect();
+        write_metric(
+            &mut out,
+            "actor_processing_seconds_total",
+            "counter",
+            "Time spent in message handlers per actor.",
+            &actor_samples,
+        );
+
+        let restart_labels: Vec<(String, String)> = actor_restarts
+            .iter()
+            .map(|(key, count)| (format!("key=\"{}\"", key), count.to_string()))
+            .collect();
+        let restart_samples: Vec<(&str, String)> = restart_labels
+            .iter()
+            .map(|(labels, value)| (labels.as_str(), value.clone()))
+            .collect();
+        write_metric(
+            &mut out,
+            "actor_restarts_total",
+            "counter",
+            "Supervised restarts attempted per actor path key.",
+            &restart_samples,
+        );
+
+        out
+    }
+}
+
+fn load(counter: &AtomicU64) -> u64 {
+    counter.load(Ordering::Relaxed)
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(&str, String)]) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (labels, value) in samples {
+        if labels.is_empty() {
+            let _ = writeln!(out, "{} {}", name, value);
+        } else {
+            let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+        }
+    }
+}
+
+/// `GET /metrics` in Prometheus text exposition format.
+pub fn route(
+    metrics: Arc<Metrics>,
+    connection_manager: ConnectionManager,
+    system: ActorSystem<GameEvent>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::path("metrics").and(warp::get()).and_then(move || {
+        let metrics = metrics.clone();
+        let connection_manager = connection_manager.clone();
+        let system = system.clone();
+        async move {
+            let actor_processing = system.processing_times().await;
+            Ok::<_, Rejection>(warp::reply::with_header(
+                metrics.render(
+                    connection_manager.count(),
+                    &actor_processing,
+                    &system.restart_counts(),
+                    system.event_bus_stats(),
+                ),
+                "content-type",
+                "text/plain; version=0.0.4",
+            ))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor_system::EventBus;
+    use crate::network::outbound_channel;
+
+    #[tokio::test]
+    async fn test_metrics_route_reports_connected_players() {
+        let metrics = Arc::new(Metrics::new());
+        let manager = ConnectionManager::new();
+        let (tx, _rx) = outbound_channel(1024);
+        manager.add("p1".to_string(), tx);
+        metrics.record_auth(true);
+        metrics.record_violation(&ValidationResult::Teleport);
+        metrics.record_violation(&ValidationResult::Valid);
+
+        let response = warp::test::request()
+            .method("GET")
+            .path("/metrics")
+            .reply(&route(
+                metrics,
+                manager,
+                ActorSystem::new("test", EventBus::new(16)),
+            ))
+            .await;
+
+        assert_eq!(response.status(), 200);
+        let body = std::str::from_utf8(response.body()).unwrap();
+        assert!(body.lines().any(|line| line == "connected_players 1"));
+        assert!(body.contains("auth_attempts_total{result=\"success\"} 1"));
+        assert!(body.contains("anticheat_violations_total{type=\"teleport\"} 1"));
+        assert!(body.contains("anticheat_violations_total{type=\"speed_hack\"} 0"));
+        assert!(body.contains("# TYPE actor_processing_seconds_total counter"));
+        assert!(body.contains("# TYPE actor_restarts_total counter"));
+        assert!(body.lines().any(|line| line == "event_bus_capacity 16"));
+        assert!(body.lines().any(|line| line == "event_bus_dropped_total 0"));
+    }
+}
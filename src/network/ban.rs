@@ -0,0 +1,69 @@
+use dashmap::DashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Peer addresses refused at connection time, each until its expiry.
+#[derive(Clone, Default)]
+pub struct BanList {
+    bans: Arc<DashMap<IpAddr, Instant>>,
+}
+
+impl BanList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bans `ip` for `duration`, replacing any existing ban.
+    pub fn ban(&self, ip: IpAddr, duration: Duration) {
+        self.bans.insert(ip, Instant::now() + duration);
+    }
+
+    /// Lifts a ban. Returns false if `ip` wasn't banned.
+    pub fn unban(&self, ip: &IpAddr) -> bool {
+        self.bans.remove(ip).is_some()
+    }
+
+    /// Whether `ip` is currently banned. Expired bans are dropped on the way.
+    pub fn is_banned(&self, ip: &IpAddr) -> bool {
+        let now = Instant::now();
+        if self.bans.get(ip).is_some_and(|expiry| *expiry > now) {
+            return true;
+        }
+        self.bans.remove_if(ip, |_, expiry| *expiry <= now);
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const IP: IpAddr = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+
+    #[test]
+    fn test_ban_and_unban() {
+        let bans = BanList::new();
+        assert!(!bans.is_banned(&IP));
+
+        bans.ban(IP, Duration::from_secs(60));
+        assert!(bans.is_banned(&IP));
+        assert!(!bans.is_banned(&IpAddr::V4(Ipv4Addr::LOCALHOST)));
+
+        assert!(bans.unban(&IP));
+        assert!(!bans.is_banned(&IP));
+        assert!(!bans.unban(&IP));
+    }
+
+    #[test]
+    fn test_ban_expires() {
+        let bans = BanList::new();
+        bans.ban(IP, Duration::from_millis(20));
+        assert!(bans.is_banned(&IP));
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(!bans.is_banned(&IP));
+        assert!(bans.bans.is_empty());
+    }
+}
@@ -1,122 +1,332 @@
 use crate::actor_system::ActorSystem;
 use crate::config::ServerConfig;
-use crate::network::ConnectionManager;
-use crate::types::{GameEvent, PlayerState, Position, ServerMessage};
+use crate::metrics::Metrics;
+use crate::network::delta::{Snapshot, diff_states};
+use crate::network::simulation::Simulation;
+use crate::network::spatial::{SpatialGrid, visible_states};
+use crate::network::{
+    ConnectionManager, OverloadBreaker, encode_compressed_server_message, encode_server_message,
+};
+use crate::profanity::ProfanityFilter;
+use crate::types::{GameEvent, InterpState, PlayerState, ServerMessage};
 use dashmap::DashMap;
+use std::collections::HashMap;
 use std::sync::Arc;
+use tokio::sync::watch;
 use tokio::time::{Duration, interval};
-use warp::ws::Message as WsMessage;
+
+/// State the broadcast loop shares with the rest of the server.
+#[derive(Default)]
+pub struct BroadcastContext {
+    pub metrics: Arc<Metrics>,
+    pub overload: Arc<OverloadBreaker>,
+    /// Filled in by the simulation task; read by `/api/state` and here.
+    pub player_states: Arc<DashMap<String, PlayerState>>,
+    /// Censors nicknames in outgoing updates when set.
+    pub nickname_filter: Option<Arc<ProfanityFilter>>,
+}
 
 pub async fn broadcast_positions(
     system: ActorSystem<GameEvent>,
     connection_manager: ConnectionManager,
     config: &ServerConfig,
+    mut shutdown: watch::Receiver<bool>,
+    context: BroadcastContext,
 ) {
+    let BroadcastContext {
+        metrics,
+        overload,
+        player_states,
+        nickname_filter,
+    } = context;
     log::info!(
         "Starting broadcast loop: {}ms tickrate (~{:.1} FPS)",
         config.tickrate_ms,
         1000.0 / config.tickrate_ms as f64
     );
 
-    let mut ticker = interval(Duration::from_millis(config.tickrate_ms));
-    let player_states: Arc<DashMap<String, PlayerState>> = Arc::new(DashMap::new());
-
-    let mut events = system.events();
-    let states_clone = player_states.clone();
-
-    tokio::spawn(async move {
-        loop {
-            match events.recv().await {
-                Ok(event) => handle_game_event(event, &states_clone),
-                Err(_) => break,
-            }
-        }
-    });
+    let mut current_tickrate = select_tickrate(&config.tickrate_ramp, 0, config.tickrate_ms);
+    let mut ticker = interval(Duration::from_millis(current_tickrate));
+    let mut simulation = Simulation::new(
+        connection_manager.clone(),
+        config.world_bounds.clone(),
+        config.wire_format,
+        metrics.clone(),
+    )
+    .with_states(player_states);
+    if let Some(filter) = nickname_filter {
+        simulation = simulation.with_nickname_filter(filter);
+    }
+    let player_states = simulation.states();
+    tokio::spawn(simulation.run(system.events(), config.sim_hz, shutdown.clone()));
 
     let mut tick_count = 0u64;
     let mut last_stats_log = std::time::Instant::now();
+    // Bytes of broadcast payload before and after compression since the last stats log.
+    let mut raw_bytes = 0u64;
+    let mut compressed_bytes = 0u64;
 
     loop {
-        ticker.tick().await;
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.changed() => {
+                log::info!("Broadcast loop stopping");
+                break;
+            }
+        }
         tick_count += 1;
+        metrics.record_tick();
 
-        let all_players: Vec<PlayerState> = player_states
+        let all_players: HashMap<String, PlayerState> = player_states
             .iter()
-            .map(|entry| entry.value().clone())
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
             .collect();
 
-        let msg = ServerMessage::StateUpdate {
-            players: all_players.clone(),
-        };
+        let viewers = connection_manager.get_connected_players();
+        let now = std::time::Instant::now();
 
-        if let Ok(json) = serde_json::to_string(&msg) {
-            connection_manager.broadcast(WsMessage::text(json));
+        let spectators = connection_manager.spectators();
+        let views = room_views(&all_players, &viewers, f64::from(config.interest_radius))
+            .into_iter()
+            .chain(spectator_views(&all_players, &spectators));
+        for (player_id, players) in views {
+            let msg = if config.send_interpolation {
+                interp_update(&players, now)
+            } else if config.use_delta_updates {
+                match state_delta(&connection_manager, &player_id, players) {
+                    Some(msg) => msg,
+                    None => continue,
+                }
+            } else {
+                ServerMessage::StateUpdate { players }
+            };
 
-            // Log stats every 5 seconds
-            if last_stats_log.elapsed().as_secs() >= 5 {
-                let actual_fps = tick_count as f64 / 5.0;
-                log::debug!(
-                    "Broadcast: {:.1} FPS, {} players, {} connections",
-                    actual_fps,
-                    all_players.len(),
+            let frame = if config.compress_broadcasts {
+                encode_compressed_server_message(&msg, config.wire_format).map(
+                    |(frame, raw_len)| {
+                        raw_bytes += raw_len as u64;
+                        compressed_bytes += frame.as_bytes().len() as u64;
+                        frame
+                    },
+                )
+            } else {
+                encode_server_message(&msg, config.wire_format)
+            };
+            if let Some(frame) = frame {
+                connection_manager.send_droppable(&player_id, frame);
+            }
+        }
+
+        // Log stats and re-evaluate the tick rate and overload breaker every 5 seconds
+        if last_stats_log.elapsed().as_secs() >= 5 {
+            metrics.set_overloaded(overload.update(connection_manager.count()));
+
+            let target_tickrate = select_tickrate(
+                &config.tickrate_ramp,
+                connection_manager.count(),
+                config.tickrate_ms,
+            );
+            if target_tickrate != current_tickrate {
+                log::info!(
+                    "Broadcast tickrate {}ms -> {}ms for {} connections",
+                    current_tickrate,
+                    target_tickrate,
                     connection_manager.count()
                 );
-                tick_count = 0;
-                last_stats_log = std::time::Instant::now();
+                current_tickrate = target_tickrate;
+                ticker = interval(Duration::from_millis(current_tickrate));
+            }
+
+            let actual_fps = tick_count as f64 / 5.0;
+            metrics.set_tick_rate(actual_fps);
+            log::debug!(
+                "Broadcast: {:.1} FPS, {} players, {} connections",
+                actual_fps,
+                all_players.len(),
+                connection_manager.count()
+            );
+            if raw_bytes > 0 {
+                log::debug!(
+                    "Broadcast compression: {} -> {} bytes ({:.1}% of original)",
+                    raw_bytes,
+                    compressed_bytes,
+                    compressed_bytes as f64 / raw_bytes as f64 * 100.0
+                );
             }
+            tick_count = 0;
+            raw_bytes = 0;
+            compressed_bytes = 0;
+            last_stats_log = std::time::Instant::now();
         }
     }
 }
 
-fn handle_game_event(event: GameEvent, states: &DashMap<String, PlayerState>) {
-    match event {
-        GameEvent::PlayerJoined {
-            player_id,
-            wallet,
-            position,
-        } => {
-            let nickname = format!(
-                "Player_{}",
-                player_id.strip_prefix("player_").unwrap_or(&player_id)
-            );
+/// Pairs each viewer with the states it can see: players in its own room within `radius`,
+/// itself included. Viewers without a known state are skipped.
+fn room_views(
+    all_players: &HashMap<String, PlayerState>,
+    viewers: &[String],
+    radius: f64,
+) -> Vec<(String, Vec<PlayerState>)> {
+    let mut rooms: HashMap<&str, HashMap<String, PlayerState>> = HashMap::new();
+    for state in all_players.values() {
+        rooms
+            .entry(state.room.as_str())
+            .or_default()
+            .insert(state.player_id.clone(), state.clone());
+    }
 
-            log::debug!(
-                "Player {} joined at ({:.2}, {:.2}, {:.2})",
-                player_id,
-                position.x,
-                position.y,
-                position.z
-            );
+    let grids: HashMap<&str, SpatialGrid> = rooms
+        .iter()
+        .map(|(room, states)| (*room, SpatialGrid::from_states(radius, states.values())))
+        .collect();
 
-            states.insert(
-                player_id.clone(),
-                PlayerState {
-                    player_id,
-                    wallet,
-                    nickname,
-                    position: position.clone(),
-                    velocity: Position::default(),
-                    last_update: std::time::Instant::now(),
-                    previous_position: position,
-                    violations: 0,
-                },
-            );
+    viewers
+        .iter()
+        .filter_map(|player_id| {
+            let viewer = all_players.get(player_id)?;
+            let room = viewer.room.as_str();
+            let players = visible_states(&grids[room], &rooms[room], viewer, radius);
+            Some((player_id.clone(), players))
+        })
+        .collect()
+}
+
+/// Everyone in each spectator's room. Spectators have no position, so nothing is culled.
+fn spectator_views(
+    all_players: &HashMap<String, PlayerState>,
+    spectators: &[(String, String)],
+) -> Vec<(String, Vec<PlayerState>)> {
+    spectators
+        .iter()
+        .map(|(spectator_id, room)| {
+            let players = all_players
+                .values()
+                .filter(|state| &state.room == room)
+                .cloned()
+                .collect();
+            (spectator_id.clone(), players)
+        })
+        .collect()
+}
+
+/// Picks the tickrate of the first ramp bucket whose `max_players` covers `players`.
+///
+/// Counts above the last bucket use its tickrate; an empty ramp always uses `default_ms`.
+fn select_tickrate(ramp: &[(usize, u64)], players: usize, default_ms: u64) -> u64 {
+    ramp.iter()
+        .find(|(max_players, _)| players <= *max_players)
+        .or(ramp.last())
+        .map_or(default_ms, |(_, tickrate)| *tickrate)
+}
+
+fn interp_update(players: &[PlayerState], now: std::time::Instant) -> ServerMessage {
+    ServerMessage::InterpUpdate {
+        players: players
+            .iter()
+            .map(|state| InterpState::from_state(state, now))
+            .collect(),
+    }
+}
+
+/// Builds the delta for `player_id` against what its connection was last sent.
+///
+/// Falls back to a full `StateUpdate` when nothing has been sent yet, and returns
+/// `None` when nothing changed.
+fn state_delta(
+    connection_manager: &ConnectionManager,
+    player_id: &str,
+    players: Vec<PlayerState>,
+) -> Option<ServerMessage> {
+    let current: Snapshot = players
+        .iter()
+        .map(|state| (state.player_id.clone(), state.clone()))
+        .collect();
+
+    let Some(previous) = connection_manager.replace_snapshot(player_id, current.clone()) else {
+        return Some(ServerMessage::StateUpdate { players });
+    };
+
+    let (changed, removed) = diff_states(&previous, &current);
+    if changed.is_empty() && removed.is_empty() {
+        return None;
+    }
+
+    Some(ServerMessage::StateDelta { changed, removed })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{MAX_HEALTH, Position};
+
+    fn player(id: &str, room: &str) -> PlayerState {
+        PlayerState {
+            player_id: id.to_string(),
+            wallet: id.to_string(),
+            nickname: id.to_string(),
+            room: room.to_string(),
+            position: Position::default(),
+            velocity: Position::default(),
+            health: MAX_HEALTH,
+            latency_ms: 0,
+            last_update: std::time::Instant::now(),
+            previous_position: Position::default(),
+            extrapolated_secs: 0.0,
+            violations: 0,
         }
-        GameEvent::PlayerMoved {
-            player_id,
-            position,
-            velocity,
-        } => {
-            if let Some(mut state) = states.get_mut(&player_id) {
-                state.previous_position = state.position.clone();
-                state.position = position;
-                state.velocity = velocity;
-                state.last_update = std::time::Instant::now();
+    }
+
+    #[test]
+    fn test_room_views_hide_other_rooms() {
+        let all_players: HashMap<String, PlayerState> = [
+            player("a", "lobby"),
+            player("b", "arena"),
+            player("c", "lobby"),
+        ]
+        .into_iter()
+        .map(|state| (state.player_id.clone(), state))
+        .collect();
+        let viewers = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+
+        for (viewer, players) in room_views(&all_players, &viewers, 500.0) {
+            let mut seen: Vec<&str> = players.iter().map(|p| p.player_id.as_str()).collect();
+            seen.sort_unstable();
+            match viewer.as_str() {
+                "a" | "c" => assert_eq!(seen, ["a", "c"]),
+                "b" => assert_eq!(seen, ["b"]),
+                other => panic!("unexpected viewer {}", other),
             }
         }
-        GameEvent::PlayerLeft { player_id } => {
-            log::debug!("Player {} left", player_id);
-            states.remove(&player_id);
-        }
+    }
+
+    #[test]
+    fn test_select_tickrate_buckets() {
+        let ramp = [(16, 4), (64, 8), (256, 16)];
+
+        assert_eq!(select_tickrate(&ramp, 0, 4), 4);
+        assert_eq!(select_tickrate(&ramp, 16, 4), 4);
+        assert_eq!(select_tickrate(&ramp, 17, 4), 8);
+        assert_eq!(select_tickrate(&ramp, 256, 4), 16);
+        assert_eq!(select_tickrate(&ramp, 1000, 4), 16);
+        assert_eq!(select_tickrate(&[], 1000, 4), 4);
+    }
+
+    #[test]
+    fn test_interp_update_carries_both_positions() {
+        let now = std::time::Instant::now();
+        let mut moved = player("a", "lobby");
+        moved.previous_position = Position::new(1.0, 0.0, 0.0);
+        moved.position = Position::new(2.0, 0.0, 0.0);
+        moved.last_update = now - Duration::from_millis(40);
+
+        let msg = interp_update(&[moved], now);
+        let json = serde_json::to_value(&msg).unwrap();
+
+        assert_eq!(json["type"], "InterpUpdate");
+        let state = &json["players"][0];
+        assert_eq!(state["previous_position"]["x"], 1.0);
+        assert_eq!(state["position"]["x"], 2.0);
+        assert_eq!(state["age_ms"], 40);
     }
 }
@@ -0,0 +1,37 @@
+use std::sync::OnceLock;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// A single wall-clock reading in epoch milliseconds and the monotonic instant it was taken at.
+static ORIGIN: OnceLock<(u64, Instant)> = OnceLock::new();
+
+/// Milliseconds since the Unix epoch as the server sees them.
+///
+/// Advanced by the monotonic clock from one wall-clock reading, so adjustments to the system
+/// clock can't make it jump or run backward mid-session.
+pub fn server_time_ms() -> u64 {
+    let (epoch_ms, origin) = ORIGIN.get_or_init(|| {
+        let epoch_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        (epoch_ms, Instant::now())
+    });
+    epoch_ms + origin.elapsed().as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_time_tracks_wall_clock_and_never_decreases() {
+        let first = server_time_ms();
+        let second = server_time_ms();
+        assert!(second >= first);
+
+        let wall_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        assert!(wall_ms.abs_diff(second) < 1_000);
+    }
+}
@@ -1,52 +1,577 @@
+use crate::network::delta::Snapshot;
 use dashmap::DashMap;
-use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{Notify, mpsc};
+use tokio::task::AbortHandle;
 use warp::ws::Message;
 
+/// A reserved player or spectator slot, released when dropped.
+pub struct ConnectionSlot {
+    slots: Arc<AtomicUsize>,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.slots.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Outbound backlog of one socket, shared between the manager and its forwarding task.
+#[derive(Clone, Debug, Default)]
+pub struct OutboundQueue {
+    queued: Arc<AtomicUsize>,
+    /// Start of the current stretch spent over the queue limit.
+    backed_up_since: Arc<Mutex<Option<Instant>>>,
+    overflow: Arc<Notify>,
+}
+
+impl OutboundQueue {
+    /// Frames waiting to be written to the socket.
+    pub fn len(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    /// Called by the forwarding task after each write with the channel's actual backlog.
+    ///
+    /// The manager only counts the frames it sends itself, so this also folds in frames the
+    /// player actor queued directly.
+    pub fn set_len(&self, len: usize) {
+        self.queued.store(len, Ordering::SeqCst);
+    }
+
+    /// Resolves once the connection is given up on as a slow consumer, either by the manager's
+    /// backpressure or by a send finding the buffer full.
+    pub async fn overflowed(&self) {
+        self.overflow.notified().await;
+    }
+}
+
+/// Sending half of a socket's outbound channel, created by `outbound_channel`.
+///
+/// A send that finds the buffer full gives up on the connection as a slow consumer, the same
+/// way backpressure does, so a client that stops reading can't grow memory without bound.
+#[derive(Clone, Debug)]
+pub struct OutboundSender {
+    tx: mpsc::Sender<Message>,
+    queue: OutboundQueue,
+}
+
+impl OutboundSender {
+    pub fn send(&self, message: Message) -> Result<(), TrySendError<Message>> {
+        let result = self.tx.try_send(message);
+        if let Err(TrySendError::Full(_)) = &result {
+            self.queue.overflow.notify_one();
+        }
+        result
+    }
+
+    /// Whether both senders feed the same socket.
+    pub fn same_channel(&self, other: &OutboundSender) -> bool {
+        self.tx.same_channel(&other.tx)
+    }
+}
+
+/// Creates a socket's outbound channel, holding up to `capacity` frames.
+pub fn outbound_channel(capacity: usize) -> (OutboundSender, mpsc::Receiver<Message>) {
+    let (tx, rx) = mpsc::channel(capacity);
+    let sender = OutboundSender {
+        tx,
+        queue: OutboundQueue::default(),
+    };
+    (sender, rx)
+}
+
+/// Limits applied to droppable sends.
+#[derive(Debug, Clone, Copy)]
+struct Backpressure {
+    max_queue: usize,
+    timeout: Duration,
+}
+
+struct Connection {
+    sender: OutboundSender,
+}
+
+impl Connection {
+    fn queue(&self) -> &OutboundQueue {
+        &self.sender.queue
+    }
+
+    fn send(&self, message: Message) -> bool {
+        // Counted up front so the forwarding task can't see the frame before the count, and
+        // taken back if the frame never made it into the channel.
+        self.queue().queued.fetch_add(1, Ordering::SeqCst);
+        let sent = self.sender.send(message).is_ok();
+        if !sent {
+            self.queue().queued.fetch_sub(1, Ordering::SeqCst);
+        }
+        sent
+    }
+}
+
 #[derive(Clone)]
 pub struct ConnectionManager {
-    connections: Arc<DashMap<String, mpsc::UnboundedSender<Message>>>,
+    connections: Arc<DashMap<String, Connection>>,
+    pending_reaps: Arc<DashMap<String, AbortHandle>>,
+    last_sent: Arc<DashMap<String, Snapshot>>,
+    /// Room of every live player actor, connected or within its reconnect grace period.
+    rooms: Arc<DashMap<String, String>>,
+    /// Room watched by each spectator. Spectators share `connections` but have no actor.
+    spectators: Arc<DashMap<String, String>>,
+    /// Player sockets holding a `ConnectionSlot`, including ones still being set up.
+    slots: Arc<AtomicUsize>,
+    /// Spectator sockets holding a `ConnectionSlot`, counted apart so they can't fill the
+    /// server.
+    spectator_slots: Arc<AtomicUsize>,
+    backpressure: Option<Backpressure>,
 }
 
 impl ConnectionManager {
     pub fn new() -> Self {
         Self {
             connections: Arc::new(DashMap::new()),
+            pending_reaps: Arc::new(DashMap::new()),
+            last_sent: Arc::new(DashMap::new()),
+            rooms: Arc::new(DashMap::new()),
+            spectators: Arc::new(DashMap::new()),
+            slots: Arc::new(AtomicUsize::new(0)),
+            spectator_slots: Arc::new(AtomicUsize::new(0)),
+            backpressure: None,
         }
     }
 
-    pub fn add(&self, player_id: String, sender: mpsc::UnboundedSender<Message>) {
-        self.connections.insert(player_id, sender);
+    /// Skips droppable sends to connections with `max_queue` or more frames queued, and
+    /// disconnects them once they have stayed backed up for `timeout`. 0 disables.
+    pub fn with_backpressure(mut self, max_queue: usize, timeout: Duration) -> Self {
+        self.backpressure = (max_queue > 0).then_some(Backpressure { max_queue, timeout });
+        self
+    }
+
+    /// Atomically claims one of `max_players` slots, or returns `None` when the server is full.
+    pub fn try_reserve_slot(&self, max_players: usize) -> Option<ConnectionSlot> {
+        reserve(&self.slots, max_players)
+    }
+
+    /// Like `try_reserve_slot`, from the separate pool of `max_spectators` spectator slots.
+    pub fn try_reserve_spectator_slot(&self, max_spectators: usize) -> Option<ConnectionSlot> {
+        reserve(&self.spectator_slots, max_spectators)
+    }
+
+    /// Registers `sender` as the player's socket and returns its queue for the forwarding task.
+    pub fn add(&self, player_id: String, sender: OutboundSender) -> OutboundQueue {
+        // A new socket has seen nothing yet, so it starts from a full update.
+        self.last_sent.remove(&player_id);
+        let queue = sender.queue.clone();
+        self.connections.insert(player_id, Connection { sender });
+        queue
+    }
+
+    /// Registers a spectator watching `room`. It receives broadcasts like a player but isn't one.
+    pub fn add_spectator(
+        &self,
+        spectator_id: String,
+        room: String,
+        sender: OutboundSender,
+    ) -> OutboundQueue {
+        self.spectators.insert(spectator_id.clone(), room);
+        self.add(spectator_id, sender)
+    }
+
+    /// Every spectator with the room it watches.
+    pub fn spectators(&self) -> Vec<(String, String)> {
+        self.spectators
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
     }
 
     pub fn remove(&self, player_id: &str) {
         self.connections.remove(player_id);
+        self.last_sent.remove(player_id);
+        self.spectators.remove(player_id);
     }
 
+    /// Removes the player's connection only if it is still backed by `sender`.
+    ///
+    /// Returns false when the player has already been re-added with a newer socket.
+    pub fn remove_if_current(&self, player_id: &str, sender: &OutboundSender) -> bool {
+        let removed = self
+            .connections
+            .remove_if(player_id, |_, current| current.sender.same_channel(sender))
+            .is_some();
+        if removed {
+            self.last_sent.remove(player_id);
+            self.spectators.remove(player_id);
+        }
+        removed
+    }
+
+    /// Records the state last sent to `player_id`, returning the previous snapshot.
+    ///
+    /// `None` means nothing has been sent over the current connection yet.
+    pub fn replace_snapshot(&self, player_id: &str, snapshot: Snapshot) -> Option<Snapshot> {
+        self.last_sent.insert(player_id.to_string(), snapshot)
+    }
+
+    /// Registers the task that stops a detached player's actor once the grace period ends.
+    pub fn schedule_reap(&self, player_id: String, reaper: AbortHandle) {
+        if let Some(previous) = self.pending_reaps.insert(player_id, reaper) {
+            previous.abort();
+        }
+    }
+
+    /// Cancels a pending reap. Returns true if the player was still within its grace period.
+    pub fn cancel_reap(&self, player_id: &str) -> bool {
+        match self.pending_reaps.remove(player_id) {
+            Some((_, reaper)) => {
+                reaper.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Claims a pending reap for the reaper task itself. Returns false if it was cancelled.
+    pub fn finish_reap(&self, player_id: &str) -> bool {
+        self.pending_reaps.remove(player_id).is_some()
+    }
+
+    /// Whether `player_id` currently has a socket registered.
+    pub fn is_connected(&self, player_id: &str) -> bool {
+        self.connections.contains_key(player_id)
+    }
+
+    pub fn send_to(&self, player_id: &str, message: Message) -> bool {
+        self.connections
+            .get(player_id)
+            .is_some_and(|connection| connection.send(message))
+    }
+
+    /// Like `send_to`, but skips a connection whose queue is over the backpressure limit.
+    ///
+    /// Used for fan-out traffic, so one stalled client can't grow memory without bound.
+    /// Returns false when skipped.
+    pub fn send_droppable(&self, player_id: &str, message: Message) -> bool {
+        let Some(connection) = self.connections.get(player_id) else {
+            return false;
+        };
+        let mut backed_up_since = connection
+            .queue()
+            .backed_up_since
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        match self.backpressure {
+            Some(limit) if connection.queue().len() >= limit.max_queue => {
+                let since = *backed_up_since.get_or_insert_with(Instant::now);
+                if since.elapsed() >= limit.timeout {
+                    log::warn!(
+                        "Disconnecting slow consumer {} with {} frames queued",
+                        player_id,
+                        connection.queue().len()
+                    );
+                    connection.queue().overflow.notify_one();
+                }
+                // The client missed a frame, so deltas have to restart from a full update.
+                self.last_sent.remove(player_id);
+                false
+            }
+            _ => {
+                *backed_up_since = None;
+                connection.send(message)
+            }
+        }
+    }
+
+    pub fn send_to_many(&self, player_ids: &[String], message: Message) -> usize {
+        player_ids
+            .iter()
+            .filter(|player_id| self.send_droppable(player_id, message.clone()))
+            .count()
+    }
+
+    /// Sends `message` to every connected player regardless of room.
+    #[cfg(test)]
     pub fn broadcast(&self, message: Message) -> usize {
-        let mut success_count = 0;
+        self.send_to_many(&self.get_connected_players(), message)
+    }
+
+    pub fn join_room(&self, player_id: String, room: String) {
+        self.rooms.insert(player_id, room);
+    }
+
+    pub fn leave_room(&self, player_id: &str) {
+        self.rooms.remove(player_id);
+    }
+
+    pub fn players_in_room(&self, room: &str) -> Vec<String> {
+        self.rooms
+            .iter()
+            .filter(|entry| entry.value() == room)
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Sends `message` to every connected player and spectator in `room`.
+    pub fn broadcast_to_room(&self, room: &str, message: Message) -> usize {
+        let mut recipients = self.players_in_room(room);
+        recipients.extend(
+            self.spectators
+                .iter()
+                .filter(|entry| entry.value() == room)
+                .map(|entry| entry.key().clone()),
+        );
+        self.send_to_many(&recipients, message)
+    }
+
+    /// Sends `farewell` followed by a close frame to every connection.
+    ///
+    /// Entries are left in place; each connection handler removes its own once the socket closes.
+    pub fn disconnect_all(&self, farewell: Message) {
         for entry in self.connections.iter() {
-            if entry.value().send(message.clone()).is_ok() {
-                success_count += 1;
-            }
+            entry.value().send(farewell.clone());
+            entry.value().send(Message::close());
         }
-        success_count
     }
 
+    /// Connected players, not counting spectators.
     pub fn count(&self) -> usize {
-        self.connections.len()
+        self.connections
+            .iter()
+            .filter(|entry| !self.spectators.contains_key(entry.key()))
+            .count()
     }
 
     pub fn get_connected_players(&self) -> Vec<String> {
         self.connections
             .iter()
+            .filter(|entry| !self.spectators.contains_key(entry.key()))
             .map(|entry| entry.key().clone())
             .collect()
     }
 }
 
+/// Claims one of `max` slots counted by `slots`, or returns `None` when all are taken.
+fn reserve(slots: &Arc<AtomicUsize>, max: usize) -> Option<ConnectionSlot> {
+    slots
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |taken| {
+            (taken < max).then_some(taken + 1)
+        })
+        .ok()?;
+    Some(ConnectionSlot {
+        slots: slots.clone(),
+    })
+}
+
 impl Default for ConnectionManager {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager_with(ids: &[&str]) -> (ConnectionManager, Vec<mpsc::Receiver<Message>>) {
+        let manager = ConnectionManager::new();
+        let receivers = ids
+            .iter()
+            .map(|id| {
+                let (tx, rx) = outbound_channel(16);
+                manager.add(id.to_string(), tx);
+                rx
+            })
+            .collect();
+        (manager, receivers)
+    }
+
+    #[test]
+    fn test_send_to_only_reaches_addressed_player() {
+        let (manager, mut receivers) = manager_with(&["a", "b", "c"]);
+
+        assert!(manager.send_to("b", Message::text("hello")));
+
+        assert!(receivers[0].try_recv().is_err());
+        assert_eq!(receivers[1].try_recv().unwrap(), Message::text("hello"));
+        assert!(receivers[2].try_recv().is_err());
+    }
+
+    #[test]
+    fn test_send_to_unknown_player() {
+        let (manager, _receivers) = manager_with(&["a"]);
+        assert!(!manager.send_to("missing", Message::text("hello")));
+    }
+
+    #[test]
+    fn test_send_to_many_counts_deliveries() {
+        let (manager, mut receivers) = manager_with(&["a", "b", "c"]);
+        drop(receivers.remove(2));
+
+        let ids = vec!["a".to_string(), "c".to_string(), "missing".to_string()];
+        assert_eq!(manager.send_to_many(&ids, Message::text("hi")), 1);
+
+        assert!(receivers[0].try_recv().is_ok());
+        assert!(receivers[1].try_recv().is_err());
+    }
+
+    #[test]
+    fn test_remove_if_current_ignores_replaced_sender() {
+        let manager = ConnectionManager::new();
+        let (old_tx, _old_rx) = outbound_channel(16);
+        let (new_tx, _new_rx) = outbound_channel(16);
+
+        manager.add("a".to_string(), old_tx.clone());
+        manager.add("a".to_string(), new_tx.clone());
+
+        assert!(!manager.remove_if_current("a", &old_tx));
+        assert_eq!(manager.count(), 1);
+        assert!(manager.remove_if_current("a", &new_tx));
+        assert_eq!(manager.count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_reap_wins_over_reaper() {
+        let manager = ConnectionManager::new();
+        let reaper = tokio::spawn(std::future::pending::<()>());
+        manager.schedule_reap("a".to_string(), reaper.abort_handle());
+
+        assert!(manager.cancel_reap("a"));
+        assert!(!manager.finish_reap("a"));
+        assert!(reaper.await.unwrap_err().is_cancelled());
+    }
+
+    #[test]
+    fn test_disconnect_all_sends_farewell_then_close() {
+        let (manager, mut receivers) = manager_with(&["a", "b"]);
+
+        manager.disconnect_all(Message::text("bye"));
+
+        for rx in receivers.iter_mut() {
+            assert_eq!(rx.try_recv().unwrap(), Message::text("bye"));
+            assert!(rx.try_recv().unwrap().is_close());
+        }
+    }
+
+    #[test]
+    fn test_reconnect_resets_snapshot() {
+        let (manager, _receivers) = manager_with(&["a"]);
+        assert!(manager.replace_snapshot("a", Snapshot::new()).is_none());
+        assert!(manager.replace_snapshot("a", Snapshot::new()).is_some());
+
+        let (tx, _rx) = outbound_channel(16);
+        manager.add("a".to_string(), tx);
+
+        assert!(manager.replace_snapshot("a", Snapshot::new()).is_none());
+    }
+
+    #[test]
+    fn test_broadcast_reaches_everyone() {
+        let (manager, mut receivers) = manager_with(&["a", "b", "c"]);
+
+        assert_eq!(manager.broadcast(Message::text("all")), 3);
+        for rx in receivers.iter_mut() {
+            assert!(rx.try_recv().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_broadcast_to_room_skips_other_rooms() {
+        let (manager, mut receivers) = manager_with(&["a", "b", "c"]);
+        manager.join_room("a".to_string(), "lobby".to_string());
+        manager.join_room("b".to_string(), "arena".to_string());
+        manager.join_room("c".to_string(), "lobby".to_string());
+        manager.leave_room("c");
+
+        assert_eq!(manager.broadcast_to_room("lobby", Message::text("hi")), 1);
+        assert!(receivers[0].try_recv().is_ok());
+        assert!(receivers[1].try_recv().is_err());
+        assert!(receivers[2].try_recv().is_err());
+    }
+
+    #[test]
+    fn test_slots_released_on_drop() {
+        let manager = ConnectionManager::new();
+        let first = manager.try_reserve_slot(2).unwrap();
+        let _second = manager.try_reserve_slot(2).unwrap();
+        assert!(manager.try_reserve_slot(2).is_none());
+
+        drop(first);
+        assert!(manager.try_reserve_slot(2).is_some());
+    }
+
+    #[test]
+    fn test_spectator_slots_counted_apart() {
+        let manager = ConnectionManager::new();
+        let _player = manager.try_reserve_slot(1).unwrap();
+        assert!(manager.try_reserve_slot(1).is_none());
+
+        let spectator = manager.try_reserve_spectator_slot(1).unwrap();
+        assert!(manager.try_reserve_spectator_slot(1).is_none());
+        drop(spectator);
+        assert!(manager.try_reserve_spectator_slot(1).is_some());
+        assert!(manager.try_reserve_spectator_slot(0).is_none());
+    }
+
+    #[test]
+    fn test_broadcast_skips_backed_up_connection() {
+        let manager = ConnectionManager::new().with_backpressure(2, Duration::from_secs(60));
+        let (stalled_tx, _stalled_rx) = outbound_channel(16);
+        let (live_tx, _live_rx) = outbound_channel(16);
+        let _stalled = manager.add("stalled".to_string(), stalled_tx);
+        let live = manager.add("live".to_string(), live_tx);
+
+        assert_eq!(manager.broadcast(Message::text("1")), 2);
+        live.set_len(0);
+        assert_eq!(manager.broadcast(Message::text("2")), 2);
+        live.set_len(0);
+        assert_eq!(manager.broadcast(Message::text("3")), 1);
+
+        // Targeted sends still go through.
+        assert!(manager.send_to("stalled", Message::close()));
+    }
+
+    #[tokio::test]
+    async fn test_full_send_buffer_drops_only_slow_consumer() {
+        let manager = ConnectionManager::new();
+        let (slow_tx, _slow_rx) = outbound_channel(2);
+        let (fast_tx, mut fast_rx) = outbound_channel(2);
+        let slow = manager.add("slow".to_string(), slow_tx);
+        let fast = manager.add("fast".to_string(), fast_tx);
+
+        for frame in ["1", "2", "3"] {
+            manager.broadcast(Message::text(frame));
+            assert_eq!(fast_rx.recv().await.unwrap(), Message::text(frame));
+        }
+
+        tokio::time::timeout(Duration::from_secs(1), slow.overflowed())
+            .await
+            .expect("full buffer did not drop the slow consumer");
+        assert_eq!(slow.len(), 2);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), fast.overflowed())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stalled_receiver_is_dropped() {
+        let manager = ConnectionManager::new().with_backpressure(1, Duration::ZERO);
+        let (tx, _rx) = outbound_channel(16);
+        let queue = manager.add("a".to_string(), tx);
+        assert!(manager.replace_snapshot("a", Snapshot::new()).is_none());
+        manager.replace_snapshot("a", Snapshot::new());
+
+        assert!(manager.send_droppable("a", Message::text("1")));
+        assert!(!manager.send_droppable("a", Message::text("2")));
+
+        tokio::time::timeout(Duration::from_secs(1), queue.overflowed())
+            .await
+            .expect("slow consumer was not dropped");
+        // The skipped frame may have been a delta, so the next update is a full one.
+        assert!(manager.replace_snapshot("a", Snapshot::new()).is_none());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::types::PlayerState;
+use std::collections::HashMap;
+
+/// What one connection was last sent, keyed by player id.
+pub type Snapshot = HashMap<String, PlayerState>;
+
+/// Diffs `current` against the snapshot last sent to a connection.
+///
+/// Returns the players that are new or whose position, velocity, health or violations changed,
+/// and the ids of players no longer present.
+pub fn diff_states(previous: &Snapshot, current: &Snapshot) -> (Vec<PlayerState>, Vec<String>) {
+    let changed = current
+        .values()
+        .filter(|state| {
+            previous
+                .get(&state.player_id)
+                .is_none_or(|last| has_changed(last, state))
+        })
+        .cloned()
+        .collect();
+
+    let removed = previous
+        .keys()
+        .filter(|player_id| !current.contains_key(*player_id))
+        .cloned()
+        .collect();
+
+    (changed, removed)
+}
+
+fn has_changed(last: &PlayerState, current: &PlayerState) -> bool {
+    last.position != current.position
+        || last.velocity != current.velocity
+        || last.health != current.health
+        || last.violations != current.violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DEFAULT_ROOM, MAX_HEALTH, Position};
+    use std::time::Instant;
+
+    fn player(id: &str, x: f64) -> PlayerState {
+        PlayerState {
+            player_id: id.to_string(),
+            wallet: id.to_string(),
+            nickname: id.to_string(),
+            room: DEFAULT_ROOM.to_string(),
+            position: Position::new(x, 0.0, 0.0),
+            velocity: Position::default(),
+            health: MAX_HEALTH,
+            latency_ms: 0,
+            last_update: Instant::now(),
+            previous_position: Position::new(x, 0.0, 0.0),
+            extrapolated_secs: 0.0,
+            violations: 0,
+        }
+    }
+
+    fn snapshot(players: &[PlayerState]) -> Snapshot {
+        players
+            .iter()
+            .map(|p| (p.player_id.clone(), p.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_unchanged_player_omitted() {
+        let previous = snapshot(&[player("a", 0.0), player("b", 10.0)]);
+        let current = snapshot(&[player("a", 0.0), player("b", 12.0)]);
+
+        let (changed, removed) = diff_states(&previous, &current);
+
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].player_id, "b");
+        assert!(removed.is_empty());
+    }
+
+    #[test]
+    fn test_new_and_removed_players() {
+        let previous = snapshot(&[player("a", 0.0), player("b", 10.0)]);
+        let current = snapshot(&[player("a", 0.0), player("c", 20.0)]);
+
+        let (changed, removed) = diff_states(&previous, &current);
+
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].player_id, "c");
+        assert_eq!(removed, vec!["b".to_string()]);
+    }
+
+    #[test]
+    fn test_violation_change_included() {
+        let previous = snapshot(&[player("a", 0.0)]);
+        let mut flagged = player("a", 0.0);
+        flagged.violations = 1;
+
+        let (changed, _) = diff_states(&previous, &snapshot(&[flagged]));
+        assert_eq!(changed.len(), 1);
+    }
+}
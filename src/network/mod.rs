@@ -1,5 +1,23 @@
+mod ban;
 mod broadcast;
+mod clock;
 mod connection;
+mod delta;
+mod overload;
+mod protocol;
+mod rate_limit;
+mod simulation;
+mod spatial;
+mod wire;
 
-pub use broadcast::broadcast_positions;
-pub use connection::ConnectionManager;
+pub use ban::BanList;
+pub use broadcast::{BroadcastContext, broadcast_positions};
+pub use clock::server_time_ms;
+pub use connection::{
+    ConnectionManager, ConnectionSlot, OutboundQueue, OutboundSender, outbound_channel,
+};
+pub use overload::OverloadBreaker;
+pub use protocol::ProtocolVersion;
+pub use rate_limit::{MessageKind, RateLimiter};
+pub use simulation::Simulation;
+pub use wire::{decode_client_message, encode_compressed_server_message, encode_server_message};
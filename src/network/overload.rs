@@ -0,0 +1,118 @@
+use crate::config::ServerConfig;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Share of the high-water mark used as the low-water mark when none is configured.
+const DEFAULT_LOW_WATER_PERCENT: usize = 80;
+
+/// Turns new logins away while the server holds too many connections, so a wave of
+/// reconnects backs off instead of piling on.
+///
+/// Opens once the connection count exceeds the high-water mark and closes again only below
+/// the low-water mark, so it doesn't flap around a single threshold.
+#[derive(Debug, Default)]
+pub struct OverloadBreaker {
+    open: AtomicBool,
+    high_water: usize,
+    low_water: usize,
+}
+
+impl OverloadBreaker {
+    /// A `high_water` of 0 disables the breaker. A `low_water` of 0 defaults to 80% of
+    /// `high_water`, but at least 1, so the breaker can always close again.
+    pub fn new(high_water: usize, low_water: usize) -> Self {
+        let low_water = match low_water {
+            0 => (high_water * DEFAULT_LOW_WATER_PERCENT / 100).max(1),
+            low_water => low_water,
+        };
+        Self {
+            open: AtomicBool::new(false),
+            high_water,
+            low_water,
+        }
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self::new(config.overload_high_water, config.overload_low_water)
+    }
+
+    /// Checks if new logins are currently being refused.
+    pub fn is_open(&self) -> bool {
+        self.open.load(Ordering::Relaxed)
+    }
+
+    /// Re-evaluates the breaker against the current connection count and returns whether it
+    /// is open.
+    pub fn update(&self, connections: usize) -> bool {
+        if self.high_water == 0 {
+            return false;
+        }
+
+        let was_open = self.is_open();
+        let open = if was_open {
+            connections >= self.low_water
+        } else {
+            connections > self.high_water
+        };
+        if open != was_open {
+            self.open.store(open, Ordering::Relaxed);
+            if open {
+                log::warn!(
+                    "{} connections exceed {}, refusing new logins",
+                    connections,
+                    self.high_water
+                );
+            } else {
+                log::info!(
+                    "{} connections below {}, accepting logins again",
+                    connections,
+                    self.low_water
+                );
+            }
+        }
+        open
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_above_high_water() {
+        let breaker = OverloadBreaker::new(100, 80);
+        assert!(!breaker.update(100));
+        assert!(breaker.update(101));
+        assert!(breaker.is_open());
+    }
+
+    #[test]
+    fn test_closes_only_below_low_water() {
+        let breaker = OverloadBreaker::new(100, 80);
+        breaker.update(150);
+
+        assert!(breaker.update(90));
+        assert!(breaker.update(80));
+        assert!(!breaker.update(79));
+        assert!(!breaker.update(90));
+    }
+
+    #[test]
+    fn test_unset_low_water_defaults_below_high_water() {
+        let breaker = OverloadBreaker::new(100, 0);
+        breaker.update(150);
+
+        assert!(breaker.update(80));
+        assert!(!breaker.update(79));
+
+        let breaker = OverloadBreaker::new(1, 0);
+        assert!(breaker.update(2));
+        assert!(breaker.update(1));
+        assert!(!breaker.update(0));
+    }
+
+    #[test]
+    fn test_zero_high_water_never_opens() {
+        let breaker = OverloadBreaker::new(0, 0);
+        assert!(!breaker.update(usize::MAX));
+    }
+}
@@ -0,0 +1,49 @@
+/// Message schema version, negotiated as a websocket subprotocol during the upgrade.
+///
+/// Only `V1` exists, so the version decides which sockets are accepted and nothing past the
+/// handshake branches on it. A version whose frames differ should be passed to the encoders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolVersion {
+    V1,
+}
+
+impl ProtocolVersion {
+    /// Newest first, so clients offering several versions get the latest one we speak.
+    const SUPPORTED: &[ProtocolVersion] = &[ProtocolVersion::V1];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ProtocolVersion::V1 => "game-v1",
+        }
+    }
+
+    /// Picks a version from a `Sec-WebSocket-Protocol` header.
+    ///
+    /// Clients that send no header predate versioning and get `V1`. Returns `None` when the
+    /// client only offers versions this server doesn't support.
+    pub fn negotiate(requested: Option<&str>) -> Option<ProtocolVersion> {
+        let Some(requested) = requested else {
+            return Some(ProtocolVersion::V1);
+        };
+        let offered: Vec<&str> = requested.split(',').map(str::trim).collect();
+        Self::SUPPORTED
+            .iter()
+            .copied()
+            .find(|version| offered.contains(&version.name()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(ProtocolVersion::negotiate(None), Some(ProtocolVersion::V1));
+        assert_eq!(
+            ProtocolVersion::negotiate(Some("game-v9, game-v1")),
+            Some(ProtocolVersion::V1)
+        );
+        assert_eq!(ProtocolVersion::negotiate(Some("game-v9")), None);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::config::ServerConfig;
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Client message kinds that are throttled independently of each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MessageKind {
+    /// Single moves and every sample of a `MoveBatch`.
+    Move,
+    Chat,
+    Attack,
+    GetState,
+    TimeSync,
+}
+
+/// Holds up to `capacity` tokens and regains `capacity` of them per second.
+struct Bucket {
+    tokens: f64,
+    capacity: f64,
+}
+
+/// A connection's token buckets, one per `MessageKind`, all refilled from the same clock.
+///
+/// Kinds without a bucket, such as those configured with a limit of 0, are never throttled.
+pub struct RateLimiter {
+    buckets: HashMap<MessageKind, Bucket>,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Per-second limits taken from `config`, starting with every bucket full.
+    pub fn new(config: &ServerConfig) -> Self {
+        Self::with_limits(
+            [
+                (MessageKind::Move, config.move_rate_limit),
+                (MessageKind::Chat, config.chat_rate_limit),
+                (MessageKind::Attack, config.attack_rate_limit),
+                (MessageKind::GetState, config.get_state_rate_limit),
+                (MessageKind::TimeSync, config.time_sync_rate_limit),
+            ],
+            Instant::now(),
+        )
+    }
+
+    pub fn with_limits(limits: impl IntoIterator<Item = (MessageKind, u32)>, now: Instant) -> Self {
+        let buckets = limits
+            .into_iter()
+            .filter(|(_, limit)| *limit > 0)
+            .map(|(kind, limit)| {
+                let capacity = limit as f64;
+                (
+                    kind,
+                    Bucket {
+                        tokens: capacity,
+                        capacity,
+                    },
+                )
+            })
+            .collect();
+        Self {
+            buckets,
+            last_refill: now,
+        }
+    }
+
+    /// Spends `count` tokens of `kind`. Nothing is spent unless all of them are available.
+    pub fn allow(&mut self, kind: MessageKind, count: u32) -> bool {
+        self.allow_at(kind, count, Instant::now())
+    }
+
+    pub fn allow_at(&mut self, kind: MessageKind, count: u32, now: Instant) -> bool {
+        self.refill(now);
+        let Some(bucket) = self.buckets.get_mut(&kind) else {
+            return true;
+        };
+
+        let count = count as f64;
+        if bucket.tokens < count {
+            return false;
+        }
+        bucket.tokens -= count;
+        true
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        if elapsed == 0.0 {
+            return;
+        }
+        self.last_refill = now;
+        for bucket in self.buckets.values_mut() {
+            bucket.tokens = (bucket.tokens + elapsed * bucket.capacity).min(bucket.capacity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_exhausted_move_budget_leaves_chat_untouched() {
+        let start = Instant::now();
+        let mut limiter =
+            RateLimiter::with_limits([(MessageKind::Move, 4), (MessageKind::Chat, 2)], start);
+
+        assert!(limiter.allow_at(MessageKind::Move, 4, start));
+        assert!(!limiter.allow_at(MessageKind::Move, 1, start));
+
+        assert!(limiter.allow_at(MessageKind::Chat, 1, start));
+        assert!(limiter.allow_at(MessageKind::Chat, 1, start));
+        assert!(!limiter.allow_at(MessageKind::Chat, 1, start));
+
+        // No bucket configured: never throttled.
+        assert!(limiter.allow_at(MessageKind::Attack, 1_000, start));
+    }
+
+    #[test]
+    fn test_buckets_refill_with_elapsed_time() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::with_limits([(MessageKind::Move, 10)], start);
+        assert!(limiter.allow_at(MessageKind::Move, 10, start));
+
+        // A batch that doesn't fit spends nothing.
+        let later = start + Duration::from_millis(500);
+        assert!(!limiter.allow_at(MessageKind::Move, 6, later));
+        assert!(limiter.allow_at(MessageKind::Move, 5, later));
+
+        // Refills stop at capacity.
+        let much_later = later + Duration::from_secs(10);
+        assert!(!limiter.allow_at(MessageKind::Move, 11, much_later));
+        assert!(limiter.allow_at(MessageKind::Move, 10, much_later));
+    }
+}
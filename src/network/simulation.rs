@@ -0,0 +1,473 @@
+use crate::anticheat::clamp_to_bounds;
+use crate::config::WireFormat;
+use crate::metrics::Metrics;
+use crate::network::{ConnectionManager, encode_server_message};
+use crate::profanity::ProfanityFilter;
+use crate::types::{GameEvent, MAX_HEALTH, PlayerState, Position, ServerMessage, WorldBounds};
+use dashmap::DashMap;
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::watch;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+/// How long a player keeps moving on its last velocity without a new move, so a client that
+/// stalls or detaches doesn't drift across the world.
+const MAX_EXTRAPOLATION_SECS: f64 = 0.5;
+
+/// The authoritative world state the broadcast loop snapshots.
+///
+/// Only the simulation task writes to `states`: it applies game events as they arrive and
+/// advances every player in fixed steps, whatever the broadcast tickrate is.
+pub struct Simulation {
+    states: Arc<DashMap<String, PlayerState>>,
+    connection_manager: ConnectionManager,
+    bounds: WorldBounds,
+    wire_format: WireFormat,
+    metrics: Arc<Metrics>,
+    nickname_filter: Option<Arc<ProfanityFilter>>,
+}
+
+impl Simulation {
+    pub fn new(
+        connection_manager: ConnectionManager,
+        bounds: WorldBounds,
+        wire_format: WireFormat,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        Self {
+            states: Arc::new(DashMap::new()),
+            connection_manager,
+            bounds,
+            wire_format,
+            metrics,
+            nickname_filter: None,
+        }
+    }
+
+    /// Masks blocked words in the nicknames this simulation stores and broadcasts.
+    pub fn with_nickname_filter(mut self, filter: Arc<ProfanityFilter>) -> Self {
+        self.nickname_filter = Some(filter);
+        self
+    }
+
+    /// Keeps the simulated states in `states`, so others can read them without the loop.
+    pub fn with_states(mut self, states: Arc<DashMap<String, PlayerState>>) -> Self {
+        self.states = states;
+        self
+    }
+
+    /// Read access to the simulated states, for the broadcast loop.
+    pub fn states(&self) -> Arc<DashMap<String, PlayerState>> {
+        self.states.clone()
+    }
+
+    /// Steps the world `sim_hz` times a second and applies game events in between, until
+    /// shutdown or the bus closes.
+    ///
+    /// A receiver that falls behind loses the events it skipped but keeps going, so a single
+    /// burst can't freeze position updates for good.
+    pub async fn run(
+        self,
+        mut events: broadcast::Receiver<GameEvent>,
+        sim_hz: u32,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        log::info!("Starting simulation loop at {} Hz", sim_hz);
+
+        let dt = 1.0 / sim_hz.max(1) as f64;
+        let mut ticker = interval(Duration::from_secs_f64(dt));
+        // A stalled runtime catches up step by step, so the world still advances by whole steps.
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Burst);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => self.step(dt),
+                result = events.recv() => match result {
+                    Ok(event) => self.apply(event),
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!("Simulation lagged, skipped {} game events", skipped);
+                        self.metrics.record_events_lagged(skipped);
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = shutdown.changed() => {
+                    log::info!("Simulation loop stopping");
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Advances every player by `dt` seconds: integrates its velocity and keeps it inside the
+    /// world bounds. A player is stopped once it has gone `MAX_EXTRAPOLATION_SECS` without a
+    /// new move.
+    ///
+    /// `previous_position` and `last_update` still describe the last accepted move, so
+    /// interpolating clients see the extrapolation as part of the same stretch.
+    pub fn step(&self, dt: f64) {
+        for mut state in self.states.iter_mut() {
+            if state.velocity == Position::default() {
+                continue;
+            }
+            let dt = dt.min(MAX_EXTRAPOLATION_SECS - state.extrapolated_secs);
+            let next = Position::new(
+                state.position.x + state.velocity.x * dt,
+                state.position.y + state.velocity.y * dt,
+                state.position.z + state.velocity.z * dt,
+            );
+            state.position = clamp_to_bounds(&next, &self.bounds);
+            state.extrapolated_secs += dt;
+            if state.extrapolated_secs >= MAX_EXTRAPOLATION_SECS {
+                state.velocity = Position::default();
+            }
+        }
+    }
+
+    fn display_nickname(&self, nickname: String) -> String {
+        match &self.nickname_filter {
+            Some(filter) => filter.censor(&nickname),
+            None => nickname,
+        }
+    }
+
+    pub fn apply(&self, event: GameEvent) {
+        let states = &self.states;
+        let connection_manager = &self.connection_manager;
+        let wire_format = self.wire_format;
+        match event {
+            GameEvent::PlayerJoined {
+                player_id,
+                wallet,
+                nickname,
+                room,
+                position,
+            } => {
+                log::debug!(
+                    "Player {} joined {} at ({:.2}, {:.2}, {:.2})",
+                    player_id,
+                    room,
+                    position.x,
+                    position.y,
+                    position.z
+                );
+
+                let nickname = self.display_nickname(nickname);
+                connection_manager.join_room(player_id.clone(), room.clone());
+                let joined = ServerMessage::PlayerJoined {
+                    player_id: player_id.clone(),
+                    nickname: nickname.clone(),
+                    position: position.clone(),
+                };
+                if let Some(frame) = encode_server_message(&joined, wire_format) {
+                    connection_manager.broadcast_to_room(&room, frame);
+                }
+
+                states.insert(
+                    player_id.clone(),
+                    PlayerState {
+                        player_id,
+                        wallet,
+                        nickname,
+                        room,
+                        position: position.clone(),
+                        velocity: Position::default(),
+                        health: MAX_HEALTH,
+                        latency_ms: 0,
+                        last_update: std::time::Instant::now(),
+                        previous_position: position,
+                        extrapolated_secs: 0.0,
+                        violations: 0,
+                    },
+                );
+            }
+            GameEvent::PlayerMoved {
+                player_id,
+                room,
+                position,
+                velocity,
+                latency_ms,
+            } => {
+                if let Some(mut state) = states.get_mut(&player_id) {
+                    state.room = room;
+                    state.previous_position = state.position.clone();
+                    state.position = position;
+                    state.velocity = velocity;
+                    state.extrapolated_secs = 0.0;
+                    state.latency_ms = latency_ms;
+                    state.last_update = std::time::Instant::now();
+                }
+            }
+            GameEvent::PlayerLeft { player_id, room } => {
+                log::debug!("Player {} left {}", player_id, room);
+                connection_manager.leave_room(&player_id);
+                states.remove(&player_id);
+
+                let left = ServerMessage::PlayerLeft { player_id };
+                if let Some(frame) = encode_server_message(&left, wire_format) {
+                    connection_manager.broadcast_to_room(&room, frame);
+                }
+            }
+            GameEvent::PlayerDamaged {
+                player_id,
+                amount,
+                remaining,
+            } => {
+                log::debug!(
+                    "Player {} took {:.1} damage, {:.1} left",
+                    player_id,
+                    amount,
+                    remaining
+                );
+                if let Some(mut state) = states.get_mut(&player_id) {
+                    state.health = remaining;
+                }
+            }
+            GameEvent::PlayerRespawned {
+                player_id,
+                position,
+            } => {
+                log::debug!("Player {} respawned", player_id);
+                if let Some(mut state) = states.get_mut(&player_id) {
+                    state.previous_position = position.clone();
+                    state.position = position;
+                    state.velocity = Position::default();
+                    state.extrapolated_secs = 0.0;
+                    state.health = MAX_HEALTH;
+                    state.last_update = std::time::Instant::now();
+                }
+            }
+            GameEvent::ViolationDetected {
+                player_id,
+                kind,
+                count,
+            } => {
+                log::debug!(
+                    "Player {} flagged for {} ({} total)",
+                    player_id,
+                    kind,
+                    count
+                );
+                if let Some(mut state) = states.get_mut(&player_id) {
+                    state.violations = count;
+                }
+            }
+            GameEvent::ChatSent {
+                player_id,
+                room,
+                nickname,
+                text,
+            } => {
+                let msg = ServerMessage::Chat {
+                    from: player_id,
+                    nickname: self.display_nickname(nickname),
+                    text,
+                };
+
+                if let Some(frame) = encode_server_message(&msg, wire_format) {
+                    connection_manager.broadcast_to_room(&room, frame);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor_system::{EventBus, EventBusStats};
+    use crate::network::outbound_channel;
+    use tokio::sync::mpsc;
+    use warp::ws::Message as WsMessage;
+
+    fn simulation(connection_manager: ConnectionManager) -> Simulation {
+        Simulation::new(
+            connection_manager,
+            WorldBounds::cube(100.0),
+            WireFormat::Json,
+            Arc::new(Metrics::new()),
+        )
+    }
+
+    fn joined(id: &str) -> GameEvent {
+        GameEvent::PlayerJoined {
+            player_id: id.to_string(),
+            wallet: id.to_string(),
+            nickname: id.to_string(),
+            room: "lobby".to_string(),
+            position: Position::default(),
+        }
+    }
+
+    fn count_joins(rx: &mut mpsc::Receiver<WsMessage>, nickname: &str) -> usize {
+        let mut count = 0;
+        while let Ok(frame) = rx.try_recv() {
+            let msg: serde_json::Value = serde_json::from_str(frame.to_str().unwrap()).unwrap();
+            if msg["type"] == "PlayerJoined" && msg["nickname"] == nickname {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn test_player_joined_notifies_room_once() {
+        let manager = ConnectionManager::new();
+        let mut receivers = Vec::new();
+        for (id, room) in [("a", "lobby"), ("b", "lobby"), ("c", "arena")] {
+            let (tx, rx) = outbound_channel(1024);
+            manager.add(id.to_string(), tx);
+            manager.join_room(id.to_string(), room.to_string());
+            receivers.push(rx);
+        }
+        let simulation = simulation(manager);
+
+        simulation.apply(GameEvent::PlayerJoined {
+            player_id: "d".to_string(),
+            wallet: "d".to_string(),
+            nickname: "Dee".to_string(),
+            room: "lobby".to_string(),
+            position: Position::default(),
+        });
+
+        assert_eq!(count_joins(&mut receivers[0], "Dee"), 1);
+        assert_eq!(count_joins(&mut receivers[1], "Dee"), 1);
+        assert_eq!(count_joins(&mut receivers[2], "Dee"), 0);
+        assert_eq!(simulation.states().get("d").unwrap().nickname, "Dee");
+    }
+
+    #[test]
+    fn test_nickname_filter_censors_broadcast_names() {
+        let manager = ConnectionManager::new();
+        let (tx, mut rx) = outbound_channel(1024);
+        manager.add("a".to_string(), tx);
+        manager.join_room("a".to_string(), "lobby".to_string());
+        let simulation =
+            simulation(manager).with_nickname_filter(Arc::new(ProfanityFilter::new(["rude"])));
+
+        simulation.apply(GameEvent::PlayerJoined {
+            player_id: "b".to_string(),
+            wallet: "b".to_string(),
+            nickname: "Rud3_Guy".to_string(),
+            room: "lobby".to_string(),
+            position: Position::default(),
+        });
+
+        assert_eq!(count_joins(&mut rx, "****_Guy"), 1);
+        assert_eq!(simulation.states().get("b").unwrap().nickname, "****_Guy");
+    }
+
+    #[test]
+    fn test_step_integrates_velocity_deterministically() {
+        let simulation = simulation(ConnectionManager::new());
+        simulation.apply(joined("a"));
+        let moved = |position: Position| GameEvent::PlayerMoved {
+            player_id: "a".to_string(),
+            room: "lobby".to_string(),
+            position,
+            velocity: Position::new(10.0, 0.0, -5.0),
+            latency_ms: 0,
+        };
+        simulation.apply(moved(Position::new(1.0, 0.0, 0.0)));
+
+        for step in 1..=4 {
+            simulation.step(0.125);
+            let state = simulation.states().get("a").unwrap().clone();
+            let elapsed = step as f64 * 0.125;
+            assert_eq!(
+                state.position,
+                Position::new(1.0 + 10.0 * elapsed, 0.0, -5.0 * elapsed)
+            );
+            // The last accepted move is left alone.
+            assert_eq!(state.previous_position, Position::default());
+        }
+
+        // Bounds stop the integration at the edge of the world.
+        simulation.apply(moved(Position::new(99.0, 0.0, -99.0)));
+        simulation.step(0.25);
+        let state = simulation.states().get("a").unwrap().clone();
+        assert_eq!(state.position, Position::new(100.0, 0.0, -100.0));
+    }
+
+    #[test]
+    fn test_extrapolation_stops_without_new_moves() {
+        let simulation = simulation(ConnectionManager::new());
+        simulation.apply(joined("a"));
+        simulation.apply(GameEvent::PlayerMoved {
+            player_id: "a".to_string(),
+            room: "lobby".to_string(),
+            position: Position::default(),
+            velocity: Position::new(4.0, 0.0, 0.0),
+            latency_ms: 0,
+        });
+
+        // The last step only covers what is left of the window.
+        for _ in 0..3 {
+            simulation.step(0.2);
+        }
+        let state = simulation.states().get("a").unwrap().clone();
+        assert!((state.position.x - 4.0 * MAX_EXTRAPOLATION_SECS).abs() < 1e-9);
+        assert_eq!(state.velocity, Position::default());
+
+        for _ in 0..10 {
+            simulation.step(0.2);
+        }
+        assert_eq!(
+            simulation.states().get("a").unwrap().position,
+            state.position
+        );
+
+        // A new move restarts the window.
+        simulation.apply(GameEvent::PlayerMoved {
+            player_id: "a".to_string(),
+            room: "lobby".to_string(),
+            position: state.position.clone(),
+            velocity: Position::new(4.0, 0.0, 0.0),
+            latency_ms: 0,
+        });
+        simulation.step(0.2);
+        let moved = simulation.states().get("a").unwrap().position.x - state.position.x;
+        assert!((moved - 0.8).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_lagged_simulation_keeps_running() {
+        let bus = EventBus::<GameEvent>::new(2);
+        let events = bus.subscribe();
+
+        // Overrun the receiver before it starts: a, b and c are lost.
+        for id in ["a", "b", "c", "d", "e"] {
+            bus.send(joined(id)).unwrap();
+        }
+        let metrics = Arc::new(Metrics::new());
+        let simulation = Simulation::new(
+            ConnectionManager::new(),
+            WorldBounds::default(),
+            WireFormat::Json,
+            metrics.clone(),
+        );
+        let states = simulation.states();
+        let (_shutdown_tx, shutdown_rx) = watch::channel(false);
+        tokio::spawn(simulation.run(events, 60, shutdown_rx));
+
+        let wait_for = |id: &'static str| {
+            let states = states.clone();
+            tokio::time::timeout(Duration::from_secs(1), async move {
+                while !states.contains_key(id) {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            })
+        };
+        // With room for only two events, sending "late" before d and e are read would evict d.
+        wait_for("d").await.unwrap();
+        wait_for("e").await.unwrap();
+        bus.send(joined("late")).unwrap();
+        wait_for("late").await.unwrap();
+
+        assert!(!states.contains_key("a"));
+        assert!(
+            metrics
+                .render(0, &[], &[], EventBusStats::default())
+                .contains("events_lagged_total 3")
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use crate::types::{PlayerState, Position};
+use std::collections::HashMap;
+
+type CellKey = (i32, i32, i32);
+
+/// Uniform grid that buckets player ids by the cell their position falls in.
+pub struct SpatialGrid {
+    cell_size: f64,
+    cells: HashMap<CellKey, Vec<String>>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f64) -> Self {
+        Self {
+            cell_size: cell_size.max(1.0),
+            cells: HashMap::new(),
+        }
+    }
+
+    /// Builds a grid from a snapshot of player states.
+    pub fn from_states<'a>(
+        cell_size: f64,
+        states: impl IntoIterator<Item = &'a PlayerState>,
+    ) -> Self {
+        let mut grid = Self::new(cell_size);
+        for state in states {
+            grid.insert(&state.player_id, &state.position);
+        }
+        grid
+    }
+
+    pub fn insert(&mut self, player_id: &str, position: &Position) {
+        let key = self.cell_of(position);
+        self.cells
+            .entry(key)
+            .or_default()
+            .push(player_id.to_string());
+    }
+
+    /// Player ids in every cell overlapping the cube of `radius` around `position`.
+    ///
+    /// Candidates still need an exact distance check by the caller.
+    pub fn candidates(&self, position: &Position, radius: f64) -> Vec<&str> {
+        let (cx, cy, cz) = self.cell_of(position);
+        let reach = (radius / self.cell_size).ceil() as i32;
+        let mut ids = Vec::new();
+
+        for x in (cx - reach)..=(cx + reach) {
+            for y in (cy - reach)..=(cy + reach) {
+                for z in (cz - reach)..=(cz + reach) {
+                    if let Some(cell) = self.cells.get(&(x, y, z)) {
+                        ids.extend(cell.iter().map(String::as_str));
+                    }
+                }
+            }
+        }
+
+        ids
+    }
+
+    fn cell_of(&self, position: &Position) -> CellKey {
+        (
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+            (position.z / self.cell_size).floor() as i32,
+        )
+    }
+}
+
+/// Returns the states visible to `viewer`: everyone within `radius`, including the viewer.
+pub fn visible_states(
+    grid: &SpatialGrid,
+    states: &HashMap<String, PlayerState>,
+    viewer: &PlayerState,
+    radius: f64,
+) -> Vec<PlayerState> {
+    grid.candidates(&viewer.position, radius)
+        .into_iter()
+        .filter_map(|id| states.get(id))
+        .filter(|state| state.position.distance_to(&viewer.position) <= radius)
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DEFAULT_ROOM, MAX_HEALTH};
+    use std::time::Instant;
+
+    fn player(id: &str, x: f64, y: f64, z: f64) -> PlayerState {
+        PlayerState {
+            player_id: id.to_string(),
+            wallet: id.to_string(),
+            nickname: id.to_string(),
+            room: DEFAULT_ROOM.to_string(),
+            position: Position::new(x, y, z),
+            velocity: Position::default(),
+            health: MAX_HEALTH,
+            latency_ms: 0,
+            last_update: Instant::now(),
+            previous_position: Position::new(x, y, z),
+            extrapolated_secs: 0.0,
+            violations: 0,
+        }
+    }
+
+    fn index(players: &[PlayerState]) -> HashMap<String, PlayerState> {
+        players
+            .iter()
+            .map(|p| (p.player_id.clone(), p.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_far_player_excluded() {
+        let players = vec![
+            player("a", 0.0, 0.0, 0.0),
+            player("b", 100.0, 0.0, 0.0),
+            player("c", 2000.0, 0.0, 0.0),
+        ];
+        let states = index(&players);
+        let grid = SpatialGrid::from_states(500.0, &players);
+
+        let visible = visible_states(&grid, &states, &players[0], 500.0);
+        let mut ids: Vec<&str> = visible.iter().map(|p| p.player_id.as_str()).collect();
+        ids.sort_unstable();
+
+        assert_eq!(ids, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_neighbouring_cell_included() {
+        // Straddles a cell boundary but is within the radius.
+        let players = vec![player("a", 490.0, 0.0, 0.0), player("b", 510.0, 0.0, 0.0)];
+        let states = index(&players);
+        let grid = SpatialGrid::from_states(500.0, &players);
+
+        let visible = visible_states(&grid, &states, &players[0], 500.0);
+        assert_eq!(visible.len(), 2);
+    }
+
+    #[test]
+    fn test_player_leaving_cell_drops_out() {
+        let mut players = vec![player("a", 0.0, 0.0, 0.0), player("b", 100.0, 0.0, 0.0)];
+        let grid = SpatialGrid::from_states(500.0, &players);
+        assert_eq!(
+            visible_states(&grid, &index(&players), &players[0], 500.0).len(),
+            2
+        );
+
+        players[1].position = Position::new(-2000.0, 0.0, 0.0);
+        let grid = SpatialGrid::from_states(500.0, &players);
+        let visible = visible_states(&grid, &index(&players), &players[0], 500.0);
+
+        assert_eq!(visible.len(), 1);
+        assert_eq!(visible[0].player_id, "a");
+    }
+}
@@ -0,0 +1,318 @@
+use crate::config::WireFormat;
+use crate::types::{ClientMessage, InterpState, MoveSample, PlayerState, Position, ServerMessage};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Write;
+use warp::ws::Message as WsMessage;
+
+// The protocol types are internally tagged for JSON clients, which bincode cannot
+// decode. These mirrors give bincode an externally tagged layout of the same variants.
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ClientMessage")]
+enum ClientMessageDef {
+    Move {
+        position: Position,
+        velocity: Position,
+        delta_time: f32,
+        client_time_ms: Option<u64>,
+    },
+    MoveBatch {
+        moves: Vec<MoveSample>,
+    },
+    GetState,
+    Chat {
+        text: String,
+    },
+    Attack {
+        target_id: String,
+    },
+    TimeSync {
+        client_send_ms: u64,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(remote = "ServerMessage")]
+enum ServerMessageDef {
+    StateUpdate {
+        players: Vec<PlayerState>,
+    },
+    StateDelta {
+        changed: Vec<PlayerState>,
+        removed: Vec<String>,
+    },
+    InterpUpdate {
+        players: Vec<InterpState>,
+    },
+    SelfState {
+        state: PlayerState,
+    },
+    Correction {
+        position: Position,
+        velocity: Position,
+    },
+    Error {
+        message: String,
+    },
+    Kicked {
+        reason: String,
+    },
+    Chat {
+        from: String,
+        nickname: String,
+        text: String,
+    },
+    PlayerJoined {
+        player_id: String,
+        nickname: String,
+        position: Position,
+    },
+    PlayerLeft {
+        player_id: String,
+    },
+    Announcement {
+        text: String,
+    },
+    TimeSyncReply {
+        client_send_ms: u64,
+        server_recv_ms: u64,
+        server_send_ms: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct BinaryClientMessage(#[serde(with = "ClientMessageDef")] ClientMessage);
+
+#[derive(Serialize)]
+struct BinaryServerMessage<'a>(#[serde(with = "ServerMessageDef")] &'a ServerMessage);
+
+/// Encodes a server message as a text frame (JSON) or a binary frame (bincode).
+pub fn encode_server_message(msg: &ServerMessage, format: WireFormat) -> Option<WsMessage> {
+    match format {
+        WireFormat::Json => serde_json::to_string(msg).ok().map(WsMessage::text),
+        WireFormat::Bincode => bincode::serialize(&BinaryServerMessage(msg))
+            .ok()
+            .map(WsMessage::binary),
+    }
+}
+
+/// Encodes a server message like `encode_server_message`, then gzips it into a binary frame
+/// for the client to inflate. Also returns the uncompressed size.
+pub fn encode_compressed_server_message(
+    msg: &ServerMessage,
+    format: WireFormat,
+) -> Option<(WsMessage, usize)> {
+    let payload = match format {
+        WireFormat::Json => serde_json::to_vec(msg).ok()?,
+        WireFormat::Bincode => bincode::serialize(&BinaryServerMessage(msg)).ok()?,
+    };
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(&payload).ok()?;
+    let compressed = encoder.finish().ok()?;
+    Some((WsMessage::binary(compressed), payload.len()))
+}
+
+/// Decodes a client frame: binary frames as bincode, text frames as JSON.
+///
+/// With `strict`, JSON fields the protocol doesn't define fail the decode instead of being
+/// ignored. The error describes what couldn't be parsed.
+pub fn decode_client_message(msg: &WsMessage, strict: bool) -> Result<ClientMessage, String> {
+    if msg.is_binary() {
+        return bincode::deserialize::<BinaryClientMessage>(msg.as_bytes())
+            .map(|BinaryClientMessage(msg)| msg)
+            .map_err(|e| e.to_string());
+    }
+
+    let text = msg
+        .to_str()
+        .map_err(|_| "expected a text or binary frame".to_string())?;
+    if !strict {
+        return serde_json::from_str(text).map_err(|e| e.to_string());
+    }
+
+    let received: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let msg = ClientMessage::deserialize(&received).map_err(|e| e.to_string())?;
+    let known = serde_json::to_value(&msg).map_err(|e| e.to_string())?;
+    match unknown_field(&received, &known) {
+        Some(field) => Err(format!("unknown field `{}`", field)),
+        None => Ok(msg),
+    }
+}
+
+/// The first key in `received` that re-encoding the decoded message doesn't produce, which
+/// is a field the protocol doesn't define.
+fn unknown_field<'a>(received: &'a Value, known: &Value) -> Option<&'a str> {
+    match (received, known) {
+        (Value::Object(received), Value::Object(known)) => {
+            received
+                .iter()
+                .find_map(|(key, value)| match known.get(key) {
+                    Some(known) => unknown_field(value, known),
+                    None => Some(key.as_str()),
+                })
+        }
+        (Value::Array(received), Value::Array(known)) => received
+            .iter()
+            .zip(known)
+            .find_map(|(received, known)| unknown_field(received, known)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_client_message(msg: ClientMessage, format: WireFormat) -> WsMessage {
+        match format {
+            WireFormat::Json => WsMessage::text(serde_json::to_string(&msg).unwrap()),
+            WireFormat::Bincode => {
+                WsMessage::binary(bincode::serialize(&BinaryClientMessage(msg)).unwrap())
+            }
+        }
+    }
+
+    fn sample_move() -> ClientMessage {
+        ClientMessage::Move {
+            position: Position::new(1.5, -2.0, 300.25),
+            velocity: Position::new(10.0, 0.0, -4.5),
+            delta_time: 0.016,
+            client_time_ms: Some(1_700_000_000_000),
+        }
+    }
+
+    #[test]
+    fn test_move_round_trips_under_bincode() {
+        let frame = encode_client_message(sample_move(), WireFormat::Bincode);
+
+        assert!(frame.is_binary());
+        assert_eq!(decode_client_message(&frame, true), Ok(sample_move()));
+    }
+
+    #[test]
+    fn test_move_round_trips_under_json() {
+        let frame = encode_client_message(sample_move(), WireFormat::Json);
+
+        assert!(frame.is_text());
+        assert_eq!(decode_client_message(&frame, true), Ok(sample_move()));
+    }
+
+    #[test]
+    fn test_move_batch_round_trips_under_bincode() {
+        let batch = ClientMessage::MoveBatch {
+            moves: vec![
+                MoveSample {
+                    position: Position::new(1.0, 2.0, 3.0),
+                    velocity: Position::new(10.0, 0.0, 0.0),
+                    delta_time: 0.016,
+                    client_time_ms: Some(1_700_000_000_000),
+                },
+                MoveSample {
+                    position: Position::new(1.25, 2.0, 3.0),
+                    velocity: Position::new(10.0, 0.0, 0.0),
+                    delta_time: 0.016,
+                    client_time_ms: None,
+                },
+            ],
+        };
+        let frame = encode_client_message(batch.clone(), WireFormat::Bincode);
+
+        assert_eq!(decode_client_message(&frame, true), Ok(batch));
+    }
+
+    #[test]
+    fn test_server_message_frame_type_follows_format() {
+        let msg = ServerMessage::Kicked {
+            reason: "bye".to_string(),
+        };
+
+        let json = encode_server_message(&msg, WireFormat::Json).unwrap();
+        assert_eq!(
+            json.to_str().unwrap(),
+            r#"{"type":"Kicked","reason":"bye"}"#
+        );
+
+        let binary = encode_server_message(&msg, WireFormat::Bincode).unwrap();
+        assert!(binary.is_binary());
+        assert!(binary.as_bytes().len() < json.as_bytes().len());
+    }
+
+    #[test]
+    fn test_compressed_state_update_inflates_to_json() {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        let players = (0..20)
+            .map(|i| PlayerState {
+                player_id: format!("player-{}", i),
+                wallet: format!("wallet-{}", i),
+                nickname: format!("Nick{}", i),
+                room: crate::types::DEFAULT_ROOM.to_string(),
+                position: Position::new(i as f64, 0.0, -(i as f64)),
+                velocity: Position::default(),
+                health: crate::types::MAX_HEALTH,
+                latency_ms: 0,
+                // In the future, so `age_ms` is 0 however long encoding takes.
+                last_update: std::time::Instant::now() + std::time::Duration::from_secs(3600),
+                previous_position: Position::default(),
+                extrapolated_secs: 0.0,
+                violations: 0,
+            })
+            .collect();
+        let msg = ServerMessage::StateUpdate { players };
+
+        let (frame, raw_len) = encode_compressed_server_message(&msg, WireFormat::Json).unwrap();
+        assert!(frame.is_binary());
+        assert!(frame.as_bytes().len() < raw_len);
+
+        let mut inflated = String::new();
+        GzDecoder::new(frame.as_bytes())
+            .read_to_string(&mut inflated)
+            .unwrap();
+        assert_eq!(inflated, serde_json::to_string(&msg).unwrap());
+        assert_eq!(inflated.len(), raw_len);
+    }
+
+    #[test]
+    fn test_garbage_binary_frame_rejected() {
+        assert!(decode_client_message(&WsMessage::binary(vec![0xff; 3]), true).is_err());
+    }
+
+    #[test]
+    fn test_unknown_field_rejected_when_strict() {
+        let frame = WsMessage::text(
+            r#"{"type":"Move","position":{"x":1.0,"y":0.0,"z":0.0},"velocity":{"x":0.0,"y":0.0,"z":0.0},"delta_time":0.1,"turbo":true}"#,
+        );
+
+        let error = decode_client_message(&frame, true).unwrap_err();
+        assert!(error.contains("unknown field `turbo`"));
+
+        assert_eq!(
+            decode_client_message(&frame, false),
+            Ok(ClientMessage::Move {
+                position: Position::new(1.0, 0.0, 0.0),
+                velocity: Position::default(),
+                delta_time: 0.1,
+                client_time_ms: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_unknown_sample_field_rejected_when_strict() {
+        let frame = WsMessage::text(
+            r#"{"type":"MoveBatch","moves":[{"position":{"x":1.0,"y":0.0,"z":0.0},"velocity":{"x":0.0,"y":0.0,"z":0.0},"delta_time":0.1,"extra":1}]}"#,
+        );
+
+        assert!(
+            decode_client_message(&frame, true)
+                .unwrap_err()
+                .contains("unknown field `extra`")
+        );
+        assert!(decode_client_message(&frame, false).is_ok());
+    }
+}
@@ -1,49 +1,275 @@
-use crate::actor_system::{Actor, ActorContext, ActorError, Handler, async_trait};
-use crate::anticheat::{ValidationResult, validate_movement};
-use crate::player::state::{GetState, Kick, MovePlayer, SendMessage};
-use crate::types::{GameEvent, MAX_SPEED, MAX_VIOLATIONS, PlayerState, Position, ServerMessage};
-use std::time::Instant;
-use tokio::sync::mpsc;
+use crate::actor_system::{Actor, ActorContext, ActorError, ActorPath, Handler, async_trait};
+use crate::anticheat::{
+    AntiCheatConfig, MoveContext, MovementLimits, SharedAntiCheatConfig, ValidationResult,
+    ValidatorChain, clamp_to_bounds, is_in_bounds, is_teleport,
+};
+use crate::config::{AntiCheatMode, ServerConfig};
+use crate::metrics::Metrics;
+use crate::network::{BanList, OutboundSender, encode_server_message, server_time_ms};
+use crate::player::audit::{AuditEntry, AuditLog};
+use crate::player::history::PositionHistory;
+use crate::player::latency::LatencyTracker;
+use crate::player::state::{
+    AllowWarp, Announce, Attack, Kick, MovePlayer, PlayerCommand, RebindSocket, SendChat,
+    TakeDamage,
+};
+use crate::player::store::{PersistedState, PlayerStore};
+use crate::rng::SharedRng;
+use crate::types::{
+    DEFAULT_ROOM, GameEvent, MAX_CHAT_LENGTH, MAX_HEALTH, MAX_VIOLATIONS, PlayerState, Position,
+    ServerMessage,
+};
+use rand::Rng;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::TrySendError;
 use warp::ws::Message as WsMessage;
 
+/// How long a caller waits for a player actor to answer a query before giving up on it.
+pub const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Minimum time between two attacks from the same player.
+const ATTACK_COOLDOWN: Duration = Duration::from_millis(250);
+
+/// Seconds of `delta_time` a client may claim ahead of the wall clock, so moves bunched up by
+/// jitter still pass. Also caps what a silent client banks for later.
+const MAX_TIME_BUDGET_SECS: f32 = 0.25;
+
+/// Name a player's actor is created under, below `/user`.
+pub fn player_actor_name(player_id: &str) -> String {
+    format!("player-{}", player_id)
+}
+
+/// Path of the actor playing `player_id`.
+pub fn player_path(player_id: &str) -> ActorPath {
+    ActorPath::user(&player_actor_name(player_id))
+}
+
 pub struct PlayerActor {
     pub player_id: String,
     pub wallet: String,
     pub nickname: String,
+    pub room: String,
     pub position: Position,
     pub velocity: Position,
+    pub health: f32,
     pub last_update: Instant,
     pub violations: u32,
-    ws_sender: mpsc::UnboundedSender<WsMessage>,
+    /// Peer address of the current socket, banned if anti-cheat kicks the player.
+    pub remote_ip: Option<IpAddr>,
+    /// Start of the clean play currently counting toward the next violation decay.
+    clean_since: Instant,
+    /// Frame time the client may still claim, refilled at wall-clock pace up to
+    /// `MAX_TIME_BUDGET_SECS` and spent by every accepted move's `delta_time`.
+    time_budget: f32,
+    /// When `time_budget` was last refilled.
+    time_budget_at: Instant,
+    last_attack: Option<Instant>,
+    /// Target of a warp the server allowed, and when the allowance lapses.
+    pending_warp: Option<(Position, Instant)>,
+    /// Set until the first move after spawning, which is only checked against the spawn area.
+    first_move_grace: bool,
+    /// Reset on every (re)connect, since the client's clock may restart with it.
+    latency: LatencyTracker,
+    /// Recently accepted positions, for validating moves the client sent a little while ago.
+    history: PositionHistory,
+    /// Recently validated moves and their verdicts, for `/admin/audit`.
+    audit: AuditLog,
+    validators: ValidatorChain,
+    /// Thresholds admins can change at runtime, checked before every validated move.
+    anticheat: SharedAntiCheatConfig,
+    /// The thresholds `validators` was built with.
+    thresholds: AntiCheatConfig,
+    /// Set when `validators` was replaced by hand, so threshold changes leave it alone.
+    custom_validators: bool,
+    bans: Option<BanList>,
+    ws_sender: OutboundSender,
+    /// Set when a send finds the socket's forwarding task gone. Without a reconnect grace the
+    /// actor then stops after the message it is handling instead of playing on for nobody.
+    client_gone: bool,
+    config: Arc<ServerConfig>,
+    store: Option<Arc<dyn PlayerStore>>,
+    metrics: Arc<Metrics>,
 }
 
 impl PlayerActor {
+    /// Spawns the player at a point jittered by `rng`.
     pub fn new(
         player_id: String,
         wallet: String,
         nickname: String,
-        ws_sender: mpsc::UnboundedSender<WsMessage>,
+        ws_sender: OutboundSender,
+        config: Arc<ServerConfig>,
+        metrics: Arc<Metrics>,
+        rng: &SharedRng,
     ) -> Self {
         Self {
             player_id,
             wallet,
             nickname,
-            position: Position::default(),
+            room: DEFAULT_ROOM.to_string(),
+            position: rng.with(|rng| spawn_position(&config, rng)),
             velocity: Position::default(),
+            health: MAX_HEALTH,
             last_update: Instant::now(),
             violations: 0,
+            remote_ip: None,
+            clean_since: Instant::now(),
+            time_budget: MAX_TIME_BUDGET_SECS,
+            time_budget_at: Instant::now(),
+            last_attack: None,
+            pending_warp: None,
+            first_move_grace: config.spawn_grace_radius > 0.0,
+            latency: LatencyTracker::new(Instant::now()),
+            history: PositionHistory::new(config.reconcile_history),
+            audit: AuditLog::new(config.audit_log_size),
+            validators: ValidatorChain::standard(&MovementLimits::from_config(&config)),
+            anticheat: AntiCheatConfig::shared(&config),
+            thresholds: AntiCheatConfig::from_config(&config),
+            custom_validators: false,
+            bans: None,
             ws_sender,
+            client_gone: false,
+            config,
+            store: None,
+            metrics,
         }
     }
 
-    fn send_to_client(&self, msg: ServerMessage) {
-        if let Ok(json) = serde_json::to_string(&msg) {
-            let _ = self.ws_sender.send(WsMessage::text(json));
+    /// Replaces the standard anti-cheat checks applied to this player's moves.
+    #[cfg(test)]
+    pub fn with_validators(mut self, validators: ValidatorChain) -> Self {
+        self.validators = validators;
+        self.custom_validators = true;
+        self
+    }
+
+    /// Loads the player's saved state from `store` when it starts and saves it there when it
+    /// stops.
+    pub fn with_store(mut self, store: Option<Arc<dyn PlayerStore>>) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Validates moves against `anticheat`, picking up changes to it from the next move on.
+    pub fn with_anticheat(mut self, anticheat: SharedAntiCheatConfig) -> Self {
+        self.anticheat = anticheat;
+        self
+    }
+
+    /// Rebuilds the standard checks if the live thresholds changed since the last move.
+    fn refresh_thresholds(&mut self) {
+        let live = self
+            .anticheat
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if live == self.thresholds {
+            return;
         }
+
+        log::debug!(
+            "Player {} validating with new anti-cheat thresholds {:?}",
+            self.player_id,
+            live
+        );
+        if !self.custom_validators {
+            self.validators = ValidatorChain::standard(
+                &MovementLimits::from_config(&self.config).with_thresholds(&live),
+            );
+        }
+        self.thresholds = live;
+    }
+
+    /// Lets anti-cheat kicks ban `remote_ip` for `auto_ban_secs`.
+    pub fn with_bans(mut self, bans: BanList) -> Self {
+        self.bans = Some(bans);
+        self
+    }
+
+    fn persist(&self) {
+        if let Some(store) = &self.store {
+            store.save(
+                &self.player_id,
+                PersistedState {
+                    position: self.position.clone(),
+                },
+            );
+        }
+    }
+
+    /// Puts an eliminated player back at the spawn point with full health and tells the
+    /// client where it now is.
+    fn respawn(&mut self, ctx: &ActorContext<GameEvent>) {
+        self.health = MAX_HEALTH;
+        self.position = self.config.spawn_point.clone();
+        self.velocity = Position::default();
+        self.history.record(Instant::now(), self.position.clone());
+        self.send_correction();
+        self.persist();
+        ctx.system.publish(GameEvent::PlayerRespawned {
+            player_id: self.player_id.clone(),
+            position: self.position.clone(),
+        });
     }
 
-    fn handle_violation(&mut self, violation_type: &str, details: &str) {
+    fn send_to_client(&mut self, msg: ServerMessage) {
+        if let Some(frame) = encode_server_message(&msg, self.config.wire_format) {
+            self.send_frame(frame);
+        }
+    }
+
+    fn send_frame(&mut self, frame: WsMessage) {
+        // A full buffer already has the connection dropping this client as too slow.
+        if let Err(TrySendError::Closed(_)) = self.ws_sender.send(frame)
+            && !self.client_gone
+        {
+            log::debug!("Socket of player {} is gone", self.player_id);
+            self.client_gone = true;
+        }
+    }
+
+    /// Stops the actor once a send has failed. Call at the end of handlers that send.
+    ///
+    /// With a reconnect grace the connection's teardown decides instead: it either stops the
+    /// actor or leaves it detached for the client to resume until the grace reaper runs.
+    fn stop_if_client_gone(&self, ctx: &mut ActorContext<GameEvent>) {
+        if self.client_gone && self.config.reconnect_grace_ms == 0 {
+            ctx.stop_self();
+        }
+    }
+
+    /// Forgives one violation per full `violation_decay_secs` of clean play. 0 disables decay.
+    fn decay_violations(&mut self, now: Instant) {
+        let interval = Duration::from_secs(self.config.violation_decay_secs);
+        if self.violations == 0 || interval.is_zero() {
+            self.clean_since = now;
+            return;
+        }
+
+        let steps = now.duration_since(self.clean_since).as_millis() / interval.as_millis();
+        let steps = steps.min(self.violations as u128) as u32;
+        self.violations -= steps;
+        self.clean_since += interval * steps;
+    }
+
+    /// Tells the client where the server still has it after a rejected move.
+    fn send_correction(&mut self) {
+        self.send_to_client(ServerMessage::Correction {
+            position: self.position.clone(),
+            velocity: self.velocity.clone(),
+        });
+    }
+
+    fn handle_violation(
+        &mut self,
+        ctx: &ActorContext<GameEvent>,
+        violation_type: &str,
+        details: &str,
+    ) {
         self.violations += 1;
+        self.clean_since = Instant::now();
         log::warn!(
             "Player {} {} | {} | Violations: {}/{}",
             self.player_id,
@@ -53,89 +279,275 @@ impl PlayerActor {
             MAX_VIOLATIONS
         );
 
+        ctx.system.publish(GameEvent::ViolationDetected {
+            player_id: self.player_id.clone(),
+            kind: violation_type.to_lowercase().replace(' ', "_"),
+            count: self.violations,
+        });
+
+        if self.config.anticheat_mode != AntiCheatMode::Enforce {
+            return;
+        }
+
         self.send_to_client(ServerMessage::Error {
             message: format!(
                 "{} detected. Violations: {}/{}",
                 violation_type, self.violations, MAX_VIOLATIONS
             ),
         });
+        self.send_correction();
 
         if self.violations >= MAX_VIOLATIONS {
             log::error!("Player {} KICKED for too many violations", self.player_id);
             self.send_to_client(ServerMessage::Kicked {
                 reason: "Too many anti-cheat violations".to_string(),
             });
+
+            if self.config.auto_ban_secs > 0
+                && let (Some(bans), Some(ip)) = (&self.bans, self.remote_ip)
+            {
+                log::warn!(
+                    "Banning {} for {}s after kicking player {}",
+                    ip,
+                    self.config.auto_ban_secs,
+                    self.player_id
+                );
+                bans.ban(ip, Duration::from_secs(self.config.auto_ban_secs));
+            }
         }
     }
-}
 
-#[async_trait]
-impl Actor<GameEvent> for PlayerActor {
-    async fn pre_start(&mut self, ctx: &mut ActorContext<GameEvent>) -> Result<(), ActorError> {
-        log::debug!(
-            "Player {} ({}) joined at ({:.2}, {:.2}, {:.2})",
-            self.player_id,
-            self.nickname,
-            self.position.x,
-            self.position.y,
-            self.position.z
-        );
+    /// Uses up the pending warp if it hasn't lapsed and `position` is within `warp_tolerance`
+    /// of its target. A lapsed warp is dropped.
+    fn take_warp(&mut self, position: &Position, now: Instant) -> bool {
+        match &self.pending_warp {
+            Some((_, expires_at)) if now > *expires_at => {
+                self.pending_warp = None;
+                false
+            }
+            Some((target, _))
+                if target.distance_to(position) <= f64::from(self.config.warp_tolerance) =>
+            {
+                self.pending_warp = None;
+                true
+            }
+            _ => false,
+        }
+    }
 
-        ctx.system.publish(GameEvent::PlayerJoined {
-            player_id: self.player_id.clone(),
-            wallet: self.wallet.clone(),
-            position: self.position.clone(),
-        });
-        Ok(())
+    /// Where a move is measured from: the position that was authoritative when the client sent
+    /// it, if that was within `reconcile_window_ms`, and the latest position otherwise.
+    fn reconcile_from(&self, msg: &MovePlayer, now: Instant) -> Position {
+        let window = Duration::from_millis(self.config.reconcile_window_ms);
+        msg.client_time_ms
+            .and_then(|client_time_ms| self.latency.server_time(client_time_ms))
+            .filter(|sent| now.saturating_duration_since(*sent) <= window)
+            .and_then(|sent| self.history.at(sent))
+            .unwrap_or(&self.position)
+            .clone()
     }
 
-    async fn post_stop(&mut self, ctx: &mut ActorContext<GameEvent>) {
-        log::debug!(
-            "Player {} ({}) left the game",
-            self.player_id,
-            self.nickname
-        );
+    /// Checks a first move only for landing inside the world within `spawn_grace_radius` of
+    /// the spawn point, since the client can't know where inside the jitter it was placed.
+    fn check_first_move(&self, position: &Position) -> ValidationResult {
+        if !is_in_bounds(position, &self.config.world_bounds) {
+            ValidationResult::OutOfBounds
+        } else if is_teleport(
+            &self.config.spawn_point,
+            position,
+            f64::from(self.config.spawn_grace_radius),
+        ) {
+            ValidationResult::Teleport
+        } else {
+            ValidationResult::Valid
+        }
+    }
 
-        ctx.system.publish(GameEvent::PlayerLeft {
+    fn check_move(
+        &self,
+        from: &Position,
+        position: &Position,
+        msg: &MovePlayer,
+    ) -> ValidationResult {
+        self.validators.check(
+            &MoveContext::new(
+                from,
+                position,
+                &msg.velocity,
+                &self.velocity,
+                msg.delta_time,
+            )
+            .with_max_delta_time(self.config.max_delta_time),
+        )
+    }
+
+    /// Whether the move claims more frame time than the client has left in its budget, as
+    /// when moves are fired faster than their `delta_time` says.
+    ///
+    /// The budget is shared by every move, so a stream of moves each claiming a little too
+    /// much runs it dry instead of passing one by one.
+    fn claims_too_much_time(&mut self, msg: &MovePlayer, now: Instant) -> bool {
+        let elapsed = now
+            .saturating_duration_since(self.time_budget_at)
+            .as_secs_f32();
+        self.time_budget = (self.time_budget + elapsed).min(MAX_TIME_BUDGET_SECS);
+        self.time_budget_at = now;
+
+        let claimed_too_much = msg.delta_time > self.time_budget;
+        if claimed_too_much {
+            log::debug!(
+                "Player {} claimed dt {:.4}s with {:.4}s left",
+                self.player_id,
+                msg.delta_time,
+                self.time_budget
+            );
+        }
+        claimed_too_much
+    }
+
+    fn handle_move(&mut self, msg: MovePlayer, ctx: &mut ActorContext<GameEvent>) {
+        if self.apply_move(msg, ctx) {
+            self.publish_move(ctx);
+        }
+    }
+
+    /// Validates the moves in order against the position each accepted one leaves behind,
+    /// then publishes where the player ended up once.
+    fn handle_move_batch(&mut self, moves: Vec<MovePlayer>, ctx: &mut ActorContext<GameEvent>) {
+        let mut moved = false;
+        for msg in moves {
+            moved |= self.apply_move(msg, ctx);
+            if self.violations >= MAX_VIOLATIONS
+                && self.config.anticheat_mode == AntiCheatMode::Enforce
+            {
+                break;
+            }
+        }
+        if moved {
+            self.publish_move(ctx);
+        }
+    }
+
+    fn publish_move(&self, ctx: &ActorContext<GameEvent>) {
+        self.persist();
+        ctx.system.publish(GameEvent::PlayerMoved {
             player_id: self.player_id.clone(),
+            room: self.room.clone(),
+            position: self.position.clone(),
+            velocity: self.velocity.clone(),
+            latency_ms: self.latency.latency_ms(),
         });
     }
-}
 
-#[async_trait]
-impl Handler<GameEvent, MovePlayer> for PlayerActor {
-    async fn handle(&mut self, msg: MovePlayer, ctx: &mut ActorContext<GameEvent>) {
-        let validation = validate_movement(
-            &self.position,
-            &msg.position,
-            &msg.velocity,
-            msg.delta_time,
-            MAX_SPEED,
-        );
+    /// Runs the anti-cheat checks on a move. Returns the position to accept, snapped onto the
+    /// world's edge if it overshot slightly, the verdict, and whether it was snapped.
+    fn validate_move(
+        &mut self,
+        msg: &MovePlayer,
+        now: Instant,
+    ) -> (Position, ValidationResult, bool) {
+        // NaN fails every comparison, so it would slip past each check below, whatever the mode.
+        if !msg.position.is_finite() || !msg.velocity.is_finite() {
+            return (self.position.clone(), ValidationResult::Invalid, false);
+        }
+        if self.config.anticheat_mode == AntiCheatMode::Disabled {
+            return (msg.position.clone(), ValidationResult::Valid, false);
+        }
+        self.refresh_thresholds();
+        if self.first_move_grace {
+            self.first_move_grace = false;
+            return (
+                msg.position.clone(),
+                self.check_first_move(&msg.position),
+                false,
+            );
+        }
 
-        match validation {
-            ValidationResult::Valid => {
-                self.position = msg.position;
-                self.velocity = msg.velocity;
-                self.last_update = Instant::now();
-                self.violations = 0;
+        let from = self.reconcile_from(msg, now);
+        let mut position = msg.position.clone();
+        let mut validation = self.check_move(&from, &position, msg);
 
-                log::debug!(
-                    "Player {} moved to ({:.2}, {:.2}, {:.2})",
+        // A small overshoot past the edge is snapped back onto it; anything a teleport away
+        // stays rejected.
+        let clamped = validation == ValidationResult::OutOfBounds
+            && !is_teleport(&from, &msg.position, self.thresholds.teleport_threshold);
+        if clamped {
+            position = clamp_to_bounds(&msg.position, &self.config.world_bounds);
+            validation = self.check_move(&from, &position, msg);
+        }
+        if validation == ValidationResult::Teleport && self.take_warp(&msg.position, now) {
+            log::debug!("Player {} warped as allowed", self.player_id);
+            validation = ValidationResult::Valid;
+        }
+        if validation == ValidationResult::Valid && self.claims_too_much_time(msg, now) {
+            validation = ValidationResult::SpeedHack;
+        }
+        (position, validation, clamped)
+    }
+
+    /// Validates one move and, if it passes, makes it the player's position without publishing
+    /// it. Returns whether the move was accepted.
+    fn apply_move(&mut self, msg: MovePlayer, ctx: &ActorContext<GameEvent>) -> bool {
+        let now = Instant::now();
+        if let Some(client_time_ms) = msg.client_time_ms {
+            match self.latency.observe(client_time_ms, now) {
+                Some(sample) => log::trace!(
+                    "Player {} latency sample {:.0}ms, smoothed {}ms",
                     self.player_id,
-                    self.position.x,
-                    self.position.y,
-                    self.position.z
-                );
+                    sample,
+                    self.latency.latency_ms()
+                ),
+                None => {
+                    log::debug!(
+                        "Dropped out-of-order move from player {} stamped {}ms",
+                        self.player_id,
+                        client_time_ms
+                    );
+                    return false;
+                }
+            }
+        }
 
-                ctx.system.publish(GameEvent::PlayerMoved {
-                    player_id: self.player_id.clone(),
-                    position: self.position.clone(),
-                    velocity: self.velocity.clone(),
-                });
+        let (position, validation, clamped) = self.validate_move(&msg, now);
+        self.metrics.record_violation(&validation);
+        self.audit.record(AuditEntry {
+            timestamp_ms: server_time_ms(),
+            from: self.position.clone(),
+            to: msg.position.clone(),
+            result: validation.clone(),
+        });
+
+        // Log-only mode reports the violation below but lets the move through, unless it
+        // isn't a position at all.
+        let accepted = match validation {
+            ValidationResult::Valid => true,
+            ValidationResult::Invalid => false,
+            _ => self.config.anticheat_mode == AntiCheatMode::LogOnly,
+        };
+        match validation {
+            ValidationResult::Valid => {}
+            ValidationResult::Invalid => {
+                self.handle_violation(
+                    ctx,
+                    "INVALID POSITION",
+                    &format!(
+                        "({}, {}, {}) velocity ({}, {}, {})",
+                        msg.position.x,
+                        msg.position.y,
+                        msg.position.z,
+                        msg.velocity.x,
+                        msg.velocity.y,
+                        msg.velocity.z
+                    ),
+                );
+                // Enforce mode has already corrected the client; the others still must.
+                if self.config.anticheat_mode != AntiCheatMode::Enforce {
+                    self.send_correction();
+                }
             }
             ValidationResult::SpeedHack => {
                 self.handle_violation(
+                    ctx,
                     "SPEED HACK",
                     &format!(
                         "({:.2}, {:.2}, {:.2}) -> ({:.2}, {:.2}, {:.2})",
@@ -150,10 +562,39 @@ impl Handler<GameEvent, MovePlayer> for PlayerActor {
             }
             ValidationResult::Teleport => {
                 self.handle_violation(
+                    ctx,
                     "TELEPORT",
                     &format!("Distance: {:.2}", self.position.distance_to(&msg.position)),
                 );
             }
+            ValidationResult::VelocityMismatch => {
+                self.handle_violation(
+                    ctx,
+                    "VELOCITY MISMATCH",
+                    &format!(
+                        "Distance: {:.2}, reported speed: {:.2}, dt: {:.4}",
+                        self.position.distance_to(&msg.position),
+                        msg.velocity.magnitude(),
+                        msg.delta_time
+                    ),
+                );
+            }
+            ValidationResult::ImpossibleAcceleration => {
+                self.handle_violation(
+                    ctx,
+                    "IMPOSSIBLE ACCELERATION",
+                    &format!(
+                        "Velocity ({:.2}, {:.2}, {:.2}) -> ({:.2}, {:.2}, {:.2}), dt: {:.4}",
+                        self.velocity.x,
+                        self.velocity.y,
+                        self.velocity.z,
+                        msg.velocity.x,
+                        msg.velocity.y,
+                        msg.velocity.z,
+                        msg.delta_time
+                    ),
+                );
+            }
             ValidationResult::OutOfBounds => {
                 log::warn!(
                     "Player {} OUT OF BOUNDS: ({:.2}, {:.2}, {:.2})",
@@ -163,40 +604,1377 @@ impl Handler<GameEvent, MovePlayer> for PlayerActor {
                     msg.position.z
                 );
 
-                self.send_to_client(ServerMessage::Error {
-                    message: "Position out of bounds".to_string(),
-                });
+                if self.config.anticheat_mode == AntiCheatMode::Enforce {
+                    self.send_to_client(ServerMessage::Error {
+                        message: "Position out of bounds".to_string(),
+                    });
+                    self.send_correction();
+                }
             }
         }
+
+        if accepted {
+            self.time_budget = (self.time_budget - msg.delta_time).max(0.0);
+            self.position = position;
+            self.velocity = msg.velocity;
+            self.last_update = now;
+            self.history.record(now, self.position.clone());
+            self.decay_violations(self.last_update);
+
+            log::debug!(
+                "Player {} moved to ({:.2}, {:.2}, {:.2})",
+                self.player_id,
+                self.position.x,
+                self.position.y,
+                self.position.z
+            );
+
+            if clamped {
+                log::debug!(
+                    "Player {} clamped from ({:.2}, {:.2}, {:.2})",
+                    self.player_id,
+                    msg.position.x,
+                    msg.position.y,
+                    msg.position.z
+                );
+                self.send_correction();
+            }
+        }
+        accepted
     }
-}
 
-#[async_trait]
-impl Handler<GameEvent, GetState> for PlayerActor {
-    async fn handle(&mut self, _msg: GetState, _ctx: &mut ActorContext<GameEvent>) -> PlayerState {
+    fn handle_kick(&mut self, msg: Kick, ctx: &mut ActorContext<GameEvent>) {
+        self.send_to_client(ServerMessage::Kicked { reason: msg.reason });
+        ctx.stop_self();
+    }
+
+    fn state(&self) -> PlayerState {
         PlayerState {
             player_id: self.player_id.clone(),
             wallet: self.wallet.clone(),
             nickname: self.nickname.clone(),
+            room: self.room.clone(),
             position: self.position.clone(),
             velocity: self.velocity.clone(),
+            health: self.health,
+            latency_ms: self.latency.latency_ms(),
             last_update: self.last_update,
             previous_position: self.position.clone(),
+            extrapolated_secs: 0.0,
             violations: self.violations,
         }
     }
 }
 
 #[async_trait]
-impl Handler<GameEvent, Kick> for PlayerActor {
-    async fn handle(&mut self, msg: Kick, _ctx: &mut ActorContext<GameEvent>) {
-        self.send_to_client(ServerMessage::Kicked { reason: msg.reason });
+impl Actor<GameEvent> for PlayerActor {
+    /// The AFK limit comes from this actor's config, so it is set here rather than through
+    /// the type-wide `timeout()`, which stays `None`.
+    ///
+    /// Any message from the client's socket, including `GetState`, resets the timer. Pongs
+    /// are answered by the socket handler and never reach the actor, so a client that only
+    /// keeps its connection alive still counts as idle.
+    fn idle_timeout(&self) -> Option<Duration> {
+        (self.config.afk_timeout_ms > 0).then(|| Duration::from_millis(self.config.afk_timeout_ms))
+    }
+
+    fn mailbox_capacity(&self) -> Option<usize> {
+        (self.config.player_mailbox_capacity > 0).then_some(self.config.player_mailbox_capacity)
+    }
+
+    async fn on_timeout(&mut self, _ctx: &mut ActorContext<GameEvent>) {
+        log::info!("Player {} kicked for inactivity", self.player_id);
+        self.send_to_client(ServerMessage::Kicked {
+            reason: "idle timeout".to_string(),
+        });
+        self.send_frame(WsMessage::close());
+    }
+
+    async fn pre_start(&mut self, ctx: &mut ActorContext<GameEvent>) -> Result<(), ActorError> {
+        if let Some(store) = &self.store
+            && let Some(saved) = store.load(&self.player_id)
+        {
+            self.position = saved.position;
+            // A restored player doesn't start at spawn, so the spawn grace doesn't apply.
+            self.first_move_grace = false;
+        }
+
+        log::debug!(
+            "Player {} ({}) joined at ({:.2}, {:.2}, {:.2})",
+            self.player_id,
+            self.nickname,
+            self.position.x,
+            self.position.y,
+            self.position.z
+        );
+
+        ctx.system.publish(GameEvent::PlayerJoined {
+            player_id: self.player_id.clone(),
+            wallet: self.wallet.clone(),
+            nickname: self.nickname.clone(),
+            room: self.room.clone(),
+            position: self.position.clone(),
+        });
+        Ok(())
+    }
+
+    async fn post_stop(&mut self, ctx: &mut ActorContext<GameEvent>) {
+        log::debug!(
+            "Player {} ({}) left the game",
+            self.player_id,
+            self.nickname
+        );
+
+        ctx.system.publish(GameEvent::PlayerLeft {
+            player_id: self.player_id.clone(),
+            room: self.room.clone(),
+        });
+
+        // Only updates the store's memory; the server flushes it to disk off the runtime.
+        self.persist();
+    }
+}
+
+#[async_trait]
+impl Handler<GameEvent, PlayerCommand> for PlayerActor {
+    async fn handle(&mut self, cmd: PlayerCommand, ctx: &mut ActorContext<GameEvent>) {
+        match cmd {
+            PlayerCommand::Move(msg) => self.handle_move(msg, ctx),
+            PlayerCommand::MoveBatch(moves) => self.handle_move_batch(moves, ctx),
+            PlayerCommand::Kick(msg) => self.handle_kick(msg, ctx),
+            PlayerCommand::GetState(reply) => reply.send(self.state()),
+            PlayerCommand::GetAudit(reply) => reply.send(self.audit.entries()),
+        }
+        self.stop_if_client_gone(ctx);
+    }
+}
+
+#[async_trait]
+impl Handler<GameEvent, Attack> for PlayerActor {
+    async fn handle(&mut self, msg: Attack, ctx: &mut ActorContext<GameEvent>) {
+        if msg.target_id == self.player_id
+            || self
+                .last_attack
+                .is_some_and(|last| last.elapsed() < ATTACK_COOLDOWN)
+        {
+            return;
+        }
+
+        let Some(target) = ctx
+            .system
+            .get_actor::<PlayerActor>(&player_path(&msg.target_id))
+            .await
+        else {
+            return;
+        };
+
+        // The target checks range against its own position, so neither actor waits on the other.
+        self.last_attack = Some(Instant::now());
+        let _ = target.tell(TakeDamage {
+            attacker_id: self.player_id.clone(),
+            attacker_room: self.room.clone(),
+            attacker_position: self.position.clone(),
+            amount: self.config.attack_damage,
+        });
+    }
+}
+
+#[async_trait]
+impl Handler<GameEvent, TakeDamage> for PlayerActor {
+    async fn handle(&mut self, msg: TakeDamage, ctx: &mut ActorContext<GameEvent>) {
+        if msg.attacker_room != self.room
+            || msg.attacker_position.distance_to(&self.position)
+                > f64::from(self.config.attack_range)
+        {
+            log::debug!(
+                "Ignoring out of range attack on {} by {}",
+                self.player_id,
+                msg.attacker_id
+            );
+            return;
+        }
+
+        // Negative damage would heal, and NaN would poison health for good.
+        if !msg.amount.is_finite() || msg.amount <= 0.0 {
+            log::warn!(
+                "Ignoring attack on {} by {} with damage {}",
+                self.player_id,
+                msg.attacker_id,
+                msg.amount
+            );
+            return;
+        }
+
+        self.health = (self.health - msg.amount).max(0.0);
+        ctx.system.publish(GameEvent::PlayerDamaged {
+            player_id: self.player_id.clone(),
+            amount: msg.amount,
+            remaining: self.health,
+        });
+
+        if self.health <= 0.0 {
+            log::info!(
+                "Player {} eliminated by {}",
+                self.player_id,
+                msg.attacker_id
+            );
+            self.respawn(ctx);
+        }
+    }
+}
+
+#[async_trait]
+impl Handler<GameEvent, AllowWarp> for PlayerActor {
+    async fn handle(&mut self, msg: AllowWarp, _ctx: &mut ActorContext<GameEvent>) {
+        let expires_at = Instant::now() + Duration::from_millis(self.config.warp_timeout_ms);
+        self.send_to_client(ServerMessage::Correction {
+            position: msg.target.clone(),
+            velocity: Position::default(),
+        });
+        self.pending_warp = Some((msg.target, expires_at));
     }
 }
 
 #[async_trait]
-impl Handler<GameEvent, SendMessage> for PlayerActor {
-    async fn handle(&mut self, msg: SendMessage, _ctx: &mut ActorContext<GameEvent>) {
-        let _ = self.ws_sender.send(WsMessage::text(msg.message));
+impl Handler<GameEvent, Announce> for PlayerActor {
+    async fn handle(&mut self, msg: Announce, ctx: &mut ActorContext<GameEvent>) {
+        self.send_to_client(ServerMessage::Announcement { text: msg.text });
+        self.stop_if_client_gone(ctx);
+    }
+}
+
+#[async_trait]
+impl Handler<GameEvent, RebindSocket> for PlayerActor {
+    async fn handle(&mut self, msg: RebindSocket, _ctx: &mut ActorContext<GameEvent>) {
+        log::debug!(
+            "Player {} reattached at ({:.2}, {:.2}, {:.2}) with {} violations",
+            self.player_id,
+            self.position.x,
+            self.position.y,
+            self.position.z,
+            self.violations
+        );
+        self.ws_sender = msg.ws_sender;
+        self.client_gone = false;
+        self.remote_ip = msg.remote_ip;
+        self.latency = LatencyTracker::new(Instant::now());
+    }
+}
+
+#[async_trait]
+impl Handler<GameEvent, SendChat> for PlayerActor {
+    async fn handle(&mut self, msg: SendChat, ctx: &mut ActorContext<GameEvent>) {
+        let text = match sanitize_chat(&msg.text) {
+            Ok(text) => text,
+            Err(message) => {
+                self.send_to_client(ServerMessage::Error { message });
+                self.stop_if_client_gone(ctx);
+                return;
+            }
+        };
+
+        if text.is_empty() {
+            return;
+        }
+
+        ctx.system.publish(GameEvent::ChatSent {
+            player_id: self.player_id.clone(),
+            room: self.room.clone(),
+            nickname: self.nickname.clone(),
+            text,
+        });
+    }
+}
+
+/// Picks a point uniformly within `spawn_jitter` of the spawn point on the x/z plane,
+/// clamped to the world bounds.
+fn spawn_position(config: &ServerConfig, rng: &mut impl Rng) -> Position {
+    let radius = f64::from(config.spawn_jitter) * rng.random::<f64>().sqrt();
+    let angle = std::f64::consts::TAU * rng.random::<f64>();
+    let spawn = &config.spawn_point;
+    let bounds = &config.world_bounds;
+
+    Position::new(
+        (spawn.x + radius * angle.cos()).clamp(bounds.min.x, bounds.max.x),
+        spawn.y.clamp(bounds.min.y, bounds.max.y),
+        (spawn.z + radius * angle.sin()).clamp(bounds.min.z, bounds.max.z),
+    )
+}
+
+/// Turns control characters into spaces, collapses runs of whitespace and trims the ends,
+/// rejecting oversized messages.
+fn sanitize_chat(text: &str) -> Result<String, String> {
+    let spaced: String = text
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    let cleaned = spaced.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if cleaned.chars().count() > MAX_CHAT_LENGTH {
+        return Err(format!(
+            "Chat message too long (max {} characters)",
+            MAX_CHAT_LENGTH
+        ));
+    }
+
+    Ok(cleaned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor_system::{ActorRef, ActorSystem, EventBus, EventBusStats};
+    use crate::network::outbound_channel;
+    use crate::player::JsonFileStore;
+    use crate::types::WORLD_BOUNDS;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_player_path_follows_naming_convention() {
+        let path = player_path("abc");
+        assert_eq!(path.to_string(), "/user/player-abc");
+        assert_eq!(path, ActorPath::from("/user") / "player-abc");
+        assert_eq!(path, ActorPath::from(path.to_string()));
+        assert_eq!(path.key(), player_actor_name("abc"));
+    }
+
+    #[test]
+    fn test_sanitize_chat_spaces_out_control_characters() {
+        assert_eq!(sanitize_chat("  hi\u{7}\nthere\t ").unwrap(), "hi there");
+        assert_eq!(sanitize_chat("line\r\n\r\nbreak").unwrap(), "line break");
+        assert_eq!(
+            sanitize_chat("too    many   spaces").unwrap(),
+            "too many spaces"
+        );
+    }
+
+    #[test]
+    fn test_sanitize_chat_rejects_oversized() {
+        let text = "a".repeat(MAX_CHAT_LENGTH + 1);
+        assert!(sanitize_chat(&text).is_err());
+        assert!(sanitize_chat(&"a".repeat(MAX_CHAT_LENGTH)).is_ok());
+    }
+
+    fn test_actor(config: ServerConfig) -> (PlayerActor, mpsc::Receiver<WsMessage>) {
+        let (tx, rx) = outbound_channel(config.send_buffer);
+        let actor = PlayerActor::new(
+            "p1".to_string(),
+            "wallet".to_string(),
+            "Nick".to_string(),
+            tx,
+            Arc::new(config),
+            Arc::new(Metrics::new()),
+            &SharedRng::default(),
+        );
+        (actor, rx)
+    }
+
+    async fn get_state(actor_ref: &ActorRef<GameEvent, PlayerActor>) -> PlayerState {
+        let (get_state, state) = PlayerCommand::get_state();
+        actor_ref.tell(get_state).unwrap();
+        state.await.unwrap()
+    }
+
+    #[test]
+    fn test_spawn_position_within_jitter_and_bounds() {
+        let config = ServerConfig {
+            spawn_point: Position::new(100.0, 5.0, -50.0),
+            spawn_jitter: 25.0,
+            ..ServerConfig::default()
+        };
+        for _ in 0..200 {
+            let spawned = spawn_position(&config, &mut rand::rng());
+            assert!(spawned.distance_to(&config.spawn_point) <= 25.0 + 1e-3);
+            assert_eq!(spawned.y, 5.0);
+        }
+
+        let edge = ServerConfig {
+            spawn_point: Position::new(WORLD_BOUNDS, 0.0, 0.0),
+            spawn_jitter: 25.0,
+            ..ServerConfig::default()
+        };
+        for _ in 0..200 {
+            assert!(spawn_position(&edge, &mut rand::rng()).x <= WORLD_BOUNDS);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_alternating_hacks_accumulate_to_kick() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (actor, mut rx) = test_actor(ServerConfig::default());
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        for step in 1..=MAX_VIOLATIONS {
+            let x = step as f64 * 0.2;
+            let clean = MovePlayer {
+                position: Position::new(x, 0.0, 0.0),
+                velocity: Position::new(10.0, 0.0, 0.0),
+                delta_time: 0.02,
+                client_time_ms: None,
+            };
+            let teleport = MovePlayer {
+                position: Position::new(x + 350.0, 0.0, 0.0),
+                ..clean.clone()
+            };
+            actor_ref.tell(PlayerCommand::Move(teleport)).unwrap();
+            actor_ref.tell(PlayerCommand::Move(clean)).unwrap();
+        }
+
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.violations, MAX_VIOLATIONS);
+
+        let mut kicked = false;
+        while let Ok(frame) = rx.try_recv() {
+            kicked |= frame.to_str().is_ok_and(|text| text.contains("Kicked"));
+        }
+        assert!(kicked);
+    }
+
+    #[tokio::test]
+    async fn test_anticheat_kick_bans_address() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let bans = BanList::new();
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let (actor, _rx) = test_actor(ServerConfig {
+            auto_ban_secs: 60,
+            ..ServerConfig::default()
+        });
+        let mut actor = actor.with_bans(bans.clone());
+        actor.remote_ip = Some(ip);
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        for _ in 0..MAX_VIOLATIONS {
+            actor_ref
+                .tell(PlayerCommand::Move(MovePlayer {
+                    position: Position::new(400.0, 0.0, 0.0),
+                    velocity: Position::new(10.0, 0.0, 0.0),
+                    delta_time: 0.1,
+                    client_time_ms: None,
+                }))
+                .unwrap();
+        }
+        get_state(&actor_ref).await;
+
+        assert!(bans.is_banned(&ip));
+    }
+
+    #[test]
+    fn test_mailbox_capacity_follows_config() {
+        let (actor, _rx) = test_actor(ServerConfig {
+            player_mailbox_capacity: 8,
+            ..ServerConfig::default()
+        });
+        assert_eq!(actor.mailbox_capacity(), Some(8));
+
+        let (actor, _rx) = test_actor(ServerConfig::default());
+        assert_eq!(actor.mailbox_capacity(), None);
+    }
+
+    fn spawn_grace_config() -> ServerConfig {
+        ServerConfig {
+            spawn_point: Position::new(100.0, 0.0, -50.0),
+            spawn_jitter: 20.0,
+            spawn_grace_radius: 30.0,
+            ..ServerConfig::default()
+        }
+    }
+
+    fn quick_move(position: Position) -> PlayerCommand {
+        PlayerCommand::Move(MovePlayer {
+            position,
+            velocity: Position::default(),
+            delta_time: 0.016,
+            client_time_ms: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_first_move_near_spawn_skips_speed_checks() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (actor, _rx) = test_actor(spawn_grace_config());
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        // Far more ground than one 16ms frame allows, but inside the spawn area.
+        let near_spawn = Position::new(125.0, 0.0, -50.0);
+        actor_ref.ask(quick_move(near_spawn.clone())).await.unwrap();
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.position, near_spawn);
+        assert_eq!(state.violations, 0);
+
+        // Full validation from the second move on.
+        actor_ref
+            .ask(quick_move(Position::new(150.0, 0.0, -50.0)))
+            .await
+            .unwrap();
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.position, near_spawn);
+        assert_eq!(state.violations, 1);
+    }
+
+    #[tokio::test]
+    async fn test_first_move_far_from_spawn_rejected() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (actor, _rx) = test_actor(spawn_grace_config());
+        let spawned = actor.position.clone();
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        actor_ref
+            .ask(quick_move(Position::new(100.0, 0.0, 10.0)))
+            .await
+            .unwrap();
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.position, spawned);
+        assert_eq!(state.violations, 1);
+    }
+
+    #[tokio::test]
+    async fn test_speed_hack_publishes_one_violation() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let mut events = system.events();
+        let (mut actor, _rx) = test_actor(ServerConfig::default());
+        actor.position = Position::default();
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        actor_ref
+            .ask(PlayerCommand::Move(MovePlayer {
+                position: Position::new(50.0, 0.0, 0.0),
+                velocity: Position::new(10.0, 0.0, 0.0),
+                delta_time: 0.1,
+                client_time_ms: None,
+            }))
+            .await
+            .unwrap();
+
+        let violations: Vec<(String, u32)> = std::iter::from_fn(|| events.try_recv().ok())
+            .filter_map(|event| match event {
+                GameEvent::ViolationDetected { kind, count, .. } => Some((kind, count)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(violations, vec![("speed_hack".to_string(), 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_log_only_speed_hack_moves_but_records_violation() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let mut events = system.events();
+        let metrics = Arc::new(Metrics::new());
+        let (tx, mut rx) = outbound_channel(1024);
+        let mut actor = PlayerActor::new(
+            "p1".to_string(),
+            "wallet".to_string(),
+            "Nick".to_string(),
+            tx,
+            Arc::new(ServerConfig {
+                anticheat_mode: AntiCheatMode::LogOnly,
+                ..ServerConfig::default()
+            }),
+            metrics.clone(),
+            &SharedRng::default(),
+        );
+        actor.position = Position::default();
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        let hacked = Position::new(50.0, 0.0, 0.0);
+        actor_ref
+            .ask(PlayerCommand::Move(MovePlayer {
+                position: hacked.clone(),
+                velocity: Position::new(10.0, 0.0, 0.0),
+                delta_time: 0.1,
+                client_time_ms: None,
+            }))
+            .await
+            .unwrap();
+
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.position, hacked);
+        assert_eq!(state.violations, 1);
+        assert!(
+            metrics
+                .render(0, &[], &[], EventBusStats::default())
+                .contains("anticheat_violations_total{type=\"speed_hack\"} 1")
+        );
+
+        let kinds: Vec<String> = std::iter::from_fn(|| events.try_recv().ok())
+            .filter_map(|event| match event {
+                GameEvent::ViolationDetected { kind, .. } => Some(kind),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(kinds, vec!["speed_hack".to_string()]);
+        // The client is never told.
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_huge_delta_time_clamped_and_flagged_as_speed_hack() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let mut events = system.events();
+        let (mut actor, _rx) = test_actor(ServerConfig::default());
+        actor.position = Position::default();
+        // The client really was silent for longer than it claims, but only a short burst of
+        // frame time is banked meanwhile.
+        actor.time_budget_at = Instant::now() - Duration::from_secs(20);
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        // 10s at full speed would cover 200 units, but only 0.25s is credited.
+        actor_ref
+            .ask(PlayerCommand::Move(MovePlayer {
+                position: Position::new(200.0, 0.0, 0.0),
+                velocity: Position::new(20.0, 0.0, 0.0),
+                delta_time: 10.0,
+                client_time_ms: None,
+            }))
+            .await
+            .unwrap();
+
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.position, Position::default());
+        let kinds: Vec<String> = std::iter::from_fn(|| events.try_recv().ok())
+            .filter_map(|event| match event {
+                GameEvent::ViolationDetected { kind, .. } => Some(kind),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(kinds, vec!["speed_hack".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_delta_time_beyond_wall_clock_flagged() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (mut actor, _rx) = test_actor(ServerConfig::default());
+        actor.position = Position::default();
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        // Short, plausible steps, but claiming 0.4s between them right after spawning. The
+        // first fits in the budget; the second overdraws it.
+        for x in [2.0, 4.0] {
+            actor_ref
+                .ask(PlayerCommand::Move(MovePlayer {
+                    position: Position::new(x, 0.0, 0.0),
+                    velocity: Position::new(10.0, 0.0, 0.0),
+                    delta_time: 0.2,
+                    client_time_ms: None,
+                }))
+                .await
+                .unwrap();
+        }
+
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.position, Position::new(2.0, 0.0, 0.0));
+        assert_eq!(state.violations, 1);
+    }
+
+    #[tokio::test]
+    async fn test_teleport_sends_correction_to_pre_move_position() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (mut actor, mut rx) = test_actor(ServerConfig::default());
+        actor.position = Position::new(5.0, 0.0, 0.0);
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        actor_ref
+            .ask(PlayerCommand::Move(MovePlayer {
+                position: Position::new(400.0, 0.0, 0.0),
+                velocity: Position::new(10.0, 0.0, 0.0),
+                delta_time: 0.1,
+                client_time_ms: None,
+            }))
+            .await
+            .unwrap();
+
+        let frames: Vec<serde_json::Value> = std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|frame| serde_json::from_str(frame.to_str().unwrap()).unwrap())
+            .collect();
+        let correction = frames
+            .iter()
+            .find(|frame| frame["type"] == "Correction")
+            .expect("no correction sent");
+
+        assert_eq!(
+            correction["position"],
+            serde_json::json!({ "x": 5.0, "y": 0.0, "z": 0.0 })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_non_finite_moves_rejected_with_correction() {
+        for mode in [AntiCheatMode::Enforce, AntiCheatMode::LogOnly] {
+            let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+            let (mut actor, mut rx) = test_actor(ServerConfig {
+                anticheat_mode: mode,
+                ..ServerConfig::default()
+            });
+            actor.position = Position::new(5.0, 0.0, 0.0);
+            let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+            for (position, velocity) in [
+                (Position::new(f64::NAN, 0.0, 0.0), Position::default()),
+                (Position::new(5.0, f64::INFINITY, 0.0), Position::default()),
+                (
+                    Position::new(5.0, 0.0, 0.0),
+                    Position::new(0.0, 0.0, f64::NEG_INFINITY),
+                ),
+            ] {
+                actor_ref
+                    .ask(PlayerCommand::Move(MovePlayer {
+                        position,
+                        velocity,
+                        delta_time: 0.1,
+                        client_time_ms: None,
+                    }))
+                    .await
+                    .unwrap();
+            }
+
+            let state = get_state(&actor_ref).await;
+            assert_eq!(state.position, Position::new(5.0, 0.0, 0.0));
+            assert_eq!(state.velocity, Position::default());
+            assert_eq!(state.violations, 3);
+
+            let corrections = std::iter::from_fn(|| rx.try_recv().ok())
+                .map(|frame| serde_json::from_str::<serde_json::Value>(frame.to_str().unwrap()))
+                .filter(|frame| frame.as_ref().unwrap()["type"] == "Correction")
+                .count();
+            assert_eq!(corrections, 3, "{:?}", mode);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_allowed_warp_passes_once_and_teleports_still_fail() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (mut actor, _rx) = test_actor(ServerConfig::default());
+        actor.position = Position::default();
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        let jump = |x: f64| {
+            PlayerCommand::Move(MovePlayer {
+                position: Position::new(x, 0.0, 0.0),
+                velocity: Position::default(),
+                delta_time: 0.1,
+                client_time_ms: None,
+            })
+        };
+
+        actor_ref
+            .ask(AllowWarp {
+                target: Position::new(500.0, 0.0, 0.0),
+            })
+            .await
+            .unwrap();
+        // Far from the warp target: still a teleport, and the warp stays pending.
+        actor_ref.ask(jump(-500.0)).await.unwrap();
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.position, Position::default());
+        assert_eq!(state.violations, 1);
+
+        actor_ref.ask(jump(502.0)).await.unwrap();
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.position, Position::new(502.0, 0.0, 0.0));
+        assert_eq!(state.violations, 1);
+
+        // The warp was used up.
+        actor_ref.ask(jump(0.0)).await.unwrap();
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.position, Position::new(502.0, 0.0, 0.0));
+        assert_eq!(state.violations, 2);
+    }
+
+    #[tokio::test]
+    async fn test_lapsed_warp_no_longer_allows_teleport() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (mut actor, _rx) = test_actor(ServerConfig::default());
+        actor.position = Position::default();
+        actor.pending_warp = Some((
+            Position::new(500.0, 0.0, 0.0),
+            Instant::now() - Duration::from_millis(1),
+        ));
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        actor_ref
+            .ask(PlayerCommand::Move(MovePlayer {
+                position: Position::new(500.0, 0.0, 0.0),
+                velocity: Position::default(),
+                delta_time: 0.1,
+                client_time_ms: None,
+            }))
+            .await
+            .unwrap();
+
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.position, Position::default());
+        assert_eq!(state.violations, 1);
+    }
+
+    #[tokio::test]
+    async fn test_slightly_out_of_bounds_move_is_clamped_and_accepted() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (mut actor, mut rx) = test_actor(ServerConfig::default());
+        actor.position = Position::new(WORLD_BOUNDS - 0.5, 0.0, 0.0);
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        actor_ref
+            .ask(PlayerCommand::Move(MovePlayer {
+                position: Position::new(WORLD_BOUNDS + 0.5, 0.0, 0.0),
+                velocity: Position::new(10.0, 0.0, 0.0),
+                delta_time: 0.1,
+                client_time_ms: None,
+            }))
+            .await
+            .unwrap();
+
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.position, Position::new(WORLD_BOUNDS, 0.0, 0.0));
+        assert_eq!(state.violations, 0);
+
+        let frames: Vec<serde_json::Value> = std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|frame| serde_json::from_str(frame.to_str().unwrap()).unwrap())
+            .collect();
+        assert!(frames.iter().all(|frame| frame["type"] != "Error"));
+        let correction = frames
+            .iter()
+            .find(|frame| frame["type"] == "Correction")
+            .expect("no correction sent");
+        assert_eq!(correction["position"]["x"], serde_json::json!(WORLD_BOUNDS));
+    }
+
+    #[tokio::test]
+    async fn test_move_batch_flags_teleport_and_accepts_trailing_moves() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let mut events = system.events();
+        let (mut actor, _rx) = test_actor(ServerConfig::default());
+        actor.position = Position::default();
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        let step = |x: f64| MovePlayer {
+            position: Position::new(x, 0.0, 0.0),
+            velocity: Position::new(10.0, 0.0, 0.0),
+            delta_time: 0.05,
+            client_time_ms: None,
+        };
+        actor_ref
+            .ask(PlayerCommand::MoveBatch(vec![
+                step(0.5),
+                step(400.0),
+                step(1.0),
+                step(1.5),
+            ]))
+            .await
+            .unwrap();
+
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.position, Position::new(1.5, 0.0, 0.0));
+        assert_eq!(state.violations, 1);
+
+        let (mut moved, mut violations) = (Vec::new(), Vec::new());
+        while let Ok(event) = events.try_recv() {
+            match event {
+                GameEvent::PlayerMoved { position, .. } => moved.push(position),
+                GameEvent::ViolationDetected { kind, .. } => violations.push(kind),
+                _ => {}
+            }
+        }
+        assert_eq!(moved, vec![Position::new(1.5, 0.0, 0.0)]);
+        assert_eq!(violations, vec!["teleport".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_lowered_max_speed_applies_to_next_move() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let mut events = system.events();
+        let anticheat = AntiCheatConfig::shared(&ServerConfig::default());
+        let (mut actor, _rx) = test_actor(ServerConfig::default());
+        actor.position = Position::default();
+        let actor = actor.with_anticheat(anticheat.clone());
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        let step = |x: f64| {
+            PlayerCommand::Move(MovePlayer {
+                position: Position::new(x, 0.0, 0.0),
+                velocity: Position::new(10.0, 0.0, 0.0),
+                delta_time: 0.1,
+                client_time_ms: None,
+            })
+        };
+        actor_ref.ask(step(1.0)).await.unwrap();
+
+        anticheat.write().unwrap().max_speed = 1.0;
+        actor_ref.ask(step(2.0)).await.unwrap();
+
+        let violations: Vec<String> = std::iter::from_fn(|| events.try_recv().ok())
+            .filter_map(|event| match event {
+                GameEvent::ViolationDetected { kind, .. } => Some(kind),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(violations, vec!["speed_hack".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_custom_validator_chain_replaces_standard_checks() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (actor, _rx) = test_actor(ServerConfig::default());
+        let actor = actor.with_validators(ValidatorChain::default());
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        actor_ref
+            .tell(PlayerCommand::Move(MovePlayer {
+                position: Position::new(400.0, 0.0, 0.0),
+                velocity: Position::default(),
+                delta_time: 0.1,
+                client_time_ms: None,
+            }))
+            .unwrap();
+
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.position, Position::new(400.0, 0.0, 0.0));
+        assert_eq!(state.violations, 0);
+    }
+
+    #[tokio::test]
+    async fn test_late_move_validated_against_history_within_window() {
+        for (reconcile_window_ms, expected_violations) in [(500, 0), (0, 1)] {
+            let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+            let (mut actor, _rx) = test_actor(ServerConfig {
+                reconcile_history: 8,
+                reconcile_window_ms,
+                ..ServerConfig::default()
+            });
+
+            // The client sent its move 300ms ago from the origin; the server has since
+            // accepted an earlier-queued move out to x = 20.
+            let now = Instant::now();
+            let sent = now - Duration::from_millis(300);
+            actor.latency = LatencyTracker::new(now - Duration::from_secs(1));
+            actor.latency.observe(10_000, sent);
+            actor.history.record(sent, Position::default());
+            actor.history.record(
+                now - Duration::from_millis(50),
+                Position::new(20.0, 0.0, 0.0),
+            );
+            actor.position = Position::new(20.0, 0.0, 0.0);
+            let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+            actor_ref
+                .ask(PlayerCommand::Move(MovePlayer {
+                    position: Position::new(1.0, 0.0, 0.0),
+                    velocity: Position::new(10.0, 0.0, 0.0),
+                    delta_time: 0.1,
+                    client_time_ms: Some(10_000),
+                }))
+                .await
+                .unwrap();
+
+            let state = get_state(&actor_ref).await;
+            assert_eq!(state.violations, expected_violations);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_move_with_older_timestamp_dropped() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (mut actor, _rx) = test_actor(ServerConfig::default());
+        actor.position = Position::default();
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        let step = |x: f64, client_time_ms: u64| MovePlayer {
+            position: Position::new(x, 0.0, 0.0),
+            velocity: Position::new(10.0, 0.0, 0.0),
+            delta_time: 0.1,
+            client_time_ms: Some(client_time_ms),
+        };
+        actor_ref
+            .tell(PlayerCommand::Move(step(1.0, 2_000)))
+            .unwrap();
+        actor_ref
+            .tell(PlayerCommand::Move(step(2.0, 1_900)))
+            .unwrap();
+
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.position, Position::new(1.0, 0.0, 0.0));
+        assert_eq!(state.violations, 0);
+    }
+
+    #[test]
+    fn test_violations_decay_per_interval() {
+        let (mut actor, _rx) = test_actor(ServerConfig {
+            violation_decay_secs: 10,
+            ..ServerConfig::default()
+        });
+        let start = Instant::now();
+        actor.violations = 3;
+        actor.clean_since = start;
+
+        actor.decay_violations(start + Duration::from_secs(9));
+        assert_eq!(actor.violations, 3);
+
+        actor.decay_violations(start + Duration::from_secs(25));
+        assert_eq!(actor.violations, 1);
+
+        // The leftover 5s still counts toward the next decay.
+        actor.decay_violations(start + Duration::from_secs(30));
+        assert_eq!(actor.violations, 0);
+    }
+
+    #[tokio::test]
+    async fn test_position_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("players.json");
+        let (tx, _rx) = outbound_channel(1024);
+
+        let store: Arc<dyn PlayerStore> = Arc::new(JsonFileStore::open(&path).unwrap());
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let actor = PlayerActor::new(
+            "p1".to_string(),
+            "wallet".to_string(),
+            "Nick".to_string(),
+            tx.clone(),
+            Arc::new(ServerConfig::default()),
+            Arc::new(Metrics::new()),
+            &SharedRng::default(),
+        )
+        .with_store(Some(store.clone()));
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+        actor_ref
+            .tell(PlayerCommand::Move(MovePlayer {
+                position: Position::new(1.0, 0.0, 0.0),
+                velocity: Position::new(10.0, 0.0, 0.0),
+                delta_time: 0.1,
+                client_time_ms: None,
+            }))
+            .unwrap();
+        get_state(&actor_ref).await;
+        system.stop_all().await;
+        store.flush().unwrap();
+
+        // A fresh store and system stand in for the restarted server. The position was saved
+        // under the player id, apart from the wallet's other characters.
+        let store: Arc<dyn PlayerStore> = Arc::new(JsonFileStore::open(&path).unwrap());
+        assert_eq!(store.load("wallet"), None);
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let actor = PlayerActor::new(
+            "p1".to_string(),
+            "wallet".to_string(),
+            "Nick".to_string(),
+            tx,
+            Arc::new(ServerConfig::default()),
+            Arc::new(Metrics::new()),
+            &SharedRng::default(),
+        )
+        .with_store(Some(store));
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.position, Position::new(1.0, 0.0, 0.0));
+    }
+
+    #[tokio::test]
+    async fn test_rebind_socket_keeps_state() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (old_tx, _old_rx) = outbound_channel(1024);
+        let (new_tx, mut new_rx) = outbound_channel(1024);
+
+        let mut actor = PlayerActor::new(
+            "p1".to_string(),
+            "wallet".to_string(),
+            "Nick".to_string(),
+            old_tx,
+            Arc::new(ServerConfig::default()),
+            Arc::new(Metrics::new()),
+            &SharedRng::default(),
+        );
+        actor.position = Position::new(10.0, 0.0, 5.0);
+        actor.violations = 3;
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        actor_ref
+            .tell(RebindSocket {
+                ws_sender: new_tx,
+                remote_ip: None,
+            })
+            .unwrap();
+        actor_ref
+            .tell(Announce {
+                text: "hello".to_string(),
+            })
+            .unwrap();
+
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.position.x, 10.0);
+        assert_eq!(state.violations, 3);
+        let frame = new_rx.recv().await.unwrap();
+        assert!(frame.to_str().unwrap().contains("hello"));
+    }
+
+    #[tokio::test]
+    async fn test_each_command_dispatches() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (mut actor, mut rx) = test_actor(ServerConfig::default());
+        actor.position = Position::default();
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        let moved = Position::new(1.0, 0.0, 0.0);
+        actor_ref
+            .ask(PlayerCommand::Move(MovePlayer {
+                position: moved.clone(),
+                velocity: Position::new(10.0, 0.0, 0.0),
+                delta_time: 0.1,
+                client_time_ms: None,
+            }))
+            .await
+            .unwrap();
+        assert_eq!(get_state(&actor_ref).await.position, moved);
+
+        actor_ref
+            .ask(PlayerCommand::Kick(Kick {
+                reason: "bye".to_string(),
+            }))
+            .await
+            .unwrap();
+        assert!(rx.try_recv().unwrap().to_str().unwrap().contains("Kicked"));
+
+        // The actor stopped, so a state request is never answered.
+        let (get_state, state) = PlayerCommand::get_state();
+        let _ = actor_ref.tell(get_state);
+        assert!(state.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_kick_stops_actor_and_publishes_player_left() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let mut events = system.events();
+        let (actor, mut rx) = test_actor(ServerConfig::default());
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        actor_ref
+            .ask(PlayerCommand::Kick(Kick {
+                reason: "griefing".to_string(),
+            }))
+            .await
+            .unwrap();
+
+        let left = tokio::time::timeout(Duration::from_secs(1), async {
+            while let Ok(event) = events.recv().await {
+                if matches!(event, GameEvent::PlayerLeft { ref player_id, .. } if player_id == "p1")
+                {
+                    return true;
+                }
+            }
+            false
+        })
+        .await
+        .unwrap();
+        assert!(left);
+
+        let released = tokio::time::timeout(Duration::from_secs(1), async {
+            while !system.list_actors().await.is_empty() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await;
+        assert!(released.is_ok());
+
+        let frame = rx.recv().await.unwrap();
+        assert!(frame.to_str().unwrap().contains("Kicked"));
+    }
+
+    #[tokio::test]
+    async fn test_dead_socket_stops_actor_on_next_send() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (actor, rx) = test_actor(ServerConfig {
+            reconnect_grace_ms: 0,
+            ..ServerConfig::default()
+        });
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        // Nothing is sent while the actor only answers queries.
+        drop(rx);
+        get_state(&actor_ref).await;
+        assert!(system.contains(actor_ref.path()).await);
+
+        actor_ref
+            .ask(Announce {
+                text: "hello".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let stopped = tokio::time::timeout(Duration::from_secs(1), async {
+            while system.contains(actor_ref.path()).await {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await;
+        assert!(stopped.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_dead_socket_left_to_grace_reaper() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (actor, rx) = test_actor(ServerConfig::default());
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        drop(rx);
+        for text in ["hello", "anyone?"] {
+            actor_ref
+                .ask(Announce {
+                    text: text.to_string(),
+                })
+                .await
+                .unwrap();
+        }
+        assert!(system.contains(actor_ref.path()).await);
+
+        // The client resumes within the grace period and hears what comes next.
+        let (new_tx, mut new_rx) = outbound_channel(1024);
+        actor_ref
+            .tell(RebindSocket {
+                ws_sender: new_tx,
+                remote_ip: None,
+            })
+            .unwrap();
+        actor_ref
+            .ask(Announce {
+                text: "welcome back".to_string(),
+            })
+            .await
+            .unwrap();
+        let frame = new_rx.recv().await.unwrap();
+        assert!(frame.to_str().unwrap().contains("welcome back"));
+    }
+
+    #[tokio::test]
+    async fn test_idle_actor_times_out() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let mut left =
+            system.events_filtered(|event| matches!(event, GameEvent::PlayerLeft { .. }));
+        let (actor, mut rx) = test_actor(ServerConfig {
+            afk_timeout_ms: 50,
+            ..ServerConfig::default()
+        });
+        system.create_actor("player-p1", actor).await.unwrap();
+
+        let stopped = tokio::time::timeout(Duration::from_secs(1), left.recv()).await;
+        assert!(matches!(stopped, Ok(Some(GameEvent::PlayerLeft { .. }))));
+
+        let kicked = rx.recv().await.unwrap();
+        assert!(kicked.to_str().unwrap().contains("idle timeout"));
+        assert!(rx.recv().await.unwrap().is_close());
+    }
+
+    async fn spawn_pair(
+        system: &ActorSystem<GameEvent>,
+        target_x: f64,
+    ) -> ActorRef<GameEvent, PlayerActor> {
+        let (attacker, _) = test_actor(ServerConfig::default());
+        let attacker_ref = system.create_actor("player-p1", attacker).await.unwrap();
+
+        let (tx, _rx) = outbound_channel(1024);
+        let mut target = PlayerActor::new(
+            "p2".to_string(),
+            "wallet2".to_string(),
+            "Target".to_string(),
+            tx,
+            Arc::new(ServerConfig::default()),
+            Arc::new(Metrics::new()),
+            &SharedRng::default(),
+        );
+        target.position = Position::new(target_x, 0.0, 0.0);
+        let target_ref = system.create_actor("player-p2", target).await.unwrap();
+
+        attacker_ref
+            .ask(Attack {
+                target_id: "p2".to_string(),
+            })
+            .await
+            .unwrap();
+        target_ref
+    }
+
+    #[tokio::test]
+    async fn test_in_range_attack_reduces_health() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let target = spawn_pair(&system, 1.0).await;
+
+        let state = get_state(&target).await;
+        assert_eq!(
+            state.health,
+            MAX_HEALTH - ServerConfig::default().attack_damage
+        );
+    }
+
+    #[tokio::test]
+    async fn test_out_of_range_attack_ignored() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let target = spawn_pair(&system, 50.0).await;
+
+        let state = get_state(&target).await;
+        assert_eq!(state.health, MAX_HEALTH);
+    }
+
+    fn hit(attacker_position: Position, amount: f32) -> TakeDamage {
+        TakeDamage {
+            attacker_id: "p2".to_string(),
+            attacker_room: DEFAULT_ROOM.to_string(),
+            attacker_position,
+            amount,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_negative_damage_ignored() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let (mut actor, _rx) = test_actor(ServerConfig::default());
+        actor.position = Position::default();
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        for amount in [-50.0, f32::NAN] {
+            actor_ref
+                .ask(hit(Position::default(), amount))
+                .await
+                .unwrap();
+        }
+        assert_eq!(get_state(&actor_ref).await.health, MAX_HEALTH);
+    }
+
+    #[tokio::test]
+    async fn test_lethal_damage_respawns_at_full_health() {
+        let system = ActorSystem::new("test", EventBus::<GameEvent>::new(16));
+        let mut events = system.events();
+        let spawn = Position::new(100.0, 0.0, -50.0);
+        let (mut actor, mut rx) = test_actor(ServerConfig {
+            spawn_point: spawn.clone(),
+            ..ServerConfig::default()
+        });
+        let died_at = Position::new(300.0, 0.0, 0.0);
+        actor.position = died_at.clone();
+        let actor_ref = system.create_actor("player-p1", actor).await.unwrap();
+
+        actor_ref.ask(hit(died_at, MAX_HEALTH * 2.0)).await.unwrap();
+
+        let state = get_state(&actor_ref).await;
+        assert_eq!(state.health, MAX_HEALTH);
+        assert_eq!(state.position, spawn);
+        assert!(system.contains(actor_ref.path()).await);
+
+        let frame: serde_json::Value =
+            serde_json::from_str(rx.try_recv().unwrap().to_str().unwrap()).unwrap();
+        assert_eq!(frame["type"], "Correction");
+        assert_eq!(frame["position"]["x"], 100.0);
+
+        let published: Vec<GameEvent> = std::iter::from_fn(|| events.try_recv().ok())
+            .filter(|event| {
+                matches!(
+                    event,
+                    GameEvent::PlayerDamaged { .. } | GameEvent::PlayerRespawned { .. }
+                )
+            })
+            .collect();
+        assert!(matches!(
+            published.as_slice(),
+            [
+                GameEvent::PlayerDamaged { remaining, .. },
+                GameEvent::PlayerRespawned { position, .. },
+            ] if *remaining == 0.0 && *position == spawn
+        ));
     }
 }
@@ -0,0 +1,76 @@
+use crate::anticheat::ValidationResult;
+use crate::types::Position;
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// One move as anti-cheat judged it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AuditEntry {
+    /// Server time the move was handled, in milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    /// Authoritative position before the move.
+    pub from: Position,
+    /// Position the client claimed.
+    pub to: Position,
+    pub result: ValidationResult,
+}
+
+/// A player's most recent moves, so operators can tell a false positive from a cheat.
+pub struct AuditLog {
+    entries: VecDeque<AuditEntry>,
+    capacity: usize,
+}
+
+impl AuditLog {
+    /// Keeps at most `capacity` moves. 0 records nothing.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn record(&mut self, entry: AuditEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// The kept moves, oldest first.
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        self.entries.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(x: f64) -> AuditEntry {
+        AuditEntry {
+            timestamp_ms: x as u64,
+            from: Position::default(),
+            to: Position::new(x, 0.0, 0.0),
+            result: ValidationResult::Valid,
+        }
+    }
+
+    #[test]
+    fn test_drops_oldest_beyond_capacity() {
+        let mut log = AuditLog::new(3);
+        for x in 1..=5 {
+            log.record(entry(x as f64));
+        }
+
+        let kept: Vec<f64> = log.entries().iter().map(|entry| entry.to.x).collect();
+        assert_eq!(kept, [3.0, 4.0, 5.0]);
+
+        let mut disabled = AuditLog::new(0);
+        disabled.record(entry(1.0));
+        assert!(disabled.entries().is_empty());
+    }
+}
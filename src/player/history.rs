@@ -0,0 +1,69 @@
+use crate::types::Position;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// The last few authoritative positions of a player and when each was accepted, so a late
+/// move can be validated against where the player was when the client sent it.
+pub struct PositionHistory {
+    entries: VecDeque<(Instant, Position)>,
+    capacity: usize,
+}
+
+impl PositionHistory {
+    /// Keeps at most `capacity` positions. 0 records nothing.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn record(&mut self, at: Instant, position: Position) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((at, position));
+    }
+
+    /// The position that was authoritative at `at`: the latest one recorded no later than it.
+    /// `None` if `at` predates everything still kept.
+    pub fn at(&self, at: Instant) -> Option<&Position> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(recorded, _)| *recorded <= at)
+            .map(|(_, position)| position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_looks_up_position_at_time() {
+        let start = Instant::now();
+        let mut history = PositionHistory::new(2);
+        for (ms, x) in [(0, 1.0), (100, 2.0), (200, 3.0)] {
+            history.record(
+                start + Duration::from_millis(ms),
+                Position::new(x, 0.0, 0.0),
+            );
+        }
+
+        // The oldest entry was evicted.
+        assert_eq!(history.at(start + Duration::from_millis(50)), None);
+        assert_eq!(
+            history.at(start + Duration::from_millis(150)),
+            Some(&Position::new(2.0, 0.0, 0.0))
+        );
+        assert_eq!(
+            history.at(start + Duration::from_millis(500)),
+            Some(&Position::new(3.0, 0.0, 0.0))
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::{Duration, Instant};
+
+/// Weight of each new sample in the smoothed latency, as in TCP's SRTT.
+const SMOOTHING: f64 = 0.125;
+
+/// Estimates one-way latency from the timestamps clients put on their moves.
+///
+/// Client and server clocks are never synchronized, so delay is measured against the fastest
+/// move seen since connect: the smallest gap between server and client time counts as zero.
+pub struct LatencyTracker {
+    baseline: Instant,
+    last_client_time: Option<u64>,
+    min_offset: Option<i128>,
+    smoothed_ms: f64,
+}
+
+impl LatencyTracker {
+    pub fn new(baseline: Instant) -> Self {
+        Self {
+            baseline,
+            last_client_time: None,
+            min_offset: None,
+            smoothed_ms: 0.0,
+        }
+    }
+
+    /// Records a move stamped `client_time_ms` that arrived at `now`.
+    ///
+    /// Returns the latency sample in milliseconds, or `None` if the timestamp went backward,
+    /// meaning the move was reordered or replayed.
+    pub fn observe(&mut self, client_time_ms: u64, now: Instant) -> Option<f64> {
+        if self
+            .last_client_time
+            .is_some_and(|last| client_time_ms < last)
+        {
+            return None;
+        }
+        self.last_client_time = Some(client_time_ms);
+
+        let server_ms = now.saturating_duration_since(self.baseline).as_millis() as i128;
+        let offset = server_ms - client_time_ms as i128;
+        let min_offset = *self
+            .min_offset
+            .insert(self.min_offset.map_or(offset, |min| min.min(offset)));
+
+        let sample = (offset - min_offset) as f64;
+        self.smoothed_ms += SMOOTHING * (sample - self.smoothed_ms);
+        Some(sample)
+    }
+
+    /// The server time matching `client_time_ms`, taking the fastest move seen as sent and
+    /// received at the same instant. `None` before the first timestamped move.
+    pub fn server_time(&self, client_time_ms: u64) -> Option<Instant> {
+        let server_ms = client_time_ms as i128 + self.min_offset?;
+        u64::try_from(server_ms)
+            .ok()
+            .map(|ms| self.baseline + Duration::from_millis(ms))
+    }
+
+    /// Smoothed one-way latency in milliseconds.
+    pub fn latency_ms(&self) -> u64 {
+        self.smoothed_ms.round() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backward_timestamp_rejected() {
+        let start = Instant::now();
+        let mut tracker = LatencyTracker::new(start);
+
+        assert!(tracker.observe(1_000, start).is_some());
+        assert!(tracker.observe(1_000, start).is_some());
+        assert!(tracker.observe(999, start).is_none());
+        assert!(tracker.observe(1_016, start).is_some());
+    }
+
+    #[test]
+    fn test_latency_measured_against_fastest_move() {
+        let start = Instant::now();
+        let mut tracker = LatencyTracker::new(start);
+
+        assert_eq!(tracker.observe(5_000, start), Some(0.0));
+        // Sent 100ms later but arrived 180ms later: 80ms slower than the first.
+        let sample = tracker.observe(5_100, start + Duration::from_millis(180));
+        assert_eq!(sample, Some(80.0));
+        assert_eq!(tracker.latency_ms(), 10);
+    }
+
+    #[test]
+    fn test_server_time_follows_fastest_move() {
+        let start = Instant::now();
+        let mut tracker = LatencyTracker::new(start);
+        assert_eq!(tracker.server_time(5_000), None);
+
+        tracker.observe(5_000, start + Duration::from_millis(40));
+        tracker.observe(5_100, start + Duration::from_millis(200));
+
+        assert_eq!(
+            tracker.server_time(5_100),
+            Some(start + Duration::from_millis(140))
+        );
+    }
+}
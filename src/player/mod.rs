@@ -1,5 +1,12 @@
 mod actor;
+mod audit;
+mod history;
+mod latency;
 mod state;
+mod store;
 
-pub use actor::PlayerActor;
-pub use state::MovePlayer;
+pub use actor::{PlayerActor, QUERY_TIMEOUT, player_actor_name, player_path};
+pub use state::{
+    AllowWarp, Announce, Attack, Kick, MovePlayer, PlayerCommand, RebindSocket, SendChat,
+};
+pub use store::{JsonFileStore, PlayerStore};
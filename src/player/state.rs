@@ -1,38 +1,145 @@
 use crate::actor_system::Message;
-use crate::types::Position;
+use crate::network::OutboundSender;
+use crate::player::audit::AuditEntry;
+use crate::types::{MoveSample, PlayerState, Position};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
 
 #[derive(Clone, Debug)]
 pub struct MovePlayer {
     pub position: Position,
     pub velocity: Position,
     pub delta_time: f32,
+    pub client_time_ms: Option<u64>,
 }
 
-impl Message for MovePlayer {
+impl From<MoveSample> for MovePlayer {
+    fn from(sample: MoveSample) -> Self {
+        Self {
+            position: sample.position,
+            velocity: sample.velocity,
+            delta_time: sample.delta_time,
+            client_time_ms: sample.client_time_ms,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Kick {
+    pub reason: String,
+}
+
+/// Answer slot for a command that expects one. Clones share the slot; the first reply wins.
+#[derive(Clone, Debug)]
+pub struct Reply<T>(Arc<Mutex<Option<oneshot::Sender<T>>>>);
+
+impl<T> Reply<T> {
+    pub fn channel() -> (Self, oneshot::Receiver<T>) {
+        let (tx, rx) = oneshot::channel();
+        (Self(Arc::new(Mutex::new(Some(tx)))), rx)
+    }
+
+    pub fn send(&self, value: T) {
+        let sender = self.0.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(sender) = sender {
+            let _ = sender.send(value);
+        }
+    }
+}
+
+/// Commands a player actor handles in one exhaustive `match`, so a new variant can't compile
+/// until the actor decides what to do with it.
+#[derive(Clone, Debug)]
+pub enum PlayerCommand {
+    Move(MovePlayer),
+    /// Moves validated in order; violations count individually but only one update is published.
+    MoveBatch(Vec<MovePlayer>),
+    Kick(Kick),
+    GetState(Reply<PlayerState>),
+    /// The player's recent moves, oldest first, for cheat investigations.
+    GetAudit(Reply<Vec<AuditEntry>>),
+}
+
+impl Message for PlayerCommand {
+    type Response = ();
+}
+
+impl PlayerCommand {
+    /// A `GetState` command and the receiver its answer arrives on once it is handled.
+    pub fn get_state() -> (Self, oneshot::Receiver<PlayerState>) {
+        let (reply, state) = Reply::channel();
+        (PlayerCommand::GetState(reply), state)
+    }
+
+    /// A `GetAudit` command and the receiver its answer arrives on once it is handled.
+    pub fn get_audit() -> (Self, oneshot::Receiver<Vec<AuditEntry>>) {
+        let (reply, audit) = Reply::channel();
+        (PlayerCommand::GetAudit(reply), audit)
+    }
+}
+
+/// A server-wide notice shown to every player.
+#[derive(Clone, Debug)]
+pub struct Announce {
+    pub text: String,
+}
+
+impl Message for Announce {
     type Response = ();
 }
 
 #[derive(Clone, Debug)]
-pub struct GetState;
+pub struct SendChat {
+    pub text: String,
+}
 
-impl Message for GetState {
-    type Response = crate::types::PlayerState;
+impl Message for SendChat {
+    type Response = ();
 }
 
+/// Points a detached player actor at a newly connected socket.
 #[derive(Clone, Debug)]
-pub struct Kick {
-    pub reason: String,
+pub struct RebindSocket {
+    pub ws_sender: OutboundSender,
+    pub remote_ip: Option<IpAddr>,
+}
+
+impl Message for RebindSocket {
+    type Response = ();
+}
+
+/// Sent by a client to hit another player.
+#[derive(Clone, Debug)]
+pub struct Attack {
+    pub target_id: String,
+}
+
+impl Message for Attack {
+    type Response = ();
+}
+
+/// Delivered to the target of an attack, which checks range against its own position.
+#[derive(Clone, Debug)]
+pub struct TakeDamage {
+    pub attacker_id: String,
+    pub attacker_room: String,
+    pub attacker_position: Position,
+    pub amount: f32,
 }
 
-impl Message for Kick {
+impl Message for TakeDamage {
     type Response = ();
 }
 
+/// Exempts the player's next move from the teleport check if it lands near `target`, for
+/// teleporters and respawns that legitimately move players far. The client is sent a
+/// `Correction` to `target` so it actually goes there.
 #[derive(Clone, Debug)]
-pub struct SendMessage {
-    pub message: String,
+pub struct AllowWarp {
+    pub target: Position,
 }
 
-impl Message for SendMessage {
+impl Message for AllowWarp {
     type Response = ();
 }
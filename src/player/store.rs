@@ -0,0 +1,112 @@
+use crate::types::Position;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The part of a player's state that survives a server restart.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PersistedState {
+    pub position: Position,
+}
+
+/// Storage for player state, keyed by player id, so each character of a wallet keeps its own.
+pub trait PlayerStore: Send + Sync {
+    fn load(&self, player_id: &str) -> Option<PersistedState>;
+
+    fn save(&self, player_id: &str, state: PersistedState);
+
+    /// Writes saved states to durable storage. May block on file I/O, so call it off the async
+    /// runtime, e.g. through `spawn_blocking`.
+    fn flush(&self) -> io::Result<()>;
+}
+
+/// Keeps states in memory and writes them all to a single JSON file on `flush`.
+pub struct JsonFileStore {
+    path: PathBuf,
+    states: DashMap<String, PersistedState>,
+    dirty: AtomicBool,
+    flush_lock: Mutex<()>,
+}
+
+impl JsonFileStore {
+    /// Opens the store at `path`, starting empty if the file does not exist yet.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let states: HashMap<String, PersistedState> = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(io::Error::other)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            path,
+            states: states.into_iter().collect(),
+            dirty: AtomicBool::new(false),
+            flush_lock: Mutex::new(()),
+        })
+    }
+}
+
+impl PlayerStore for JsonFileStore {
+    fn load(&self, player_id: &str) -> Option<PersistedState> {
+        self.states.get(player_id).map(|state| state.clone())
+    }
+
+    fn save(&self, player_id: &str, state: PersistedState) {
+        self.states.insert(player_id.to_string(), state);
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let _guard = self.flush_lock.lock().unwrap_or_else(|e| e.into_inner());
+        if !self.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let snapshot: HashMap<String, PersistedState> = self
+            .states
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+
+        // Write a sibling file and rename it over the old one so a crash mid-write
+        // leaves the previous copy intact.
+        let tmp = self.path.with_extension("tmp");
+        let result = serde_json::to_vec(&snapshot)
+            .map_err(io::Error::other)
+            .and_then(|json| std::fs::write(&tmp, json))
+            .and_then(|_| std::fs::rename(&tmp, &self.path));
+
+        if result.is_err() {
+            self.dirty.store(true, Ordering::SeqCst);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("players.json");
+
+        let store = JsonFileStore::open(&path).unwrap();
+        assert_eq!(store.load("wallet"), None);
+
+        let state = PersistedState {
+            position: Position::new(1.0, 2.0, 3.0),
+        };
+        store.save("wallet", state.clone());
+        store.flush().unwrap();
+
+        let reopened = JsonFileStore::open(&path).unwrap();
+        assert_eq!(reopened.load("wallet"), Some(state));
+    }
+}
@@ -0,0 +1,206 @@
+use std::io;
+use std::path::Path;
+
+/// Stand-ins players use for letters, and the letter each one replaces.
+const LEET_SUBSTITUTIONS: &[(char, char)] = &[
+    ('0', 'o'),
+    ('1', 'i'),
+    ('3', 'e'),
+    ('4', 'a'),
+    ('5', 's'),
+    ('7', 't'),
+    ('8', 'b'),
+    ('9', 'g'),
+    ('@', 'a'),
+    ('$', 's'),
+    ('!', 'i'),
+];
+
+/// Skipped while matching, so `b.a.d` or `b_a_d` doesn't get past `bad`.
+const SEPARATORS: &[char] = &['_', '-', '.'];
+
+/// Matches text against an operator-supplied list of blocked words, ignoring case, separators
+/// and simple leet substitutions.
+///
+/// Only whole words match: a listed word must not have a letter or digit directly before or
+/// after it, so `ass` blocks `ass_hat` but not `Cassandra`. A filter without words is
+/// disabled and lets everything through.
+#[derive(Debug, Default)]
+pub struct ProfanityFilter {
+    words: Vec<Vec<char>>,
+}
+
+impl ProfanityFilter {
+    pub fn new<S: AsRef<str>>(words: impl IntoIterator<Item = S>) -> Self {
+        let words = words
+            .into_iter()
+            .map(|word| {
+                normalize(word.as_ref())
+                    .into_iter()
+                    .map(|(c, _)| c)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|word| !word.is_empty())
+            .collect();
+        Self { words }
+    }
+
+    /// Reads one word per line. Blank lines and lines starting with `#` are skipped.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(Self::new(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#')),
+        ))
+    }
+
+    /// Loads the list at `path`. Without a configured or existing list, filtering is disabled
+    /// with a warning rather than failing startup.
+    pub fn from_config(path: Option<&str>) -> io::Result<Self> {
+        let Some(path) = path else {
+            log::warn!("No nickname word list configured, profanity filtering is disabled");
+            return Ok(Self::default());
+        };
+
+        match Self::load(path) {
+            Ok(filter) => {
+                log::info!("Loaded {} blocked words from {}", filter.words.len(), path);
+                Ok(filter)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                log::warn!(
+                    "Nickname word list {} not found, profanity filtering is disabled",
+                    path
+                );
+                Ok(Self::default())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.words.is_empty()
+    }
+
+    /// Checks if `text` contains any blocked word.
+    pub fn is_blocked(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        let normalized = normalize(text);
+        self.words
+            .iter()
+            .any(|word| matches(&chars, &normalized, word).next().is_some())
+    }
+
+    /// `text` with every blocked word, including separators inside it, replaced by `*`.
+    pub fn censor(&self, text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let normalized = normalize(text);
+        let mut masked = vec![false; chars.len()];
+        for word in &self.words {
+            for window in matches(&chars, &normalized, word) {
+                masked[window[0].1..=window[window.len() - 1].1].fill(true);
+            }
+        }
+
+        chars
+            .into_iter()
+            .zip(masked)
+            .map(|(c, masked)| if masked { '*' } else { c })
+            .collect()
+    }
+}
+
+/// The lowercased letters of `text` with leet substitutions undone and separators dropped,
+/// each paired with its char index in `text`.
+fn normalize(text: &str) -> Vec<(char, usize)> {
+    text.chars()
+        .enumerate()
+        .filter(|(_, c)| !SEPARATORS.contains(c))
+        .map(|(index, c)| {
+            let c = c.to_lowercase().next().unwrap_or(c);
+            let c = LEET_SUBSTITUTIONS
+                .iter()
+                .find(|(from, _)| *from == c)
+                .map_or(c, |(_, to)| *to);
+            (c, index)
+        })
+        .collect()
+}
+
+/// Every run of `normalized` that spells `word` as a whole word of `text`, the chars it was
+/// normalized from.
+fn matches<'a>(
+    text: &'a [char],
+    normalized: &'a [(char, usize)],
+    word: &'a [char],
+) -> impl Iterator<Item = &'a [(char, usize)]> + 'a {
+    let is_boundary = |index: Option<usize>| {
+        index
+            .and_then(|index| text.get(index))
+            .is_none_or(|c| !c.is_alphanumeric())
+    };
+    normalized.windows(word.len()).filter(move |window| {
+        let (first, last) = (window[0].1, window[window.len() - 1].1);
+        window.iter().map(|(c, _)| *c).eq(word.iter().copied())
+            && is_boundary(first.checked_sub(1))
+            && is_boundary(Some(last + 1))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter() -> ProfanityFilter {
+        ProfanityFilter::new(["badword", "Rude"])
+    }
+
+    #[test]
+    fn test_blocks_listed_word_in_any_case() {
+        let filter = filter();
+        assert!(filter.is_blocked("BadWord"));
+        assert!(filter.is_blocked("xx_rude_xx"));
+        assert!(filter.is_blocked("b.a.d-w_o.r.d"));
+    }
+
+    #[test]
+    fn test_allows_clean_name() {
+        let filter = filter();
+        assert!(!filter.is_blocked("Player_1"));
+        assert!(!filter.is_blocked("bad.wolf"));
+        assert!(!ProfanityFilter::default().is_blocked("badword"));
+    }
+
+    #[test]
+    fn test_only_whole_words_match() {
+        let filter = ProfanityFilter::new(["ass"]);
+        assert!(!filter.is_blocked("Cassandra"));
+        assert!(!filter.is_blocked("Passive"));
+        assert!(filter.is_blocked("ass_hat"));
+        assert!(filter.is_blocked("Big.Ass"));
+        assert_eq!(filter.censor("Cassandra.ass"), "Cassandra.***");
+    }
+
+    #[test]
+    fn test_catches_leet_substitutions() {
+        let filter = filter();
+        assert!(filter.is_blocked("b4dw0rd"));
+        assert!(filter.is_blocked("RUD3"));
+        assert_eq!(filter.censor("Mr.R-u-d3_Guy"), "Mr.******_Guy");
+    }
+
+    #[test]
+    fn test_missing_list_disables_filtering() {
+        let path = std::env::temp_dir().join(format!("no-such-list-{}.txt", uuid::Uuid::new_v4()));
+        let filter = ProfanityFilter::from_config(path.to_str()).unwrap();
+        assert!(!filter.is_enabled());
+
+        std::fs::write(&path, "# comment\n\nbadword\n").unwrap();
+        let filter = ProfanityFilter::from_config(path.to_str()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(filter.is_enabled());
+        assert!(filter.is_blocked("badword") && !filter.is_blocked("comment"));
+    }
+}
@@ -0,0 +1,193 @@
+use crate::actor_system::ActorSystem;
+use crate::types::GameEvent;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use tokio::sync::watch;
+use tokio::time::{Duration, Instant};
+
+/// How often buffered lines are written out, bounding what a crash can lose.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// One line of a recording.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// Milliseconds since the recording started.
+    pub elapsed_ms: u64,
+    pub event: GameEvent,
+}
+
+/// Writes every game event to a newline-delimited JSON file, for debugging and cheat
+/// forensics.
+///
+/// Events are serialized on the runtime and written out by a dedicated thread, so slow disks
+/// never stall a runtime worker.
+pub struct EventRecorder {
+    file: File,
+    started: Instant,
+}
+
+impl EventRecorder {
+    /// Starts a recording at `path`, replacing any file already there.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            file: File::create(path)?,
+            started: Instant::now(),
+        })
+    }
+
+    /// Records the system's game events until `stop` fires or the bus closes, then waits for
+    /// the writer thread to write out whatever is still buffered.
+    pub async fn run(self, system: ActorSystem<GameEvent>, mut stop: watch::Receiver<bool>) {
+        let mut events = system.events();
+        // Holding the system would keep the bus open forever.
+        drop(system);
+        let (lines, receiver) = mpsc::channel();
+        let writer = BufWriter::new(self.file);
+        let writer = std::thread::spawn(move || write_lines(writer, receiver));
+
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => {
+                        if !record(&lines, self.started, &event) {
+                            break;
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!("Event recorder lagged, {} events not recorded", skipped);
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = stop.changed() => {
+                    // Keep what was published before the stop.
+                    loop {
+                        match events.try_recv() {
+                            Ok(event) => {
+                                if !record(&lines, self.started, &event) {
+                                    break;
+                                }
+                            }
+                            Err(TryRecvError::Lagged(_)) => {}
+                            Err(_) => break,
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+
+        drop(lines);
+        let _ = tokio::task::spawn_blocking(move || writer.join()).await;
+    }
+}
+
+/// Hands `event` to the writer thread. Returns false once the writer has given up.
+fn record(lines: &mpsc::Sender<String>, started: Instant, event: &GameEvent) -> bool {
+    let line = RecordedEvent {
+        elapsed_ms: started.elapsed().as_millis() as u64,
+        event: event.clone(),
+    };
+    match serde_json::to_string(&line) {
+        Ok(line) => lines.send(line).is_ok(),
+        Err(e) => {
+            log::error!("Failed to serialize recorded event: {}", e);
+            true
+        }
+    }
+}
+
+/// Writes lines until every sender is gone, flushing at least every `FLUSH_INTERVAL`.
+fn write_lines(mut writer: BufWriter<File>, lines: mpsc::Receiver<String>) {
+    let mut last_flush = std::time::Instant::now();
+    loop {
+        let result = match lines.recv_timeout(FLUSH_INTERVAL) {
+            Ok(line) => writeln!(writer, "{}", line).and_then(|_| {
+                if last_flush.elapsed() >= FLUSH_INTERVAL {
+                    last_flush = std::time::Instant::now();
+                    writer.flush()
+                } else {
+                    Ok(())
+                }
+            }),
+            Err(RecvTimeoutError::Timeout) => {
+                last_flush = std::time::Instant::now();
+                writer.flush()
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if let Err(e) = result {
+            log::error!("Stopping event recording: {}", e);
+            return;
+        }
+    }
+
+    if let Err(e) = writer.flush() {
+        log::error!("Failed to flush event recording: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor_system::EventBus;
+    use crate::types::Position;
+
+    #[tokio::test]
+    async fn test_recording_replays_events_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.ndjson");
+        let system = ActorSystem::new("test", EventBus::new(16));
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let recorder = EventRecorder::create(&path).unwrap();
+        let handle = tokio::spawn(recorder.run(system.clone(), stop_rx));
+        tokio::task::yield_now().await;
+
+        let emitted = vec![
+            GameEvent::PlayerJoined {
+                player_id: "p1".to_string(),
+                wallet: "wallet1".to_string(),
+                nickname: "Alice".to_string(),
+                room: "lobby".to_string(),
+                position: Position::new(0.0, 0.0, 0.0),
+            },
+            GameEvent::PlayerMoved {
+                player_id: "p1".to_string(),
+                room: "lobby".to_string(),
+                position: Position::new(1.5, 0.0, -2.0),
+                velocity: Position::new(15.0, 0.0, -20.0),
+                latency_ms: 12,
+            },
+            GameEvent::ViolationDetected {
+                player_id: "p1".to_string(),
+                kind: "SPEED HACK".to_string(),
+                count: 1,
+            },
+            GameEvent::PlayerLeft {
+                player_id: "p1".to_string(),
+                room: "lobby".to_string(),
+            },
+        ];
+        for event in &emitted {
+            system.publish(event.clone());
+        }
+        stop_tx.send(true).unwrap();
+        handle.await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let recorded: Vec<RecordedEvent> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert!(
+            recorded
+                .windows(2)
+                .all(|pair| pair[0].elapsed_ms <= pair[1].elapsed_ms)
+        );
+        let events: Vec<GameEvent> = recorded.into_iter().map(|line| line.event).collect();
+        assert_eq!(events, emitted);
+    }
+}
@@ -0,0 +1,46 @@
+use crate::config::ServerConfig;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::sync::{Arc, Mutex};
+
+/// The random source for debug session ids and spawn jitter, shared by every connection.
+///
+/// Seeded from `debug_seed` in debug mode, so a test harness that connects players in the
+/// same order gets the same players on every run.
+#[derive(Clone)]
+pub struct SharedRng(Arc<Mutex<StdRng>>);
+
+impl SharedRng {
+    pub fn seeded(seed: u64) -> Self {
+        Self(Arc::new(Mutex::new(StdRng::seed_from_u64(seed))))
+    }
+
+    pub fn from_os() -> Self {
+        Self(Arc::new(Mutex::new(StdRng::from_os_rng())))
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Self {
+        match config.debug_seed {
+            Some(seed) if config.debug_mode => {
+                log::warn!("Debug randomness seeded with {}", seed);
+                Self::seeded(seed)
+            }
+            Some(_) => {
+                log::warn!("debug_seed is ignored outside debug mode");
+                Self::from_os()
+            }
+            None => Self::from_os(),
+        }
+    }
+
+    /// Runs `f` with exclusive use of the generator.
+    pub fn with<T>(&self, f: impl FnOnce(&mut StdRng) -> T) -> T {
+        f(&mut self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl Default for SharedRng {
+    fn default() -> Self {
+        Self::from_os()
+    }
+}
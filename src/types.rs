@@ -1,31 +1,56 @@
 use crate::actor_system::SystemEvent;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::net::IpAddr;
 use std::time::Instant;
 
-pub const MAX_SPEED: f32 = 100.0;
-pub const TELEPORT_THRESHOLD: f32 = 300.0;
+pub const MAX_SPEED: f64 = 100.0;
+pub const TELEPORT_THRESHOLD: f64 = 300.0;
 pub const MAX_VIOLATIONS: u32 = 10;
-pub const WORLD_BOUNDS: f32 = 1000.0;
+pub const WORLD_BOUNDS: f64 = 1000.0;
 pub const JWT_EXPIRATION_HOURS: i64 = 24;
+pub const MAX_CHAT_LENGTH: usize = 256;
+pub const MIN_NICKNAME_LENGTH: usize = 2;
+pub const MAX_NICKNAME_LENGTH: usize = 20;
+pub const MAX_ROOM_LENGTH: usize = 32;
+pub const MAX_CHARACTER_ID_LENGTH: usize = 32;
+pub const MAX_HEALTH: f32 = 100.0;
+/// Room players join when they don't ask for one.
+pub const DEFAULT_ROOM: &str = "lobby";
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// A point or vector in world space.
+///
+/// Coordinates are kept and computed on as `f64`, so distances stay accurate near the edges
+/// of large worlds, but travel as `f32` on the wire both ways to keep frames compact.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Position {
-    pub x: f32,
-    pub y: f32,
-    pub z: f32,
+    #[serde(serialize_with = "serialize_f32", deserialize_with = "deserialize_f32")]
+    pub x: f64,
+    #[serde(serialize_with = "serialize_f32", deserialize_with = "deserialize_f32")]
+    pub y: f64,
+    #[serde(serialize_with = "serialize_f32", deserialize_with = "deserialize_f32")]
+    pub z: f64,
 }
 
 impl Position {
-    pub fn new(x: f32, y: f32, z: f32) -> Self {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
         Self { x, y, z }
     }
 
-    pub fn distance_to(&self, other: &Position) -> f32 {
+    pub fn magnitude(&self) -> f64 {
+        (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
+    pub fn distance_to(&self, other: &Position) -> f64 {
         let dx = self.x - other.x;
         let dy = self.y - other.y;
         let dz = self.z - other.z;
         (dx * dx + dy * dy + dz * dz).sqrt()
     }
+
+    /// Checks that no coordinate is NaN or infinite.
+    pub fn is_finite(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite() && self.z.is_finite()
+    }
 }
 
 impl Default for Position {
@@ -38,42 +63,164 @@ impl Default for Position {
     }
 }
 
-#[derive(Clone, Debug)]
+/// Axis-aligned box players must stay inside, inclusive on both ends.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct WorldBounds {
+    pub min: Position,
+    pub max: Position,
+}
+
+impl WorldBounds {
+    /// A cube centred on the origin extending `half_extent` along every axis.
+    pub fn cube(half_extent: f64) -> Self {
+        Self {
+            min: Position::new(-half_extent, -half_extent, -half_extent),
+            max: Position::new(half_extent, half_extent, half_extent),
+        }
+    }
+}
+
+impl Default for WorldBounds {
+    fn default() -> Self {
+        Self::cube(WORLD_BOUNDS)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub enum GameEvent {
     PlayerJoined {
         player_id: String,
         wallet: String,
+        nickname: String,
+        room: String,
         position: Position,
     },
     PlayerMoved {
         player_id: String,
+        room: String,
         position: Position,
         velocity: Position,
+        latency_ms: u64,
     },
     PlayerLeft {
         player_id: String,
+        room: String,
+    },
+    ChatSent {
+        player_id: String,
+        room: String,
+        nickname: String,
+        text: String,
+    },
+    PlayerDamaged {
+        player_id: String,
+        amount: f32,
+        remaining: f32,
+    },
+    /// An eliminated player came back at `position` with full health.
+    PlayerRespawned {
+        player_id: String,
+        position: Position,
+    },
+    /// Anti-cheat rejected a move. `count` is the player's violation total after this one.
+    ViolationDetected {
+        player_id: String,
+        kind: String,
+        count: u32,
     },
 }
 
 impl SystemEvent for GameEvent {}
 
-#[derive(Clone, Debug, Deserialize)]
+/// Unknown fields are ignored; `network::decode_client_message` can reject them instead.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ClientMessage {
     Move {
         position: Position,
         velocity: Position,
         delta_time: f32,
+        /// Client clock reading in milliseconds when the move was sent.
+        #[serde(default)]
+        client_time_ms: Option<u64>,
+    },
+    /// Several moves coalesced by the client, oldest first.
+    MoveBatch {
+        moves: Vec<MoveSample>,
     },
     GetState,
+    Chat {
+        text: String,
+    },
+    Attack {
+        target_id: String,
+    },
+    /// Asks for the server clock; answered with `ServerMessage::TimeSyncReply`.
+    TimeSync {
+        client_send_ms: u64,
+    },
+}
+
+/// One move inside a `ClientMessage::MoveBatch`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MoveSample {
+    pub position: Position,
+    pub velocity: Position,
+    pub delta_time: f32,
+    #[serde(default)]
+    pub client_time_ms: Option<u64>,
 }
 
 #[derive(Clone, Debug, Serialize)]
 #[serde(tag = "type")]
 pub enum ServerMessage {
-    StateUpdate { players: Vec<PlayerState> },
-    Error { message: String },
-    Kicked { reason: String },
+    StateUpdate {
+        players: Vec<PlayerState>,
+    },
+    StateDelta {
+        changed: Vec<PlayerState>,
+        removed: Vec<String>,
+    },
+    InterpUpdate {
+        players: Vec<InterpState>,
+    },
+    SelfState {
+        state: PlayerState,
+    },
+    Correction {
+        position: Position,
+        velocity: Position,
+    },
+    Error {
+        message: String,
+    },
+    Kicked {
+        reason: String,
+    },
+    Chat {
+        from: String,
+        nickname: String,
+        text: String,
+    },
+    PlayerJoined {
+        player_id: String,
+        nickname: String,
+        position: Position,
+    },
+    PlayerLeft {
+        player_id: String,
+    },
+    Announcement {
+        text: String,
+    },
+    /// NTP-style timestamps, in server milliseconds since the Unix epoch, for the client to
+    /// work out its clock offset and round-trip time.
+    TimeSyncReply {
+        client_send_ms: u64,
+        server_recv_ms: u64,
+        server_send_ms: u64,
+    },
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -81,21 +228,124 @@ pub struct PlayerState {
     pub player_id: String,
     pub wallet: String,
     pub nickname: String,
+    pub room: String,
     pub position: Position,
     pub velocity: Position,
-    #[serde(skip)]
+    pub health: f32,
+    /// Smoothed one-way latency estimated from move timestamps.
+    pub latency_ms: u64,
+    /// Sent as `age_ms`, the milliseconds since this state was last updated.
+    #[serde(rename = "age_ms", serialize_with = "serialize_age_ms")]
     pub last_update: Instant,
     #[serde(skip)]
     pub previous_position: Position,
+    /// Seconds the simulation has moved the player on its own since its last accepted move.
+    #[serde(skip)]
+    pub extrapolated_secs: f64,
     pub violations: u32,
 }
 
+/// A player's two most recent authoritative positions, for clients that interpolate.
+#[derive(Clone, Debug, Serialize)]
+pub struct InterpState {
+    pub player_id: String,
+    pub position: Position,
+    pub previous_position: Position,
+    pub velocity: Position,
+    /// Milliseconds since `position` was accepted.
+    pub age_ms: u64,
+}
+
+impl InterpState {
+    pub fn from_state(state: &PlayerState, now: Instant) -> Self {
+        Self {
+            player_id: state.player_id.clone(),
+            position: state.position.clone(),
+            previous_position: state.previous_position.clone(),
+            velocity: state.velocity.clone(),
+            age_ms: now.saturating_duration_since(state.last_update).as_millis() as u64,
+        }
+    }
+}
+
+fn serialize_f32<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f32(*value as f32)
+}
+
+fn deserialize_f32<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    f32::deserialize(deserializer).map(f64::from)
+}
+
+fn serialize_age_ms<S: Serializer>(instant: &Instant, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(instant.elapsed().as_millis() as u64)
+}
+
 #[derive(Debug, Deserialize)]
 pub struct AuthRequest {
     pub wallet_address: String,
     pub signature: String,
     pub message: String,
     pub nickname: String,
+    #[serde(default)]
+    pub room: Option<String>,
+    /// Lets one wallet play several characters at once, each with its own player id.
+    #[serde(default)]
+    pub character_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChallengeQuery {
+    pub wallet: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChallengeResponse {
+    pub nonce: String,
+    pub expires_in: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KickRequest {
+    pub player_id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WarpRequest {
+    pub player_id: String,
+    pub target: Position,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BanRequest {
+    pub ip: IpAddr,
+    /// Falls back to the configured `ban_duration_secs`.
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnbanRequest {
+    pub ip: IpAddr,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnnounceRequest {
+    pub text: String,
+}
+
+/// New anti-cheat thresholds. Fields left out keep their current value.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AntiCheatRequest {
+    pub max_speed: Option<f64>,
+    pub teleport_threshold: Option<f64>,
+    pub speed_tolerance_factor: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub jwt_token: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -110,12 +360,88 @@ pub struct Claims {
     pub wallet_address: String,
     pub player_id: String,
     pub nickname: String,
+    /// `None` for tokens issued before rooms existed; treated as `DEFAULT_ROOM`.
+    #[serde(default)]
+    pub room: Option<String>,
     pub exp: usize,
 }
 
+/// An authenticated session, keyed by player id.
 #[derive(Clone)]
 pub struct SessionInfo {
+    pub wallet: String,
     pub jwt_token: String,
     pub nickname: String,
-    pub created_at: Instant,
+    /// Expiry of `jwt_token`, as a unix timestamp.
+    pub token_exp: usize,
+    /// Id of the `/auth` request that opened the session, so its websocket can be traced to it.
+    pub request_id: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn state(last_update: Instant) -> PlayerState {
+        PlayerState {
+            player_id: "p1".to_string(),
+            wallet: "wallet".to_string(),
+            nickname: "Nick".to_string(),
+            room: DEFAULT_ROOM.to_string(),
+            position: Position::default(),
+            velocity: Position::default(),
+            health: MAX_HEALTH,
+            latency_ms: 0,
+            last_update,
+            previous_position: Position::default(),
+            extrapolated_secs: 0.0,
+            violations: 0,
+        }
+    }
+
+    /// `distance_to` as it was computed with `f32` coordinates.
+    fn f32_distance(a: &Position, b: &Position) -> f32 {
+        let dx = a.x as f32 - b.x as f32;
+        let dy = a.y as f32 - b.y as f32;
+        let dz = a.z as f32 - b.z as f32;
+        (dx * dx + dy * dy + dz * dz).sqrt()
+    }
+
+    #[test]
+    fn test_distance_near_world_edge_more_accurate_than_f32() {
+        let edge = Position::new(WORLD_BOUNDS, WORLD_BOUNDS, -WORLD_BOUNDS);
+        let near = Position::new(
+            WORLD_BOUNDS - 0.001,
+            WORLD_BOUNDS - 0.002,
+            -WORLD_BOUNDS + 0.002,
+        );
+        let exact = 0.003;
+
+        let f64_error = (edge.distance_to(&near) - exact).abs();
+        let f32_error = (f64::from(f32_distance(&edge, &near)) - exact).abs();
+        assert!(f64_error < 1e-9, "f64 error {}", f64_error);
+        assert!(f32_error > 1e-6, "f32 error {}", f32_error);
+    }
+
+    #[test]
+    fn test_position_travels_as_f32() {
+        let json = serde_json::to_string(&Position::new(0.1, -2.5, 1e-3)).unwrap();
+        assert_eq!(json, r#"{"x":0.1,"y":-2.5,"z":0.001}"#);
+
+        let decoded: Position = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.x, f64::from(0.1f32));
+    }
+
+    #[test]
+    fn test_player_state_reports_age_ms() {
+        let fresh = serde_json::to_value(state(Instant::now())).unwrap();
+        let stale =
+            serde_json::to_value(state(Instant::now() - Duration::from_millis(500))).unwrap();
+
+        assert!(fresh["age_ms"].as_u64().unwrap() < 100);
+        assert!(stale["age_ms"].as_u64().unwrap() >= 500);
+        assert!(fresh.get("last_update").is_none());
+        assert!(fresh.get("previous_position").is_none());
+    }
 }